@@ -0,0 +1,305 @@
+//! Command-line arguments
+
+use std::{path, time};
+use gpio::{GpioEdge, GpioValue};
+
+pub const USAGE: &str = "\
+usage: gpio [OPTIONS] COMMAND [ARGS]
+
+commands:
+    get PIN                 print the value of PIN
+    set PIN VALUE           set PIN to VALUE (0, 1, low or high)
+    toggle PIN              invert the value of PIN
+    pulse PIN [--value VALUE] [--width DURATION]
+                            set PIN to VALUE (default high) for DURATION (default 100ms),
+                            then to the inverse
+    watch PIN... [--edge EDGE] [--timeout DURATION] [--count N] [--format FORMAT]
+                            print edges of the pins until N edges were seen, no edge
+                            occurred within DURATION or the tool is interrupted, followed by
+                            a summary; EDGE is rising, falling or both (default), FORMAT is
+                            text (default) or json for one JSON object per line
+    info PIN                print the sysfs attributes of PIN
+    serve [--input PIN]... [--output PIN]...
+                            serve the pins to other processes on the socket until
+                            interrupted, see --socket
+    help                    print this message
+
+options:
+    --backend BACKEND       sysfs (default), dummy or remote for pins served by `gpio serve`
+    --state FILE            file the dummy backend keeps pin values in, defaults to
+                            $GPIO_DUMMY_STATE or gpio-dummy.state in the temporary directory
+    --socket PATH           socket of `gpio serve`, defaults to $GPIO_SOCKET or /run/gpio.sock
+
+Durations are given in us, ms or s, e.g. 250ms; plain numbers are milliseconds.
+
+exit status: 0 on success, 1 on errors, 2 on invalid arguments, 3 if watch timed out
+before seeing N edges";
+
+quick_error! {
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum ArgsError {
+        Missing(what: &'static str) {
+            description("missing argument")
+            display("missing {}", what)
+        }
+        Invalid(what: &'static str, arg: String) {
+            description("invalid argument")
+            display("invalid {}: {}", what, arg)
+        }
+        Unexpected(arg: String) {
+            description("unexpected argument")
+            display("unexpected argument: {}", arg)
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BackendKind {
+    SysFs,
+    Dummy,
+    Remote,
+}
+
+/// Output format of watched edges
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Get(u16),
+    Set(u16, GpioValue),
+    Toggle(u16),
+    Pulse {
+        pin: u16,
+        value: GpioValue,
+        width: time::Duration,
+    },
+    Watch {
+        pins: Vec<u16>,
+        edge: GpioEdge,
+        timeout: Option<time::Duration>,
+        count: Option<u64>,
+        format: Format,
+    },
+    Info(u16),
+    Serve {
+        inputs: Vec<u16>,
+        outputs: Vec<u16>,
+    },
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Args {
+    pub backend: BackendKind,
+    pub state: Option<path::PathBuf>,
+    pub socket: Option<path::PathBuf>,
+    pub command: Command,
+}
+
+fn parse_pin(arg: &str) -> Result<u16, ArgsError> {
+    arg.parse().map_err(|_| ArgsError::Invalid("pin", arg.to_owned()))
+}
+
+fn parse_value(arg: &str) -> Result<GpioValue, ArgsError> {
+    match arg {
+        "0" | "low" => Ok(GpioValue::Low),
+        "1" | "high" => Ok(GpioValue::High),
+        _ => Err(ArgsError::Invalid("value", arg.to_owned())),
+    }
+}
+
+fn parse_edge(arg: &str) -> Result<GpioEdge, ArgsError> {
+    match arg {
+        "rising" => Ok(GpioEdge::Rising),
+        "falling" => Ok(GpioEdge::Falling),
+        "both" => Ok(GpioEdge::Both),
+        _ => Err(ArgsError::Invalid("edge", arg.to_owned())),
+    }
+}
+
+fn parse_format(arg: &str) -> Result<Format, ArgsError> {
+    match arg {
+        "text" => Ok(Format::Text),
+        "json" => Ok(Format::Json),
+        _ => Err(ArgsError::Invalid("format", arg.to_owned())),
+    }
+}
+
+fn parse_duration(arg: &str) -> Result<time::Duration, ArgsError> {
+    let invalid = || ArgsError::Invalid("duration", arg.to_owned());
+    let (number, unit) = match arg.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(index) => arg.split_at(index),
+        None => (arg, "ms"),
+    };
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let secs = match unit {
+        "us" => number / 1e6,
+        "ms" => number / 1e3,
+        "s" => number,
+        _ => return Err(invalid()),
+    };
+    time::Duration::try_from_secs_f64(secs).map_err(|_| invalid())
+}
+
+/// Arguments of a command, options may appear anywhere between its positional arguments
+struct CommandArgs {
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl CommandArgs {
+    fn collect<I>(mut args: I, options: &[&'static str]) -> Result<Self, ArgsError>
+    where
+        I: Iterator<Item = String>,
+    {
+        let mut positional = Vec::new();
+        let mut values = Vec::new();
+        while let Some(arg) = args.next() {
+            match options.iter().find(|&&o| o == arg) {
+                Some(&option) => {
+                    let value = args.next().ok_or(ArgsError::Missing(option))?;
+                    values.push((arg, value));
+                }
+                None if arg.starts_with("--") => return Err(ArgsError::Unexpected(arg)),
+                None => positional.push(arg),
+            }
+        }
+        Ok(CommandArgs {
+            positional,
+            options: values,
+        })
+    }
+
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(o, _)| o == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The values of an option that may be given several times
+    fn options(&self, name: &str) -> impl Iterator<Item = &str> {
+        let name = name.to_owned();
+        self.options
+            .iter()
+            .filter(move |(o, _)| *o == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The positional arguments, which must number `count`
+    fn positional(&mut self, count: usize, what: &'static str) -> Result<Vec<String>, ArgsError> {
+        if self.positional.len() < count {
+            return Err(ArgsError::Missing(what));
+        }
+        if self.positional.len() > count {
+            return Err(ArgsError::Unexpected(self.positional[count].clone()));
+        }
+        Ok(self.positional.drain(..).collect())
+    }
+}
+
+impl Args {
+    /// Parse the arguments following the program name
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, ArgsError> {
+        let mut args = args.into_iter();
+        let mut backend = BackendKind::SysFs;
+        let mut state = None;
+        let mut socket = None;
+
+        let command = loop {
+            let arg = args.next().ok_or(ArgsError::Missing("command"))?;
+            match arg.as_str() {
+                "--backend" => {
+                    backend = match args.next().ok_or(ArgsError::Missing("backend"))?.as_str() {
+                        "sysfs" => BackendKind::SysFs,
+                        "dummy" => BackendKind::Dummy,
+                        "remote" => BackendKind::Remote,
+                        other => return Err(ArgsError::Invalid("backend", other.to_owned())),
+                    }
+                }
+                "--state" => {
+                    state = Some(args.next().ok_or(ArgsError::Missing("state file"))?.into())
+                }
+                "--socket" => {
+                    socket = Some(args.next().ok_or(ArgsError::Missing("socket"))?.into())
+                }
+                "-h" | "--help" => break "help".to_owned(),
+                _ if arg.starts_with("--") => return Err(ArgsError::Unexpected(arg)),
+                _ => break arg,
+            }
+        };
+
+        let command = match command.as_str() {
+            "get" | "toggle" | "info" => {
+                let pin = parse_pin(&CommandArgs::collect(args, &[])?.positional(1, "pin")?[0])?;
+                match command.as_str() {
+                    "get" => Command::Get(pin),
+                    "toggle" => Command::Toggle(pin),
+                    _ => Command::Info(pin),
+                }
+            }
+            "set" => {
+                let mut args = CommandArgs::collect(args, &[])?;
+                let positional = args.positional(2, "pin and value")?;
+                Command::Set(parse_pin(&positional[0])?, parse_value(&positional[1])?)
+            }
+            "pulse" => {
+                let mut args = CommandArgs::collect(args, &["--value", "--width"])?;
+                let pin = parse_pin(&args.positional(1, "pin")?[0])?;
+                Command::Pulse {
+                    pin,
+                    value: args.option("--value").map_or(Ok(GpioValue::High), parse_value)?,
+                    width: args
+                        .option("--width")
+                        .map_or(Ok(time::Duration::from_millis(100)), parse_duration)?,
+                }
+            }
+            "watch" => {
+                let mut args =
+                    CommandArgs::collect(args, &["--edge", "--timeout", "--count", "--format"])?;
+                let count = args.positional.len().max(1);
+                let pins = args
+                    .positional(count, "pin")?
+                    .iter()
+                    .map(|pin| parse_pin(pin))
+                    .collect::<Result<_, _>>()?;
+                Command::Watch {
+                    pins,
+                    edge: args.option("--edge").map_or(Ok(GpioEdge::Both), parse_edge)?,
+                    timeout: args.option("--timeout").map(parse_duration).transpose()?,
+                    count: args
+                        .option("--count")
+                        .map(|n| match n.parse() {
+                            Ok(count) if count > 0 => Ok(count),
+                            _ => Err(ArgsError::Invalid("count", n.to_owned())),
+                        })
+                        .transpose()?,
+                    format: args.option("--format").map_or(Ok(Format::Text), parse_format)?,
+                }
+            }
+            "serve" => {
+                let mut args = CommandArgs::collect(args, &["--input", "--output"])?;
+                args.positional(0, "pin")?;
+                let pins = |option| args.options(option).map(parse_pin).collect::<Result<_, _>>();
+                Command::Serve {
+                    inputs: pins("--input")?,
+                    outputs: pins("--output")?,
+                }
+            }
+            "help" => Command::Help,
+            _ => return Err(ArgsError::Invalid("command", command)),
+        };
+
+        Ok(Args {
+            backend,
+            state,
+            socket,
+            command,
+        })
+    }
+}
@@ -0,0 +1,575 @@
+//! Pin access through sysfs, a dummy backend or a server
+
+use std::{collections, env, fs, io, path, ptr, thread, time};
+use std::io::Write;
+use nix;
+use gpio::{GpioEdge, GpioIn, GpioOut, GpioValue};
+use gpio::dummy::{DummyEdgeIter, DummyError, DummyGpioIn};
+use gpio::remote::{Client, PinMode, RemoteEdgeIter, RemoteError, Server};
+use gpio::sysfs::{self, GpioError, KernelTree, SysFsGpioEdgeIter, SysFsGpioInput, SysFsGpioOutput,
+                  SysFsTree};
+use signal;
+
+/// How often watching dummy or remote pins checks for signals
+pub const SIGNAL_CHECK: time::Duration = time::Duration::from_millis(100);
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum BackendError {
+        Gpio(err: GpioError) {
+            from()
+            description("gpio error")
+            display("{}", err)
+            cause(err)
+        }
+        Dummy(err: DummyError) {
+            from()
+            description("dummy gpio error")
+            display("{}", err)
+        }
+        Remote(err: RemoteError) {
+            from()
+            description("remote gpio error")
+            display("{}", err)
+        }
+        Io(err: io::Error) {
+            from()
+            description("io error")
+            display("I/O error: {}", err)
+            cause(err)
+        }
+        State(path: path::PathBuf, line: usize) {
+            description("invalid dummy state file")
+            display("invalid dummy state file {} in line {}", path.display(), line)
+        }
+        Unsupported(what: &'static str) {
+            description("unsupported by the backend")
+            display("{} is not supported by this backend", what)
+        }
+    }
+}
+
+pub type BackendResult<T> = Result<T, BackendError>;
+
+/// An edge seen while watching pins
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub pin: u16,
+    pub value: GpioValue,
+}
+
+/// Why watching stopped
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WatchEnd {
+    /// The callback asked to stop
+    Stopped,
+    /// No edge occurred within the timeout
+    Timeout,
+    /// SIGINT or SIGTERM was received
+    Interrupted,
+}
+
+pub trait Backend {
+    /// Read the value of `pin`, without reconfiguring it if possible
+    fn get(&self, pin: u16) -> BackendResult<GpioValue>;
+
+    /// Drive `pin` with `value`
+    fn set(&self, pin: u16, value: GpioValue) -> BackendResult<()>;
+
+    /// Drive `pin` with the inverse of its value
+    fn toggle(&self, pin: u16) -> BackendResult<()> {
+        let value = self.get(pin)?;
+        self.set(pin, invert(value))
+    }
+
+    /// Drive `pin` with `value` for `width`, then with the inverse value
+    fn pulse(&self, pin: u16, value: GpioValue, width: time::Duration) -> BackendResult<()>;
+
+    /// Report edges of `pins` to `on_edge` until it returns `false`, the timeout expires or a
+    /// signal is received
+    fn watch(
+        &self,
+        pins: &[u16],
+        edge: GpioEdge,
+        timeout: Option<time::Duration>,
+        on_edge: &mut dyn FnMut(Edge) -> bool,
+    ) -> BackendResult<WatchEnd>;
+
+    /// Describe the configuration of `pin`
+    fn info(&self, pin: u16) -> BackendResult<Vec<(&'static str, String)>>;
+
+    /// A server owning `inputs` and `outputs`
+    fn server(&self, inputs: &[u16], outputs: &[u16]) -> BackendResult<Server>;
+}
+
+fn invert(value: GpioValue) -> GpioValue {
+    match value {
+        GpioValue::Low => GpioValue::High,
+        GpioValue::High => GpioValue::Low,
+    }
+}
+
+fn timeout_ms(timeout: time::Duration) -> u64 {
+    timeout.as_secs() * 1000 + u64::from(timeout.subsec_millis())
+}
+
+/// How long to wait for the next edge before checking for signals again, `None` once `timeout`
+/// has passed since `last_edge`
+fn slice(timeout: Option<time::Duration>, last_edge: time::Instant) -> Option<time::Duration> {
+    match timeout {
+        Some(timeout) => match timeout.checked_sub(last_edge.elapsed()) {
+            Some(remaining) if remaining > time::Duration::from_secs(0) => {
+                Some(remaining.min(SIGNAL_CHECK))
+            }
+            _ => None,
+        },
+        None => Some(SIGNAL_CHECK),
+    }
+}
+
+/// The value of a pin after an edge, known unless watching both edges
+fn edge_value<F: FnOnce() -> BackendResult<GpioValue>>(
+    edge: GpioEdge,
+    read: F,
+) -> BackendResult<GpioValue> {
+    match edge {
+        GpioEdge::Rising => Ok(GpioValue::High),
+        GpioEdge::Falling => Ok(GpioValue::Low),
+        _ => read(),
+    }
+}
+
+/// Pins of the kernel's sysfs interface
+///
+/// Pins that are already exported are used as they are configured, so reading an output does
+/// not turn it into an input and another process keeps its pins. Pins exported by the tool are
+/// unexported again when the command is done.
+#[derive(Debug, Default)]
+pub struct SysFs {
+    tree: KernelTree,
+}
+
+impl SysFs {
+    fn read_attribute(&self, pin: u16, name: &str) -> BackendResult<String> {
+        let data = self.tree.read(&format!("gpio{}/{}", pin, name))?;
+        Ok(String::from_utf8_lossy(&data).trim().to_owned())
+    }
+
+    fn is_exported(&self, pin: u16) -> bool {
+        self.tree.exists(&format!("gpio{}", pin))
+    }
+
+    fn output(&self, pin: u16) -> BackendResult<SysFsGpioOutput> {
+        if self.is_exported(pin) {
+            Ok(SysFsGpioOutput::attach(pin)?)
+        } else {
+            Ok(SysFsGpioOutput::open(pin)?)
+        }
+    }
+
+    fn input(&self, pin: u16) -> BackendResult<SysFsGpioInput> {
+        if self.is_exported(pin) {
+            Ok(SysFsGpioInput::attach(pin)?)
+        } else {
+            Ok(SysFsGpioInput::open(pin)?)
+        }
+    }
+}
+
+impl Backend for SysFs {
+    fn get(&self, pin: u16) -> BackendResult<GpioValue> {
+        if self.is_exported(pin) {
+            // the kernel applies active_low to the value attribute already
+            return Ok(sysfs::inspect_at(&self.tree, pin)?.value);
+        }
+        Ok(SysFsGpioInput::open(pin)?.read_value()?)
+    }
+
+    fn set(&self, pin: u16, value: GpioValue) -> BackendResult<()> {
+        Ok(self.output(pin)?.set_value(value)?)
+    }
+
+    fn toggle(&self, pin: u16) -> BackendResult<()> {
+        if self.is_exported(pin) {
+            let mut gpio = SysFsGpioOutput::attach(pin)?;
+            let value = gpio.info()?.value;
+            return Ok(gpio.set_value(invert(value))?);
+        }
+        // read the pin and drive it through the same export
+        let gpio = SysFsGpioInput::open(pin)?;
+        let value = gpio.read_value()?;
+        Ok(gpio.into_output()?.set_value(invert(value))?)
+    }
+
+    fn pulse(&self, pin: u16, value: GpioValue, width: time::Duration) -> BackendResult<()> {
+        let mut gpio = self.output(pin)?;
+        gpio.set_value(value)?;
+        thread::sleep(width);
+        Ok(gpio.set_value(invert(value))?)
+    }
+
+    fn watch(
+        &self,
+        pins: &[u16],
+        edge: GpioEdge,
+        timeout: Option<time::Duration>,
+        on_edge: &mut dyn FnMut(Edge) -> bool,
+    ) -> BackendResult<WatchEnd> {
+        let mut gpios = Vec::new();
+        for &pin in pins {
+            let mut gpio = self.input(pin)?;
+            gpio.set_edge(edge)?;
+            // reading the value clears the event pending on a freshly opened value file
+            gpio.read_value()?;
+            gpios.push(gpio);
+        }
+
+        let mut iter = SysFsGpioEdgeIter::new()?;
+        if let Some(timeout) = timeout {
+            iter.timeout_ms(timeout_ms(timeout));
+        }
+        for gpio in &gpios {
+            iter.add(gpio)?;
+        }
+
+        for result in iter {
+            let gpio = match result {
+                Ok(gpio) => gpio,
+                Err(GpioError::EpollEventCount(0)) => return Ok(WatchEnd::Timeout),
+                Err(GpioError::Epoll(nix::Error::Sys(nix::errno::Errno::EINTR)))
+                    if signal::interrupted() =>
+                {
+                    return Ok(WatchEnd::Interrupted)
+                }
+                Err(err) => return Err(err.into()),
+            };
+            let value = edge_value(edge, || Ok(gpio.read_value()?))?;
+            if !on_edge(Edge { pin: gpio.gpio_num(), value }) {
+                break;
+            }
+        }
+        Ok(WatchEnd::Stopped)
+    }
+
+    fn info(&self, pin: u16) -> BackendResult<Vec<(&'static str, String)>> {
+        let mut info = vec![("pin", pin.to_string())];
+        if !self.is_exported(pin) {
+            info.push(("exported", "no".to_owned()));
+            return Ok(info);
+        }
+        info.push(("exported", "yes".to_owned()));
+        for &name in &["direction", "value", "edge", "active_low"] {
+            info.push((name, self.read_attribute(pin, name)?));
+        }
+        Ok(info)
+    }
+
+    fn server(&self, inputs: &[u16], outputs: &[u16]) -> BackendResult<Server> {
+        let mut server = Server::new();
+        for &pin in inputs {
+            server = server.input(pin, self.input(pin)?);
+        }
+        for &pin in outputs {
+            server = server.output(pin, self.output(pin)?);
+        }
+        Ok(server)
+    }
+}
+
+/// Dummy pins whose values are kept in a file, so consecutive invocations share them
+///
+/// Every line of the file holds a pin and its value, e.g. `17 1`. Pins missing from the file are
+/// low. Watching polls the file, so edges are caused by other invocations setting pins.
+#[derive(Debug, Clone)]
+pub struct Dummy {
+    path: path::PathBuf,
+}
+
+impl Dummy {
+    /// Use the state file at `path`, `$GPIO_DUMMY_STATE` or one in the temporary directory
+    pub fn new(path: Option<path::PathBuf>) -> Dummy {
+        let path = path
+            .or_else(|| env::var_os("GPIO_DUMMY_STATE").map(path::PathBuf::from))
+            .unwrap_or_else(|| env::temp_dir().join("gpio-dummy.state"));
+        Dummy { path }
+    }
+
+    fn load(&self) -> BackendResult<collections::BTreeMap<u16, GpioValue>> {
+        let data = match fs::read_to_string(&self.path) {
+            Ok(data) => data,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let mut pins = collections::BTreeMap::new();
+        for (index, line) in data.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = || BackendError::State(self.path.clone(), index + 1);
+            let mut fields = line.split_whitespace();
+            let pin = fields.next().and_then(|p| p.parse().ok()).ok_or_else(invalid)?;
+            let value = match fields.next() {
+                Some("0") => GpioValue::Low,
+                Some("1") => GpioValue::High,
+                _ => return Err(invalid()),
+            };
+            pins.insert(pin, value);
+        }
+        Ok(pins)
+    }
+
+    /// Write the state to a temporary file first, so watchers never read a partial state
+    fn store(&self, pins: &collections::BTreeMap<u16, GpioValue>) -> BackendResult<()> {
+        let tmp = self.path.with_extension(format!("tmp{}", std::process::id()));
+        {
+            let mut file = io::BufWriter::new(fs::File::create(&tmp)?);
+            for (pin, &value) in pins {
+                writeln!(file, "{} {}", pin, u8::from(value))?;
+            }
+            file.flush()?;
+        }
+        Ok(fs::rename(&tmp, &self.path)?)
+    }
+
+    fn value(&self, pin: u16) -> BackendResult<GpioValue> {
+        Ok(self.load()?.get(&pin).cloned().unwrap_or(GpioValue::Low))
+    }
+
+    /// An input reading `pin` from the state file
+    fn input(&self, pin: u16) -> BackendResult<DummyGpioIn> {
+        let dummy = self.clone();
+        let last = std::sync::Mutex::new(dummy.value(pin)?);
+        // a state file that is invalid or being replaced does not make the pin fail, it keeps
+        // its last value meanwhile
+        Ok(DummyGpioIn::new(move || {
+            let mut last = last.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+            if let Ok(value) = dummy.value(pin) {
+                *last = value;
+            }
+            *last
+        }))
+    }
+}
+
+/// An output writing a pin of the state file
+#[derive(Debug)]
+struct DummyStateOut {
+    dummy: Dummy,
+    pin: u16,
+}
+
+impl GpioOut for DummyStateOut {
+    type Error = BackendError;
+
+    fn set_low(&mut self) -> BackendResult<()> {
+        self.dummy.set(self.pin, GpioValue::Low)
+    }
+
+    fn set_high(&mut self) -> BackendResult<()> {
+        self.dummy.set(self.pin, GpioValue::High)
+    }
+}
+
+impl Backend for Dummy {
+    fn get(&self, pin: u16) -> BackendResult<GpioValue> {
+        self.value(pin)
+    }
+
+    fn set(&self, pin: u16, value: GpioValue) -> BackendResult<()> {
+        let mut pins = self.load()?;
+        pins.insert(pin, value);
+        self.store(&pins)
+    }
+
+    fn pulse(&self, pin: u16, value: GpioValue, width: time::Duration) -> BackendResult<()> {
+        self.set(pin, value)?;
+        thread::sleep(width);
+        self.set(pin, invert(value))
+    }
+
+    fn watch(
+        &self,
+        pins: &[u16],
+        edge: GpioEdge,
+        timeout: Option<time::Duration>,
+        on_edge: &mut dyn FnMut(Edge) -> bool,
+    ) -> BackendResult<WatchEnd> {
+        let mut gpios = Vec::new();
+        for &pin in pins {
+            let mut gpio = self.input(pin)?;
+            gpio.set_edge(edge)?;
+            gpios.push((pin, gpio));
+        }
+
+        let mut iter = DummyEdgeIter::new()?;
+        for (_, gpio) in &gpios {
+            iter.add(gpio)?;
+        }
+
+        // signals do not interrupt the dummy iterator, so it waits in slices
+        let mut last_edge = time::Instant::now();
+        loop {
+            if signal::interrupted() {
+                return Ok(WatchEnd::Interrupted);
+            }
+            match slice(timeout, last_edge) {
+                Some(slice) => iter.timeout_ms(timeout_ms(slice).max(1)),
+                None => return Ok(WatchEnd::Timeout),
+            };
+            let gpio = match iter.next().expect("edge iterators never end") {
+                Ok(gpio) => gpio,
+                Err(DummyError::Timeout) => continue,
+                Err(err) => return Err(err.into()),
+            };
+            last_edge = time::Instant::now();
+            let pin = gpios
+                .iter()
+                .find(|(_, g)| ptr::eq(g, gpio))
+                .map(|&(pin, _)| pin)
+                .expect("edge of a watched pin");
+            let value = edge_value(edge, || Ok(gpio.read_value()?))?;
+            if !on_edge(Edge { pin, value }) {
+                return Ok(WatchEnd::Stopped);
+            }
+        }
+    }
+
+    fn info(&self, pin: u16) -> BackendResult<Vec<(&'static str, String)>> {
+        Ok(vec![
+            ("pin", pin.to_string()),
+            ("backend", "dummy".to_owned()),
+            ("value", u8::from(self.value(pin)?).to_string()),
+            ("state", self.path.display().to_string()),
+        ])
+    }
+
+    fn server(&self, inputs: &[u16], outputs: &[u16]) -> BackendResult<Server> {
+        let mut server = Server::new();
+        for &pin in inputs {
+            server = server.input(pin, self.input(pin)?);
+        }
+        for &pin in outputs {
+            let dummy = self.clone();
+            server = server.output(pin, DummyStateOut { dummy, pin });
+        }
+        Ok(server)
+    }
+}
+
+/// Pins served by `gpio serve`, connecting anew for every command
+#[derive(Debug, Clone)]
+pub struct Remote {
+    socket: path::PathBuf,
+}
+
+impl Remote {
+    /// Use the server listening on `socket`, `$GPIO_SOCKET` or /run/gpio.sock
+    pub fn new(socket: Option<path::PathBuf>) -> Remote {
+        let socket = socket
+            .or_else(|| env::var_os("GPIO_SOCKET").map(path::PathBuf::from))
+            .unwrap_or_else(|| path::PathBuf::from("/run/gpio.sock"));
+        Remote { socket }
+    }
+
+    /// The socket of the server
+    #[inline]
+    pub fn socket(&self) -> &path::Path {
+        &self.socket
+    }
+
+    fn connect(&self) -> BackendResult<Client> {
+        Ok(Client::connect(&self.socket)?)
+    }
+}
+
+impl Backend for Remote {
+    fn get(&self, pin: u16) -> BackendResult<GpioValue> {
+        Ok(self.connect()?.read(pin)?)
+    }
+
+    fn set(&self, pin: u16, value: GpioValue) -> BackendResult<()> {
+        Ok(self.connect()?.write(pin, value)?)
+    }
+
+    fn pulse(&self, pin: u16, value: GpioValue, width: time::Duration) -> BackendResult<()> {
+        let client = self.connect()?;
+        client.write(pin, value)?;
+        thread::sleep(width);
+        Ok(client.write(pin, invert(value))?)
+    }
+
+    fn watch(
+        &self,
+        pins: &[u16],
+        edge: GpioEdge,
+        timeout: Option<time::Duration>,
+        on_edge: &mut dyn FnMut(Edge) -> bool,
+    ) -> BackendResult<WatchEnd> {
+        let client = self.connect()?;
+        let mut gpios = Vec::new();
+        for &pin in pins {
+            let mut gpio = client.input(pin);
+            gpio.set_edge(edge)?;
+            gpios.push(gpio);
+        }
+
+        let mut iter = RemoteEdgeIter::new()?;
+        for gpio in &gpios {
+            iter.add(gpio)?;
+        }
+
+        // signals do not interrupt the remote iterator, so it waits in slices
+        let mut last_edge = time::Instant::now();
+        loop {
+            if signal::interrupted() {
+                return Ok(WatchEnd::Interrupted);
+            }
+            match slice(timeout, last_edge) {
+                Some(slice) => iter.timeout_ms(timeout_ms(slice).max(1)),
+                None => return Ok(WatchEnd::Timeout),
+            };
+            let gpio = match iter.next().expect("edge iterators never end") {
+                Ok(gpio) => gpio,
+                Err(RemoteError::Timeout) => continue,
+                Err(err) => return Err(err.into()),
+            };
+            last_edge = time::Instant::now();
+            let value = gpio.last_event().expect("returned pins have an event");
+            if !on_edge(Edge { pin: gpio.pin(), value }) {
+                return Ok(WatchEnd::Stopped);
+            }
+        }
+    }
+
+    fn info(&self, pin: u16) -> BackendResult<Vec<(&'static str, String)>> {
+        let client = self.connect()?;
+        let mode = client
+            .pins()?
+            .into_iter()
+            .find(|&(served, _)| served == pin)
+            .map(|(_, mode)| mode);
+        let mut info = vec![
+            ("pin", pin.to_string()),
+            ("backend", "remote".to_owned()),
+            ("socket", self.socket.display().to_string()),
+        ];
+        match mode {
+            Some(mode) => {
+                let direction = match mode {
+                    PinMode::Input => "in",
+                    PinMode::Output => "out",
+                };
+                info.push(("direction", direction.to_owned()));
+                let value = client.read(pin).map(|value| u8::from(value).to_string());
+                // outputs have no value until written
+                info.push(("value", value.unwrap_or_else(|_| "unknown".to_owned())));
+            }
+            None => info.push(("served", "no".to_owned())),
+        }
+        Ok(info)
+    }
+
+    fn server(&self, _inputs: &[u16], _outputs: &[u16]) -> BackendResult<Server> {
+        Err(BackendError::Unsupported("serving"))
+    }
+}
@@ -0,0 +1,114 @@
+//! Command-line access to GPIO pins
+//!
+//! Reads, writes and watches pins through the sysfs interface, see `gpio help`. The dummy
+//! backend (`--backend dummy`) keeps pin values in a file instead, so scripts using the tool can
+//! be tested without hardware:
+//!
+//! ```text
+//! $ gpio --backend dummy set 17 1
+//! $ gpio --backend dummy toggle 17
+//! $ gpio --backend dummy get 17
+//! 0
+//! ```
+//!
+//! `gpio serve` keeps pins open and shares them with other processes, which access them with
+//! `--backend remote`:
+//!
+//! ```text
+//! $ gpio --socket /tmp/gpio.sock serve --input 17 --output 18 &
+//! $ gpio --socket /tmp/gpio.sock --backend remote set 18 1
+//! ```
+
+extern crate gpio;
+extern crate nix;
+#[macro_use]
+extern crate quick_error;
+
+mod args;
+mod backend;
+mod monitor;
+mod signal;
+
+use std::{env, io, path, process, thread};
+use args::{Args, BackendKind, Command};
+use backend::{Backend, BackendResult, Dummy, Remote, SysFs, WatchEnd, SIGNAL_CHECK};
+use monitor::Monitor;
+
+/// Exit status if watching timed out before the requested number of edges was seen
+const EXIT_TIMEOUT: i32 = 3;
+
+/// Run `command`, serving on `socket` if asked to, returning the exit status
+fn run(backend: &dyn Backend, command: Command, socket: &path::Path) -> BackendResult<i32> {
+    match command {
+        Command::Get(pin) => println!("{}", u8::from(backend.get(pin)?)),
+        Command::Set(pin, value) => backend.set(pin, value)?,
+        Command::Toggle(pin) => backend.toggle(pin)?,
+        Command::Pulse { pin, value, width } => backend.pulse(pin, value, width)?,
+        Command::Watch {
+            pins,
+            edge,
+            timeout,
+            count,
+            format,
+        } => {
+            signal::install();
+            let mut monitor = Monitor::new(format, &pins);
+            let mut output = Ok(());
+            let end = backend.watch(&pins, edge, timeout, &mut |edge| {
+                output = monitor.edge(edge);
+                output.is_ok() && count.is_none_or(|count| monitor.edges() < count)
+            })?;
+            // a closed pipe ends watching like --count, without a summary nobody would read
+            match output.and_then(|()| monitor.summary(end)) {
+                Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(0),
+                result => result?,
+            }
+            if end == WatchEnd::Timeout && count.is_some() {
+                return Ok(EXIT_TIMEOUT);
+            }
+        }
+        Command::Info(pin) => {
+            for (name, value) in backend.info(pin)? {
+                println!("{}: {}", name, value);
+            }
+        }
+        Command::Serve { inputs, outputs } => {
+            signal::install();
+            let server = backend.server(&inputs, &outputs)?.listen(socket)?;
+            eprintln!("serving on {}", server.path().display());
+            while !signal::interrupted() {
+                thread::sleep(SIGNAL_CHECK);
+            }
+            // unexports the pins and removes the socket
+            server.shutdown();
+        }
+        Command::Help => println!("{}", args::USAGE),
+    }
+    Ok(0)
+}
+
+fn main() {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("gpio: {}\nsee `gpio help` for usage", err);
+            process::exit(2);
+        }
+    };
+
+    let remote = Remote::new(args.socket);
+    let socket = remote.socket().to_owned();
+    let backend: Box<dyn Backend> = match args.backend {
+        BackendKind::SysFs => Box::new(SysFs::default()),
+        BackendKind::Dummy => Box::new(Dummy::new(args.state)),
+        BackendKind::Remote => Box::new(remote),
+    };
+
+    match run(&*backend, args.command, &socket) {
+        Ok(status) => process::exit(status),
+        Err(err) => {
+            eprintln!("gpio: {}", err);
+            process::exit(1);
+        }
+    }
+}
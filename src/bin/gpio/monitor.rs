@@ -0,0 +1,200 @@
+//! Output of watched edges as text or JSON Lines
+//!
+//! In JSON mode every edge is an object on its own line with the fields
+//!
+//! * `event`: always `edge`
+//! * `seq`: sequence number of the edge, starting at 1
+//! * `pin`, `edge` (`rising` or `falling`) and `value` (0 or 1)
+//! * `monotonic`: `CLOCK_MONOTONIC` in seconds, as used by the kernel log
+//! * `time`: wall-clock time in seconds since the Unix epoch
+//!
+//! e.g. `{"event":"edge","seq":1,"pin":17,"edge":"rising","value":1,"monotonic":5123.000412345,
+//! "time":1760000000.123456}`, without the line break. When watching ends, an object with `event`
+//! `summary` follows. It holds the `reason` watching ended for (`count`, `timeout` or
+//! `interrupted`), its `duration` in seconds, the number of `edges`, their `rate` per second and
+//! the same counts and rates for each of the `pins`, split into `rising` and `falling` edges.
+//!
+//! In text mode edges are printed as `PIN EDGE VALUE` and the summary goes to stderr, so the
+//! output of scripts reading edges is not affected.
+
+use std::{collections, io, time};
+use std::io::Write;
+use nix::libc;
+use gpio::GpioValue;
+use args::Format;
+use backend::{Edge, WatchEnd};
+
+#[derive(Debug, Default, Copy, Clone)]
+struct Counts {
+    rising: u64,
+    falling: u64,
+}
+
+impl Counts {
+    fn edges(&self) -> u64 {
+        self.rising + self.falling
+    }
+}
+
+/// Seconds on `CLOCK_MONOTONIC`
+fn monotonic() -> f64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+    }
+    ts.tv_sec as f64 + ts.tv_nsec as f64 / 1e9
+}
+
+/// Seconds since the Unix epoch
+fn wall_clock() -> f64 {
+    time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+fn edge_name(value: GpioValue) -> &'static str {
+    match value {
+        GpioValue::Low => "falling",
+        GpioValue::High => "rising",
+    }
+}
+
+fn end_name(end: WatchEnd) -> &'static str {
+    match end {
+        WatchEnd::Stopped => "count",
+        WatchEnd::Timeout => "timeout",
+        WatchEnd::Interrupted => "interrupted",
+    }
+}
+
+/// Prints edges and keeps statistics for the summary
+#[derive(Debug)]
+pub struct Monitor {
+    format: Format,
+    start: time::Instant,
+    seq: u64,
+    pins: collections::BTreeMap<u16, Counts>,
+}
+
+impl Monitor {
+    /// Start monitoring `pins`, all of them are listed in the summary
+    pub fn new(format: Format, pins: &[u16]) -> Monitor {
+        Monitor {
+            format,
+            start: time::Instant::now(),
+            seq: 0,
+            pins: pins.iter().map(|&pin| (pin, Counts::default())).collect(),
+        }
+    }
+
+    /// Number of edges seen so far
+    #[inline]
+    pub fn edges(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn edge(&mut self, edge: Edge) -> io::Result<()> {
+        self.seq += 1;
+        let counts = self.pins.entry(edge.pin).or_default();
+        match edge.value {
+            GpioValue::Low => counts.falling += 1,
+            GpioValue::High => counts.rising += 1,
+        }
+
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        match self.format {
+            Format::Text => writeln!(
+                out,
+                "{} {} {}",
+                edge.pin,
+                edge_name(edge.value),
+                u8::from(edge.value)
+            ),
+            Format::Json => writeln!(
+                out,
+                "{{\"event\":\"edge\",\"seq\":{},\"pin\":{},\"edge\":\"{}\",\"value\":{},\
+                 \"monotonic\":{:.9},\"time\":{:.6}}}",
+                self.seq,
+                edge.pin,
+                edge_name(edge.value),
+                u8::from(edge.value),
+                monotonic(),
+                wall_clock()
+            ),
+        }?;
+        out.flush()
+    }
+
+    pub fn summary(&self, end: WatchEnd) -> io::Result<()> {
+        let duration = self.start.elapsed().as_secs_f64();
+        let rate = |edges: u64| {
+            if duration > 0.0 {
+                edges as f64 / duration
+            } else {
+                0.0
+            }
+        };
+
+        match self.format {
+            Format::Text => {
+                let stderr = io::stderr();
+                let mut out = stderr.lock();
+                writeln!(
+                    out,
+                    "{} edges in {:.3}s ({:.2}/s), stopped by {}",
+                    self.seq,
+                    duration,
+                    rate(self.seq),
+                    end_name(end)
+                )?;
+                for (pin, counts) in &self.pins {
+                    writeln!(
+                        out,
+                        "  {}: {} edges ({} rising, {} falling, {:.2}/s)",
+                        pin,
+                        counts.edges(),
+                        counts.rising,
+                        counts.falling,
+                        rate(counts.edges())
+                    )?;
+                }
+                Ok(())
+            }
+            Format::Json => {
+                let pins: Vec<String> = self
+                    .pins
+                    .iter()
+                    .map(|(pin, counts)| {
+                        format!(
+                            "{{\"pin\":{},\"edges\":{},\"rising\":{},\"falling\":{},\
+                             \"rate\":{:.3}}}",
+                            pin,
+                            counts.edges(),
+                            counts.rising,
+                            counts.falling,
+                            rate(counts.edges())
+                        )
+                    })
+                    .collect();
+                let stdout = io::stdout();
+                let mut out = stdout.lock();
+                writeln!(
+                    out,
+                    "{{\"event\":\"summary\",\"reason\":\"{}\",\"duration\":{:.6},\"edges\":{},\
+                     \"rate\":{:.3},\"pins\":[{}]}}",
+                    end_name(end),
+                    duration,
+                    self.seq,
+                    rate(self.seq),
+                    pins.join(",")
+                )?;
+                out.flush()
+            }
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! Stopping on SIGINT and SIGTERM, so watching can print its summary
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use nix::libc;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Record SIGINT and SIGTERM instead of terminating
+///
+/// Blocking waits like `epoll_wait` fail with `EINTR` when a signal arrives, after which
+/// `interrupted` returns `true`.
+pub fn install() {
+    let handler = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+/// Whether SIGINT or SIGTERM was received
+#[inline]
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,114 @@
+//! Headers of the BeagleBone Black
+//!
+//! The four GPIO banks of the AM335x have 32 GPIOs each, which are numbered consecutively, so
+//! `GPIO1_12` is number 44. Each bank is a GPIO chip of its own.
+
+/// Labels of the GPIO chip of each bank, as named by recent and by older kernels
+pub const CHIPS: [[&str; 2]; 4] = [
+    ["gpio-0-31", "44e07000.gpio"],
+    ["gpio-32-63", "4804c000.gpio"],
+    ["gpio-64-95", "481ac000.gpio"],
+    ["gpio-96-127", "481ae000.gpio"],
+];
+
+/// The expansion header `P8`
+pub const P8: [(&str, Option<u16>); 46] = [
+    ("GND", None),
+    ("GND", None),
+    ("GPIO1_6", Some(38)),
+    ("GPIO1_7", Some(39)),
+    ("GPIO1_2", Some(34)),
+    ("GPIO1_3", Some(35)),
+    ("GPIO2_2", Some(66)),
+    ("GPIO2_3", Some(67)),
+    ("GPIO2_5", Some(69)),
+    ("GPIO2_4", Some(68)),
+    ("GPIO1_13", Some(45)),
+    ("GPIO1_12", Some(44)),
+    ("GPIO0_23", Some(23)),
+    ("GPIO0_26", Some(26)),
+    ("GPIO1_15", Some(47)),
+    ("GPIO1_14", Some(46)),
+    ("GPIO0_27", Some(27)),
+    ("GPIO2_1", Some(65)),
+    ("GPIO0_22", Some(22)),
+    ("GPIO1_31", Some(63)),
+    ("GPIO1_30", Some(62)),
+    ("GPIO1_5", Some(37)),
+    ("GPIO1_4", Some(36)),
+    ("GPIO1_1", Some(33)),
+    ("GPIO1_0", Some(32)),
+    ("GPIO1_29", Some(61)),
+    ("GPIO2_22", Some(86)),
+    ("GPIO2_24", Some(88)),
+    ("GPIO2_23", Some(87)),
+    ("GPIO2_25", Some(89)),
+    ("GPIO0_10", Some(10)),
+    ("GPIO0_11", Some(11)),
+    ("GPIO0_9", Some(9)),
+    ("GPIO2_17", Some(81)),
+    ("GPIO0_8", Some(8)),
+    ("GPIO2_16", Some(80)),
+    ("GPIO2_14", Some(78)),
+    ("GPIO2_15", Some(79)),
+    ("GPIO2_12", Some(76)),
+    ("GPIO2_13", Some(77)),
+    ("GPIO2_10", Some(74)),
+    ("GPIO2_11", Some(75)),
+    ("GPIO2_8", Some(72)),
+    ("GPIO2_9", Some(73)),
+    ("GPIO2_6", Some(70)),
+    ("GPIO2_7", Some(71)),
+];
+
+/// The expansion header `P9`
+///
+/// Pins 41 and 42 are connected to two GPIOs each, only `GPIO0_20` and `GPIO0_7` are listed.
+pub const P9: [(&str, Option<u16>); 46] = [
+    ("GND", None),
+    ("GND", None),
+    ("VDD_3V3", None),
+    ("VDD_3V3", None),
+    ("VDD_5V", None),
+    ("VDD_5V", None),
+    ("SYS_5V", None),
+    ("SYS_5V", None),
+    ("PWR_BUT", None),
+    ("SYS_RESETN", None),
+    ("GPIO0_30", Some(30)),
+    ("GPIO1_28", Some(60)),
+    ("GPIO0_31", Some(31)),
+    ("GPIO1_18", Some(50)),
+    ("GPIO1_16", Some(48)),
+    ("GPIO1_19", Some(51)),
+    ("GPIO0_5", Some(5)),
+    ("GPIO0_4", Some(4)),
+    ("GPIO0_13", Some(13)),
+    ("GPIO0_12", Some(12)),
+    ("GPIO0_3", Some(3)),
+    ("GPIO0_2", Some(2)),
+    ("GPIO1_17", Some(49)),
+    ("GPIO0_15", Some(15)),
+    ("GPIO3_21", Some(117)),
+    ("GPIO0_14", Some(14)),
+    ("GPIO3_19", Some(115)),
+    ("GPIO3_17", Some(113)),
+    ("GPIO3_15", Some(111)),
+    ("GPIO3_16", Some(112)),
+    ("GPIO3_14", Some(110)),
+    ("VDD_ADC", None),
+    ("AIN4", None),
+    ("GNDA_ADC", None),
+    ("AIN6", None),
+    ("AIN5", None),
+    ("AIN2", None),
+    ("AIN3", None),
+    ("AIN0", None),
+    ("AIN1", None),
+    ("GPIO0_20", Some(20)),
+    ("GPIO0_7", Some(7)),
+    ("GND", None),
+    ("GND", None),
+    ("GND", None),
+    ("GND", None),
+];
@@ -0,0 +1,311 @@
+//! Pin names of common boards
+//!
+//! Pins are opened by their kernel number, while boards label them by their position on a pin
+//! header. A `Board` translates between the two:
+//!
+//! * Raspberry Pi pins are known by their BCM number (`BCM17`, also `GPIO17`), their physical
+//!   position on the header (`PHYS11`, `J8_11`, or `P1_11` on 26-pin boards) or their wiringPi
+//!   number (`WPI0`)
+//! * BeagleBone Black pins are known by their header and position (`P8_12`, also `P8.12`) or
+//!   their GPIO bank and bit (`GPIO1_12`)
+//!
+//! The board running the program is detected from `/proc/device-tree/model`, falling back to
+//! `/proc/cpuinfo` for older kernels. `Board::detect_at` reads these files below another root
+//! directory instead, e.g. fixtures in tests.
+//!
+//! `Board::gpio` and friends return the GPIO numbers of the board's documentation, i.e. the BCM
+//! number or the bank and bit as `bank * 32 + bit`. They are kernel numbers only on kernels
+//! numbering the board's GPIO chips from 0, which recent Raspberry Pi kernels do not: they start
+//! at 512. `Board::kernel_number` looks up the chip of a GPIO with the `sysfs::chips` module and
+//! returns the number the running kernel uses.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use std::fs;
+//! use gpio::boards::Board;
+//! use gpio::sysfs::KernelTree;
+//!
+//! let root = std::env::temp_dir().join(format!("gpio-boards-{}", std::process::id()));
+//! fs::create_dir_all(root.join("proc/device-tree")).unwrap();
+//! fs::write(root.join("proc/device-tree/model"), "Raspberry Pi 4 Model B Rev 1.4\0").unwrap();
+//!
+//! let board = Board::detect_at(&root).unwrap();
+//! assert_eq!(board, Board::RaspberryPi);
+//! assert_eq!(board.gpio("PHYS11").unwrap(), 17);
+//! assert_eq!(board.gpio("WPI2").unwrap(), 27);
+//! assert_eq!(board.physical(13), Some(27));
+//! assert!(board.gpio("PHYS6").is_err()); // ground
+//!
+//! // the first Raspberry Pi had different pins on the header
+//! assert_eq!(Board::RaspberryPiRev1.gpio("WPI2").unwrap(), 21);
+//! assert_eq!(Board::BeagleBoneBlack.gpio("P8_12").unwrap(), 44);
+//!
+//! // a kernel numbering the GPIOs of the Raspberry Pi 4 from 512
+//! let chip = root.join("sys/class/gpio/gpiochip512");
+//! fs::create_dir_all(&chip).unwrap();
+//! fs::write(chip.join("base"), "512\n").unwrap();
+//! fs::write(chip.join("ngpio"), "58\n").unwrap();
+//! fs::write(chip.join("label"), "pinctrl-bcm2711\n").unwrap();
+//! let tree = KernelTree::at(root.join("sys/class/gpio"));
+//! assert_eq!(board.kernel_number_at(&tree, "PHYS11").unwrap(), 529);
+//! # fs::remove_dir_all(root).unwrap();
+//! ```
+
+use std::{fmt, fs, io, path};
+use sysfs::{chips, GpioError, KernelTree, SysFsTree};
+
+mod beaglebone;
+mod raspberry_pi;
+
+use self::raspberry_pi::Layout;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum BoardError {
+        Io(err: io::Error) {
+            from()
+            description("io error")
+            display("I/O error: {}", err)
+            cause(err)
+        }
+        Sysfs(err: GpioError) {
+            from()
+            description("sysfs error")
+            display("sysfs error: {}", err)
+            cause(err)
+        }
+        /// The board could not be detected, with its model if known
+        Unknown(model: String) {
+            description("unknown board")
+            display("unknown board {:?}", model)
+        }
+        /// The board has no GPIO of the name
+        UnknownPin(board: Board, name: String) {
+            description("unknown pin")
+            display("{} has no GPIO {}", board, name)
+        }
+        /// The kernel has no GPIO chip of the board with the GPIO
+        NoChip(board: Board, gpio: u16) {
+            description("GPIO chip not found")
+            display("no GPIO chip of {} has GPIO {}", board, gpio)
+        }
+    }
+}
+
+pub type BoardResult<T> = Result<T, BoardError>;
+
+/// A pin of a header
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct HeaderPin {
+    /// The name of the header, e.g. `J8` or `P9`
+    pub header: &'static str,
+    /// The position on the header, starting at 1
+    pub number: u8,
+    /// The function of the pin, e.g. `GPIO17` or `GND`
+    pub name: &'static str,
+    /// The GPIO number, `None` for pins that are not GPIOs
+    pub gpio: Option<u16>,
+}
+
+type Header = (&'static str, &'static [(&'static str, Option<u16>)]);
+
+/// A supported board
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Board {
+    /// The Raspberry Pi Model B revision 1, with a different 26-pin header
+    RaspberryPiRev1,
+    /// The Raspberry Pi Model A and Model B revision 2, with the 26-pin header `P1`
+    RaspberryPiRev2,
+    /// All Raspberry Pi models with the 40-pin header `J8`, i.e. all since the Model B+
+    RaspberryPi,
+    /// The BeagleBone Black and boards with the same headers, e.g. the BeagleBone Green
+    BeagleBoneBlack,
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Board::RaspberryPiRev1 => "Raspberry Pi (revision 1)",
+            Board::RaspberryPiRev2 => "Raspberry Pi (revision 2)",
+            Board::RaspberryPi => "Raspberry Pi",
+            Board::BeagleBoneBlack => "BeagleBone Black",
+        })
+    }
+}
+
+/// The contents of the file at `path`, `None` if it does not exist
+fn read(path: &path::Path) -> io::Result<Option<String>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(String::from_utf8_lossy(&data).into_owned())),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// The number following `prefix` in `name`
+fn number(name: &str, prefix: &str) -> Option<u8> {
+    name.strip_prefix(prefix)?.parse().ok()
+}
+
+impl Board {
+    /// Detect the board running the program
+    #[inline]
+    pub fn detect() -> BoardResult<Board> {
+        Self::detect_at("/")
+    }
+
+    /// Detect a board from `proc/device-tree/model` and `proc/cpuinfo` below `root`
+    pub fn detect_at<P: AsRef<path::Path>>(root: P) -> BoardResult<Board> {
+        let root = root.as_ref();
+        let cpuinfo = read(&root.join("proc/cpuinfo"))?.unwrap_or_default();
+        let field = |name: &str| {
+            cpuinfo.lines().find_map(|line| {
+                let mut parts = line.splitn(2, ':');
+                match (parts.next(), parts.next()) {
+                    (Some(key), Some(value)) if key.trim() == name => Some(value.trim()),
+                    _ => None,
+                }
+            })
+        };
+        let model = read(&root.join("proc/device-tree/model"))?
+            .map(|model| model.trim_end_matches('\0').trim().to_owned())
+            .or_else(|| field("Model").map(str::to_owned));
+        let hardware = field("Hardware").unwrap_or("");
+
+        match model {
+            Some(ref model) if model.starts_with("Raspberry Pi") => (),
+            Some(ref model) if model.contains("AM335x BeagleBone") => {
+                return Ok(Board::BeagleBoneBlack)
+            }
+            Some(model) => return Err(BoardError::Unknown(model)),
+            None if hardware.starts_with("BCM27") || hardware.starts_with("BCM28") => (),
+            None if hardware.contains("AM33XX") => return Ok(Board::BeagleBoneBlack),
+            None => return Err(BoardError::Unknown(hardware.to_owned())),
+        }
+
+        let layout = field("Revision")
+            .and_then(raspberry_pi::layout)
+            .or_else(|| model.as_ref().map(|model| raspberry_pi::layout_of_model(model)));
+        Ok(match layout {
+            Some(Layout::Rev1) => Board::RaspberryPiRev1,
+            Some(Layout::Rev2) => Board::RaspberryPiRev2,
+            Some(Layout::J8) | None => Board::RaspberryPi,
+        })
+    }
+
+    fn headers(self) -> &'static [Header] {
+        match self {
+            Board::RaspberryPiRev1 => &[("P1", &raspberry_pi::P1_REV1)],
+            Board::RaspberryPiRev2 => &[("P1", &raspberry_pi::P1_REV2)],
+            Board::RaspberryPi => &[("J8", &raspberry_pi::J8)],
+            Board::BeagleBoneBlack => &[("P8", &beaglebone::P8), ("P9", &beaglebone::P9)],
+        }
+    }
+
+    /// All pins of all headers of the board
+    pub fn pins(self) -> impl Iterator<Item = HeaderPin> {
+        self.headers().iter().flat_map(|&(header, pins)| {
+            pins.iter().enumerate().map(move |(index, &(name, gpio))| HeaderPin {
+                header,
+                number: index as u8 + 1,
+                name,
+                gpio,
+            })
+        })
+    }
+
+    /// The pin at position `number` of `header`, e.g. `P8` and 12
+    pub fn header_pin(self, header: &str, number: u8) -> Option<HeaderPin> {
+        self.pins()
+            .find(|pin| pin.header.eq_ignore_ascii_case(header) && pin.number == number)
+    }
+
+    #[inline]
+    fn is_raspberry_pi(self) -> bool {
+        self != Board::BeagleBoneBlack
+    }
+
+    /// The BCM number of the GPIO at position `number` of a Raspberry Pi header
+    pub fn physical(self, number: u8) -> Option<u16> {
+        if !self.is_raspberry_pi() {
+            return None;
+        }
+        self.headers()[0].1.get(usize::from(number).checked_sub(1)?)?.1
+    }
+
+    /// BCM GPIO `bcm` of a Raspberry Pi, if it exists, also if not on the header
+    pub fn bcm(self, bcm: u8) -> Option<u16> {
+        if self.is_raspberry_pi() && u16::from(bcm) < raspberry_pi::GPIOS {
+            Some(u16::from(bcm))
+        } else {
+            None
+        }
+    }
+
+    /// The BCM number of wiringPi pin `pin` of a Raspberry Pi
+    pub fn wiring_pi(self, pin: u8) -> Option<u16> {
+        self.physical((*raspberry_pi::WIRING_PI.get(usize::from(pin))?)?)
+    }
+
+    /// The number of the GPIO named `name`, using any notation of the board
+    ///
+    /// Names are not case-sensitive. The number is a kernel number only on kernels numbering
+    /// the GPIO chips from 0, see `kernel_number`.
+    pub fn gpio(self, name: &str) -> BoardResult<u16> {
+        let upper = name.to_ascii_uppercase();
+        let unknown = || BoardError::UnknownPin(self, name.to_owned());
+
+        for &(header, _) in self.headers() {
+            let position = upper
+                .strip_prefix(header)
+                .and_then(|rest| rest.strip_prefix(['_', '.', '-']))
+                .and_then(|rest| rest.parse().ok());
+            if let Some(position) = position {
+                return self
+                    .header_pin(header, position)
+                    .and_then(|pin| pin.gpio)
+                    .ok_or_else(unknown);
+            }
+        }
+
+        let gpio = if self.is_raspberry_pi() {
+            if let Some(bcm) = number(&upper, "BCM").or_else(|| number(&upper, "GPIO")) {
+                self.bcm(bcm)
+            } else if let Some(physical) = number(&upper, "PHYS") {
+                self.physical(physical)
+            } else {
+                number(&upper, "WPI").and_then(|pin| self.wiring_pi(pin))
+            }
+        } else {
+            let mut bank = upper.strip_prefix("GPIO").unwrap_or("").splitn(2, '_');
+            match (bank.next().map(str::parse), bank.next().map(str::parse)) {
+                (Some(Ok(bank @ 0..=3)), Some(Ok(bit @ 0..=31))) => Some(bank * 32 + bit),
+                _ => None,
+            }
+        };
+        gpio.ok_or_else(unknown)
+    }
+
+    /// The kernel number of the GPIO named `name` on the running kernel
+    #[inline]
+    pub fn kernel_number(self, name: &str) -> BoardResult<u16> {
+        self.kernel_number_at(&KernelTree::new(), name)
+    }
+
+    /// The kernel number of the GPIO named `name`, looking up its chip in `tree`
+    pub fn kernel_number_at(self, tree: &dyn SysFsTree, name: &str) -> BoardResult<u16> {
+        let gpio = self.gpio(name)?;
+        let (labels, offset): (&[&str], u16) = if self.is_raspberry_pi() {
+            (&raspberry_pi::CHIPS, gpio)
+        } else {
+            (&beaglebone::CHIPS[usize::from(gpio / 32)], gpio % 32)
+        };
+        let chips = chips::list_at(tree)?;
+        let chip = chips
+            .iter()
+            .find(|chip| labels.contains(&chip.label()))
+            .ok_or(BoardError::NoChip(self, gpio))?;
+        Ok(chip.gpio_num(offset)?)
+    }
+}
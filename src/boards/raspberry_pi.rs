@@ -0,0 +1,185 @@
+//! Headers of the Raspberry Pi models
+//!
+//! GPIOs are identified by their BCM number, which is their offset within the GPIO chip of the
+//! SoC. Older kernels number that chip from 0, so BCM numbers are kernel numbers there.
+
+/// The 40-pin header `J8` of all models since the Model B+
+pub const J8: [(&str, Option<u16>); 40] = [
+    ("3V3", None),
+    ("5V", None),
+    ("GPIO2", Some(2)),
+    ("5V", None),
+    ("GPIO3", Some(3)),
+    ("GND", None),
+    ("GPIO4", Some(4)),
+    ("GPIO14", Some(14)),
+    ("GND", None),
+    ("GPIO15", Some(15)),
+    ("GPIO17", Some(17)),
+    ("GPIO18", Some(18)),
+    ("GPIO27", Some(27)),
+    ("GND", None),
+    ("GPIO22", Some(22)),
+    ("GPIO23", Some(23)),
+    ("3V3", None),
+    ("GPIO24", Some(24)),
+    ("GPIO10", Some(10)),
+    ("GND", None),
+    ("GPIO9", Some(9)),
+    ("GPIO25", Some(25)),
+    ("GPIO11", Some(11)),
+    ("GPIO8", Some(8)),
+    ("GND", None),
+    ("GPIO7", Some(7)),
+    ("GPIO0", Some(0)),
+    ("GPIO1", Some(1)),
+    ("GPIO5", Some(5)),
+    ("GND", None),
+    ("GPIO6", Some(6)),
+    ("GPIO12", Some(12)),
+    ("GPIO13", Some(13)),
+    ("GND", None),
+    ("GPIO19", Some(19)),
+    ("GPIO16", Some(16)),
+    ("GPIO26", Some(26)),
+    ("GPIO20", Some(20)),
+    ("GND", None),
+    ("GPIO21", Some(21)),
+];
+
+/// The 26-pin header `P1` of the Model A and of the Model B from revision 2
+pub const P1_REV2: [(&str, Option<u16>); 26] = [
+    ("3V3", None),
+    ("5V", None),
+    ("GPIO2", Some(2)),
+    ("5V", None),
+    ("GPIO3", Some(3)),
+    ("GND", None),
+    ("GPIO4", Some(4)),
+    ("GPIO14", Some(14)),
+    ("GND", None),
+    ("GPIO15", Some(15)),
+    ("GPIO17", Some(17)),
+    ("GPIO18", Some(18)),
+    ("GPIO27", Some(27)),
+    ("GND", None),
+    ("GPIO22", Some(22)),
+    ("GPIO23", Some(23)),
+    ("3V3", None),
+    ("GPIO24", Some(24)),
+    ("GPIO10", Some(10)),
+    ("GND", None),
+    ("GPIO9", Some(9)),
+    ("GPIO25", Some(25)),
+    ("GPIO11", Some(11)),
+    ("GPIO8", Some(8)),
+    ("GND", None),
+    ("GPIO7", Some(7)),
+];
+
+/// The 26-pin header `P1` of the first Model B, which differs from later ones in pins 3, 5 and 13
+pub const P1_REV1: [(&str, Option<u16>); 26] = [
+    ("3V3", None),
+    ("5V", None),
+    ("GPIO0", Some(0)),
+    ("5V", None),
+    ("GPIO1", Some(1)),
+    ("GND", None),
+    ("GPIO4", Some(4)),
+    ("GPIO14", Some(14)),
+    ("GND", None),
+    ("GPIO15", Some(15)),
+    ("GPIO17", Some(17)),
+    ("GPIO18", Some(18)),
+    ("GPIO21", Some(21)),
+    ("GND", None),
+    ("GPIO22", Some(22)),
+    ("GPIO23", Some(23)),
+    ("3V3", None),
+    ("GPIO24", Some(24)),
+    ("GPIO10", Some(10)),
+    ("GND", None),
+    ("GPIO9", Some(9)),
+    ("GPIO25", Some(25)),
+    ("GPIO11", Some(11)),
+    ("GPIO8", Some(8)),
+    ("GND", None),
+    ("GPIO7", Some(7)),
+];
+
+/// The physical pins of wiringPi's pin numbers, pins 17-20 are on the `P5` header of the Model B
+/// revision 2 and not supported
+pub const WIRING_PI: [Option<u8>; 32] = [
+    Some(11),
+    Some(12),
+    Some(13),
+    Some(15),
+    Some(16),
+    Some(18),
+    Some(22),
+    Some(7),
+    Some(3),
+    Some(5),
+    Some(24),
+    Some(26),
+    Some(19),
+    Some(21),
+    Some(23),
+    Some(8),
+    Some(10),
+    None,
+    None,
+    None,
+    None,
+    Some(29),
+    Some(31),
+    Some(33),
+    Some(35),
+    Some(37),
+    Some(32),
+    Some(36),
+    Some(38),
+    Some(40),
+    Some(27),
+    Some(28),
+];
+
+/// Number of GPIOs of the BCM283x
+pub const GPIOS: u16 = 54;
+
+/// Labels of the GPIO chip with the header's GPIOs, up to the Pi 3, of the Pi 4 and of the Pi 5
+pub const CHIPS: [&str; 3] = ["pinctrl-bcm2835", "pinctrl-bcm2711", "pinctrl-rp1"];
+
+/// Header layout of a board revision, as found in `/proc/cpuinfo`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Layout {
+    Rev1,
+    Rev2,
+    J8,
+}
+
+/// The header layout of a board with the hexadecimal `revision` code
+pub fn layout(revision: &str) -> Option<Layout> {
+    let code = u32::from_str_radix(revision.trim(), 16).ok()?;
+    // new-style codes describe the board in bit fields, all such boards have 40 pins
+    if code & (1 << 23) != 0 {
+        return Some(Layout::J8);
+    }
+    // old-style codes of overvolted boards have bit 24 set
+    Some(match code & 0xff_ffff {
+        0x2 | 0x3 => Layout::Rev1,
+        0x4..=0xf => Layout::Rev2,
+        _ => Layout::J8,
+    })
+}
+
+/// The header layout of a board with device tree `model`
+pub fn layout_of_model(model: &str) -> Layout {
+    let old = model.starts_with("Raspberry Pi Model A Rev") ||
+        model.starts_with("Raspberry Pi Model B Rev");
+    match model.rsplit("Rev ").next() {
+        Some("1") | Some("1.0") if old && model.contains("Model B") => Layout::Rev1,
+        _ if old => Layout::Rev2,
+        _ => Layout::J8,
+    }
+}
@@ -0,0 +1,281 @@
+//! Push-button gestures
+//!
+//! `Button` turns the raw edges of a push-button into high-level events: debounced presses and
+//! releases, clicks, double-clicks, long-presses and auto-repeat while held down.
+//!
+//! The button does not measure time itself, instead every call is passed the current time. This
+//! keeps it independent of how edges are waited for and allows tests to run on a made-up
+//! timeline. Some events are caused by time passing rather than by an edge (e.g. a long-press),
+//! so `poll` must also be called when `next_deadline` is reached, e.g. by using it as the timeout
+//! of the edge iterator.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use std::time::{Duration, Instant};
+//! use gpio::GpioValue;
+//! use gpio::button::{Button, ButtonEvent};
+//! use gpio::dummy::DummyGpioIn;
+//!
+//! // the button pulls its input low when pressed
+//! let pin = DummyGpioIn::with_value(true);
+//!
+//! let mut button = Button::new(&pin, GpioValue::Low)
+//!     .unwrap()
+//!     .debounce(Duration::from_millis(10))
+//!     .double_click(Some(Duration::from_millis(250)))
+//!     .long_press(Some(Duration::from_millis(1000)));
+//!
+//! let t0 = Instant::now();
+//! let at = |ms| t0 + Duration::from_millis(ms);
+//!
+//! // a short press, including some contact bounce
+//! let mut events = Vec::new();
+//! for &(ms, value) in &[(0, false), (2, true), (3, false)] {
+//!     pin.set(value);
+//!     events.extend(button.handle_edge(&pin, at(ms)).unwrap());
+//! }
+//! // no further edges arrive, the edge iterator times out at the next deadline
+//! assert_eq!(button.next_deadline(), Some(at(13)));
+//! events.extend(button.poll(at(13)).unwrap());
+//!
+//! pin.set(true);
+//! events.extend(button.handle_edge(&pin, at(120)).unwrap());
+//! events.extend(button.poll(at(130)).unwrap());
+//!
+//! // the click is only reported once no second click follows
+//! assert_eq!(events, vec![ButtonEvent::Pressed, ButtonEvent::Released]);
+//! assert_eq!(button.poll(at(400)).unwrap(), vec![ButtonEvent::Click]);
+//!
+//! // holding the button down results in a long-press
+//! pin.set(false);
+//! assert_eq!(button.handle_edge(&pin, at(1000)).unwrap(), vec![]);
+//! assert_eq!(button.poll(at(1010)).unwrap(), vec![ButtonEvent::Pressed]);
+//! assert_eq!(button.poll(at(2500)).unwrap(), vec![ButtonEvent::LongPress]);
+//! ```
+
+use std::{ptr, time};
+use super::{GpioIn, GpioValue};
+
+/// An event reported by a `Button`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ButtonEvent {
+    /// The button was pressed down
+    Pressed,
+    /// The button was released
+    Released,
+    /// The button was pressed and released once
+    Click,
+    /// The button was clicked twice in short succession
+    DoubleClick,
+    /// The button has been held down for the long-press duration
+    LongPress,
+    /// The button is still held down, sent periodically if auto-repeat is enabled
+    Repeat,
+}
+
+/// A push-button connected to an input
+#[derive(Debug)]
+pub struct Button<'a, P: 'a> {
+    pin: &'a P,
+    pressed_value: GpioValue,
+    debounce: time::Duration,
+    double_click: Option<time::Duration>,
+    long_press: Option<time::Duration>,
+    repeat: Option<(time::Duration, time::Duration)>,
+
+    /// Debounced state of the button
+    is_pressed: bool,
+    /// Since when the raw state differs from the debounced state
+    candidate: Option<time::Instant>,
+    pressed_at: time::Instant,
+    /// Whether the current press turned into a long-press or repeat, suppressing the click
+    held: bool,
+    long_press_sent: bool,
+    next_repeat: Option<time::Instant>,
+    /// Release time of a click that may still become a double-click
+    pending_click: Option<time::Instant>,
+}
+
+impl<'a, P: GpioIn> Button<'a, P> {
+    /// Create a new button that reads `pressed_value` while held down
+    ///
+    /// Defaults to a debounce time of 20 ms, a double-click window of 300 ms, a long-press after
+    /// 800 ms and no auto-repeat.
+    pub fn new(pin: &'a P, pressed_value: GpioValue) -> Result<Self, P::Error> {
+        let now = time::Instant::now();
+        Ok(Button {
+            pin,
+            pressed_value,
+            debounce: time::Duration::from_millis(20),
+            double_click: Some(time::Duration::from_millis(300)),
+            long_press: Some(time::Duration::from_millis(800)),
+            repeat: None,
+            is_pressed: pin.read_value()? == pressed_value,
+            candidate: None,
+            pressed_at: now,
+            held: false,
+            long_press_sent: false,
+            next_repeat: None,
+            pending_click: None,
+        })
+    }
+
+    /// Time the input has to be stable before a change is accepted
+    pub fn debounce(mut self, debounce: time::Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Maximum time between releasing the button and pressing it again to count as a
+    /// double-click
+    ///
+    /// Clicks are reported only after this window has passed. With `None`, double-clicks are
+    /// not detected and clicks are reported immediately on release.
+    pub fn double_click(mut self, window: Option<time::Duration>) -> Self {
+        self.double_click = window;
+        self
+    }
+
+    /// Time the button has to be held down to report a long-press, `None` to disable
+    pub fn long_press(mut self, duration: Option<time::Duration>) -> Self {
+        self.long_press = duration;
+        self
+    }
+
+    /// Report `Repeat` events while held down, starting after `delay` and then every `interval`
+    pub fn repeat(mut self, delay: time::Duration, interval: time::Duration) -> Self {
+        let interval = interval.max(time::Duration::from_millis(1));
+        self.repeat = Some((delay, interval));
+        self
+    }
+
+    /// Whether the button is held down, after debouncing
+    #[inline]
+    pub fn is_pressed(&self) -> bool {
+        self.is_pressed
+    }
+
+    /// Process an edge on `triggered` at time `now`
+    ///
+    /// Edges on other pins are ignored, allowing the button to share an edge iterator with
+    /// other inputs.
+    pub fn handle_edge(
+        &mut self,
+        triggered: &P,
+        now: time::Instant,
+    ) -> Result<Vec<ButtonEvent>, P::Error> {
+        if ptr::eq(triggered, self.pin) {
+            self.poll(now)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Read the input and report all events that are due at time `now`
+    pub fn poll(&mut self, now: time::Instant) -> Result<Vec<ButtonEvent>, P::Error> {
+        let raw_pressed = self.pin.read_value()? == self.pressed_value;
+        let mut events = Vec::new();
+
+        if raw_pressed == self.is_pressed {
+            self.candidate = None;
+        } else {
+            let since = *self.candidate.get_or_insert(now);
+            if now.duration_since(since) >= self.debounce {
+                self.candidate = None;
+                self.run_timers(since, &mut events);
+                self.transition(raw_pressed, since, &mut events);
+            }
+        }
+
+        self.run_timers(now, &mut events);
+        Ok(events)
+    }
+
+    /// The next time `poll` needs to be called, even if no edge occurs
+    pub fn next_deadline(&self) -> Option<time::Instant> {
+        let mut deadlines = Vec::new();
+
+        deadlines.extend(self.candidate.map(|c| c + self.debounce));
+        if self.is_pressed {
+            if !self.long_press_sent {
+                deadlines.extend(self.long_press.map(|d| self.pressed_at + d));
+            }
+            deadlines.extend(self.next_repeat);
+        } else if let (Some(released), Some(window)) = (self.pending_click, self.double_click) {
+            deadlines.push(released + window);
+        }
+
+        deadlines.into_iter().min()
+    }
+
+    fn transition(&mut self, pressed: bool, at: time::Instant, events: &mut Vec<ButtonEvent>) {
+        self.is_pressed = pressed;
+
+        if pressed {
+            self.pressed_at = at;
+            self.held = false;
+            self.long_press_sent = false;
+            self.next_repeat = self.repeat.map(|(delay, _)| at + delay);
+            events.push(ButtonEvent::Pressed);
+            return;
+        }
+
+        self.next_repeat = None;
+        events.push(ButtonEvent::Released);
+
+        if self.held {
+            return;
+        }
+
+        if self.double_click.is_none() {
+            events.push(ButtonEvent::Click);
+        } else if self.pending_click.take().is_some() {
+            events.push(ButtonEvent::DoubleClick);
+        } else {
+            self.pending_click = Some(at);
+        }
+    }
+
+    /// Reports all timed events that are due at `until`
+    fn run_timers(&mut self, until: time::Instant, events: &mut Vec<ButtonEvent>) {
+        if !self.is_pressed {
+            if let (Some(released), Some(window)) = (self.pending_click, self.double_click) {
+                if released + window <= until {
+                    self.pending_click = None;
+                    events.push(ButtonEvent::Click);
+                }
+            }
+            return;
+        }
+
+        if !self.long_press_sent {
+            if let Some(duration) = self.long_press {
+                if self.pressed_at + duration <= until {
+                    self.long_press_sent = true;
+                    self.hold(events);
+                    events.push(ButtonEvent::LongPress);
+                }
+            }
+        }
+
+        if let Some((_, interval)) = self.repeat {
+            while let Some(next) = self.next_repeat {
+                if next > until {
+                    break;
+                }
+                self.hold(events);
+                events.push(ButtonEvent::Repeat);
+                self.next_repeat = Some(next + interval);
+            }
+        }
+    }
+
+    /// Marks the current press as held, a preceding click can no longer become a double-click
+    fn hold(&mut self, events: &mut Vec<ButtonEvent>) {
+        self.held = true;
+        if self.pending_click.take().is_some() {
+            events.push(ButtonEvent::Click);
+        }
+    }
+}
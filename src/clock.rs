@@ -0,0 +1,205 @@
+//! Time sources
+//!
+//! Parts of the crate that wait or measure time themselves, such as the dummy edge iterator or
+//! the waveform recorder, do so through a `Clock`. `SystemClock` uses the real time, while
+//! `VirtualClock` only advances when told to, making timing-dependent tests instant and
+//! reproducible.
+//!
+//! Helpers that are passed the current time, like `Button` or `PulseMeter`, can be driven from a
+//! clock by passing `clock.now()`.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use gpio::clock::{Clock, VirtualClock};
+//!
+//! let clock = VirtualClock::new();
+//! let start = clock.now();
+//!
+//! // sleeping on a virtual clock returns immediately, advancing it instead
+//! clock.sleep(Duration::from_secs(3600));
+//! clock.advance(Duration::from_millis(5));
+//! assert_eq!(clock.now() - start, Duration::from_millis(3_600_005));
+//! ```
+
+use std::{sync, thread, time};
+
+/// Wakes up threads waiting through `Clock::wait`
+///
+/// Every notification increments a generation counter. Waiting threads read the generation
+/// before checking for work and pass it to `Clock::wait`, which returns immediately if a
+/// notification happened in between.
+#[derive(Debug, Default)]
+pub struct Notifier {
+    generation: sync::Mutex<u64>,
+    cond: sync::Condvar,
+}
+
+impl Notifier {
+    /// Create a new notifier
+    #[inline]
+    pub fn new() -> Notifier {
+        Default::default()
+    }
+
+    #[inline]
+    fn lock(&self) -> sync::MutexGuard<'_, u64> {
+        self.generation
+            .lock()
+            .unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// The current generation
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        *self.lock()
+    }
+
+    /// Wake up all waiting threads
+    pub fn notify(&self) {
+        *self.lock() += 1;
+        self.cond.notify_all();
+    }
+
+    /// Block until the generation differs from `generation` or `timeout` of real time passed
+    fn wait_real(&self, generation: u64, timeout: Option<time::Duration>) {
+        let deadline = timeout.map(|t| time::Instant::now() + t);
+        let mut current = self.lock();
+
+        while *current == generation {
+            current = match deadline {
+                None => self.cond
+                    .wait(current)
+                    .unwrap_or_else(sync::PoisonError::into_inner),
+                Some(deadline) => {
+                    let now = time::Instant::now();
+                    if now >= deadline {
+                        return;
+                    }
+                    self.cond
+                        .wait_timeout(current, deadline - now)
+                        .unwrap_or_else(sync::PoisonError::into_inner)
+                        .0
+                }
+            };
+        }
+    }
+}
+
+/// A source of time
+pub trait Clock: Send + Sync {
+    /// The current time
+    fn now(&self) -> time::Instant;
+
+    /// Wait for `duration` to pass
+    fn sleep(&self, duration: time::Duration);
+
+    /// Wait until `notifier` has moved past `generation` or `timeout` has passed
+    ///
+    /// Without a timeout, waits until notified.
+    #[inline]
+    fn wait(&self, notifier: &Notifier, generation: u64, timeout: Option<time::Duration>) {
+        notifier.wait_real(generation, timeout)
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for sync::Arc<C> {
+    #[inline]
+    fn now(&self) -> time::Instant {
+        (**self).now()
+    }
+
+    #[inline]
+    fn sleep(&self, duration: time::Duration) {
+        (**self).sleep(duration)
+    }
+
+    #[inline]
+    fn wait(&self, notifier: &Notifier, generation: u64, timeout: Option<time::Duration>) {
+        (**self).wait(notifier, generation, timeout)
+    }
+}
+
+/// The real system clock
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> time::Instant {
+        time::Instant::now()
+    }
+
+    #[inline]
+    fn sleep(&self, duration: time::Duration) {
+        thread::sleep(duration)
+    }
+}
+
+/// A clock that is advanced manually
+///
+/// Sleeping does not block, but advances the clock by the requested duration. Waiting on a
+/// `Notifier` with a timeout returns immediately as well, advancing the clock by the timeout if
+/// no notification happened yet; only waits without a timeout block. Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct VirtualClock {
+    start: time::Instant,
+    elapsed: sync::Arc<sync::Mutex<time::Duration>>,
+}
+
+impl VirtualClock {
+    /// Create a new virtual clock, starting at the current system time
+    pub fn new() -> VirtualClock {
+        VirtualClock {
+            start: time::Instant::now(),
+            elapsed: sync::Arc::new(sync::Mutex::new(time::Duration::from_secs(0))),
+        }
+    }
+
+    #[inline]
+    fn elapsed_mut(&self) -> sync::MutexGuard<'_, time::Duration> {
+        self.elapsed.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// Advance the clock by `duration`
+    #[inline]
+    pub fn advance(&self, duration: time::Duration) {
+        *self.elapsed_mut() += duration;
+    }
+
+    /// Time passed since the clock was created
+    #[inline]
+    pub fn elapsed(&self) -> time::Duration {
+        *self.elapsed_mut()
+    }
+}
+
+impl Default for VirtualClock {
+    #[inline]
+    fn default() -> VirtualClock {
+        VirtualClock::new()
+    }
+}
+
+impl Clock for VirtualClock {
+    #[inline]
+    fn now(&self) -> time::Instant {
+        self.start + self.elapsed()
+    }
+
+    #[inline]
+    fn sleep(&self, duration: time::Duration) {
+        self.advance(duration)
+    }
+
+    fn wait(&self, notifier: &Notifier, generation: u64, timeout: Option<time::Duration>) {
+        if notifier.generation() != generation {
+            return;
+        }
+        match timeout {
+            Some(timeout) => self.advance(timeout),
+            None => notifier.wait_real(generation, None),
+        }
+    }
+}
@@ -0,0 +1,376 @@
+//! Named pins from a configuration file
+//!
+//! Instead of spreading pin numbers over an application, pins are described once in a TOML or
+//! JSON file and looked up by name. Every table of the file describes a pin:
+//!
+//! ```toml
+//! [door_relay]
+//! pin = 24
+//! direction = "out"
+//! polarity = "active-low"
+//! initial = 0
+//!
+//! [estop]
+//! backend = "sysfs"
+//! pin = 17
+//! direction = "in"
+//! edge = "falling"
+//! ```
+//!
+//! The same file in JSON is an object of objects, e.g. `{"estop": {"pin": 17, "direction":
+//! "in"}}`. The keys of a pin are:
+//!
+//! * `pin`, the number of the pin, required
+//! * `direction`, `in` or `out`, required
+//! * `backend`, `sysfs` (the default) or `dummy`
+//! * `chip`, the label of the GPIO chip of a sysfs pin, making `pin` the offset within the chip
+//!   rather than the kernel number, which can change with the kernel
+//! * `polarity`, `active-high` (the default) or `active-low`, which inverts all values
+//! * `edge`, `none` (the default), `rising`, `falling` or `both`, only for inputs
+//! * `initial`, the value an output is set to when opened, `0`, `1`, `low` or `high`
+//!
+//! Values of active-low pins are inverted by the library, so `initial` and `edge` refer to the
+//! logical value rather than the level of the pin.
+//!
+//! Loading a file checks it completely, including conflicts between pins, before any pin is
+//! opened. A `PinRegistry` then opens all pins of a `Config` and hands them out by name. Pins
+//! addressed differently, by kernel number and by chip, are only compared by the registry, which
+//! resolves chips before opening any pin.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use gpio::{GpioIn, GpioOut, GpioValue};
+//! use gpio::config::{Config, ConfigError, PinRegistry};
+//!
+//! let config = Config::from_toml(r#"
+//!     [door_relay]
+//!     backend = "dummy"
+//!     pin = 24
+//!     direction = "out"
+//!     polarity = "active-low"
+//!     initial = 0
+//!
+//!     [estop]
+//!     backend = "dummy"
+//!     pin = 17
+//!     direction = "in"
+//! "#).unwrap();
+//!
+//! let mut pins = PinRegistry::open(&config).unwrap();
+//! let relay = pins.output("door_relay").unwrap();
+//! assert_eq!(relay.value(), Some(GpioValue::Low));
+//! relay.set_high().unwrap();
+//! assert_eq!(pins.input("estop").unwrap().read_value().unwrap(), GpioValue::Low);
+//!
+//! // conflicts are found before any pin is opened
+//! let conflict = Config::from_json(r#"{
+//!     "a": {"pin": 4, "direction": "in"},
+//!     "b": {"pin": 4, "direction": "out"}
+//! }"#);
+//! match conflict {
+//!     Err(ConfigError::Conflict(first, second)) => assert_eq!(first + &second, "ab"),
+//!     other => panic!("unexpected result: {:?}", other),
+//! }
+//! ```
+
+use std::{fmt, fs, io, path};
+use super::{GpioEdge, GpioValue};
+
+mod parse;
+mod registry;
+
+pub use self::registry::{NamedInput, NamedOutput, PinError, PinRegistry};
+
+use self::parse::{Scalar, Table};
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum ConfigError {
+        Io(err: io::Error) {
+            from()
+            description("io error")
+            display("I/O error: {}", err)
+            cause(err)
+        }
+        /// The file is not valid TOML or JSON, or uses unsupported features
+        Syntax(line: usize, message: String) {
+            description("syntax error")
+            display("syntax error in line {}: {}", line, message)
+        }
+        /// A pin is missing a key or has an invalid one
+        Invalid(name: String, message: String) {
+            description("invalid pin configuration")
+            display("invalid configuration of pin {:?}: {}", name, message)
+        }
+        /// Two pins use the same pin of the same backend
+        Conflict(first: String, second: String) {
+            description("pins configured twice")
+            display("pins {:?} and {:?} are the same", first, second)
+        }
+        /// A pin could not be opened
+        Open(name: String, err: PinError) {
+            description("failed to open pin")
+            display("failed to open pin {:?}: {}", name, err)
+            cause(err)
+        }
+        /// No pin of the name is configured
+        Unknown(name: String) {
+            description("unknown pin")
+            display("no pin named {:?}", name)
+        }
+        /// A pin was requested with the wrong direction
+        Direction(name: String, direction: Direction) {
+            description("pin has a different direction")
+            display("pin {:?} is an {}", name, direction)
+        }
+    }
+}
+
+pub type ConfigResult<T> = Result<T, ConfigError>;
+
+/// The driver used for a pin
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Backend {
+    /// The kernel's sysfs interface
+    Sysfs,
+    /// Dummy pins, for testing applications without hardware
+    Dummy,
+}
+
+/// Whether a pin is an input or an output
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Direction {
+    Input,
+    Output,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Direction::Input => "input",
+            Direction::Output => "output",
+        })
+    }
+}
+
+/// Which level of a pin is a logical 1
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Polarity {
+    ActiveHigh,
+    ActiveLow,
+}
+
+impl Polarity {
+    /// Convert between logical values and levels of a pin, which is the same in both directions
+    #[inline]
+    pub fn apply(self, value: GpioValue) -> GpioValue {
+        match (self, value) {
+            (Polarity::ActiveHigh, value) => value,
+            (Polarity::ActiveLow, GpioValue::Low) => GpioValue::High,
+            (Polarity::ActiveLow, GpioValue::High) => GpioValue::Low,
+        }
+    }
+}
+
+/// The configuration of a single pin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinConfig {
+    /// The name the pin is looked up by
+    pub name: String,
+    pub backend: Backend,
+    /// The number of the pin, as used by the backend, or the offset within `chip`
+    pub pin: u16,
+    /// The label of the GPIO chip of a sysfs pin
+    pub chip: Option<String>,
+    pub direction: Direction,
+    pub polarity: Polarity,
+    /// The edges an input signals, always `GpioEdge::None` for outputs
+    pub edge: GpioEdge,
+    /// The value an output is set to when opened, always `None` for inputs
+    pub initial: Option<GpioValue>,
+}
+
+/// The string of `value`, if it is one
+fn string(value: &Scalar) -> Option<&str> {
+    match *value {
+        Scalar::String(ref s) => Some(s),
+        _ => None,
+    }
+}
+
+fn backend(value: &Scalar) -> Option<Backend> {
+    match string(value)? {
+        "sysfs" => Some(Backend::Sysfs),
+        "dummy" => Some(Backend::Dummy),
+        _ => None,
+    }
+}
+
+fn pin(value: &Scalar) -> Option<u16> {
+    match *value {
+        Scalar::Integer(n) if n >= 0 && n <= i64::from(u16::MAX) => Some(n as u16),
+        _ => None,
+    }
+}
+
+fn direction(value: &Scalar) -> Option<Direction> {
+    match string(value)? {
+        "in" => Some(Direction::Input),
+        "out" => Some(Direction::Output),
+        _ => None,
+    }
+}
+
+fn polarity(value: &Scalar) -> Option<Polarity> {
+    match string(value)? {
+        "active-high" => Some(Polarity::ActiveHigh),
+        "active-low" => Some(Polarity::ActiveLow),
+        _ => None,
+    }
+}
+
+fn edge(value: &Scalar) -> Option<GpioEdge> {
+    match string(value)? {
+        "none" => Some(GpioEdge::None),
+        "rising" => Some(GpioEdge::Rising),
+        "falling" => Some(GpioEdge::Falling),
+        "both" => Some(GpioEdge::Both),
+        _ => None,
+    }
+}
+
+fn value(value: &Scalar) -> Option<GpioValue> {
+    match *value {
+        Scalar::Integer(0) => Some(GpioValue::Low),
+        Scalar::Integer(1) => Some(GpioValue::High),
+        Scalar::Bool(b) => Some(GpioValue::from(b)),
+        Scalar::String(ref s) if s == "low" => Some(GpioValue::Low),
+        Scalar::String(ref s) if s == "high" => Some(GpioValue::High),
+        _ => None,
+    }
+}
+
+impl PinConfig {
+    /// Read a pin from the entries of its table
+    fn from_table(table: Table) -> ConfigResult<PinConfig> {
+        let name = table.name;
+        let invalid = |message: &str| ConfigError::Invalid(name.clone(), message.to_owned());
+        let mut config = PinConfig {
+            name: name.clone(),
+            backend: Backend::Sysfs,
+            pin: 0,
+            chip: None,
+            direction: Direction::Input,
+            polarity: Polarity::ActiveHigh,
+            edge: GpioEdge::None,
+            initial: None,
+        };
+        let (mut pin_set, mut direction_set, mut edge_set) = (false, false, false);
+
+        for (key, scalar) in table.entries {
+            let bad = || match scalar {
+                Scalar::String(ref s) => invalid(&format!("invalid {} {:?}", key, s)),
+                Scalar::Bool(b) => invalid(&format!("invalid {} {}", key, b)),
+                Scalar::Integer(n) => invalid(&format!("invalid {} {}", key, n)),
+            };
+            match key.as_str() {
+                "backend" => config.backend = backend(&scalar).ok_or_else(bad)?,
+                "pin" => {
+                    config.pin = pin(&scalar).ok_or_else(bad)?;
+                    pin_set = true;
+                }
+                "direction" => {
+                    config.direction = direction(&scalar).ok_or_else(bad)?;
+                    direction_set = true;
+                }
+                "polarity" => config.polarity = polarity(&scalar).ok_or_else(bad)?,
+                "edge" => {
+                    config.edge = edge(&scalar).ok_or_else(bad)?;
+                    edge_set = true;
+                }
+                "chip" => config.chip = Some(string(&scalar).ok_or_else(bad)?.to_owned()),
+                "initial" => config.initial = Some(value(&scalar).ok_or_else(bad)?),
+                _ => return Err(invalid(&format!("unknown key {}", key))),
+            }
+        }
+
+        if !pin_set {
+            return Err(invalid("missing pin"));
+        }
+        if !direction_set {
+            return Err(invalid("missing direction"));
+        }
+        if config.chip.is_some() && config.backend != Backend::Sysfs {
+            return Err(invalid("only sysfs pins belong to a chip"));
+        }
+        match config.direction {
+            Direction::Input if config.initial.is_some() => {
+                Err(invalid("an input cannot have an initial value"))
+            }
+            Direction::Output if edge_set => Err(invalid("an output cannot signal edges")),
+            _ => Ok(config),
+        }
+    }
+}
+
+/// A checked set of pin configurations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pins: Vec<PinConfig>,
+}
+
+impl Config {
+    /// Load the file at `path`, as JSON if its extension is `json` and as TOML otherwise
+    pub fn load<P: AsRef<path::Path>>(path: P) -> ConfigResult<Config> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        if path.extension().is_some_and(|extension| extension == "json") {
+            Config::from_json(&text)
+        } else {
+            Config::from_toml(&text)
+        }
+    }
+
+    /// Parse a configuration in TOML
+    pub fn from_toml(text: &str) -> ConfigResult<Config> {
+        Config::from_tables(parse::toml(text)?)
+    }
+
+    /// Parse a configuration in JSON
+    pub fn from_json(text: &str) -> ConfigResult<Config> {
+        Config::from_tables(parse::json(text)?)
+    }
+
+    fn from_tables(tables: Vec<Table>) -> ConfigResult<Config> {
+        let mut pins: Vec<PinConfig> = Vec::with_capacity(tables.len());
+        for table in tables {
+            if pins.iter().any(|pin| pin.name == table.name) {
+                let message = format!("pin defined again in line {}", table.line);
+                return Err(ConfigError::Invalid(table.name, message));
+            }
+            let config = PinConfig::from_table(table)?;
+            let same = pins
+                .iter()
+                .find(|pin| {
+                    (pin.backend, &pin.chip, pin.pin) == (config.backend, &config.chip, config.pin)
+                });
+            if let Some(first) = same {
+                return Err(ConfigError::Conflict(first.name.clone(), config.name));
+            }
+            pins.push(config);
+        }
+        Ok(Config { pins })
+    }
+
+    /// All pins, in the order of the file
+    #[inline]
+    pub fn pins(&self) -> &[PinConfig] {
+        &self.pins
+    }
+
+    /// The pin named `name`
+    pub fn get(&self, name: &str) -> Option<&PinConfig> {
+        self.pins.iter().find(|pin| pin.name == name)
+    }
+}
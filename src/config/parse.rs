@@ -0,0 +1,225 @@
+//! The subsets of TOML and JSON used by configuration files
+//!
+//! Both formats are parsed into a list of named tables of scalar values, nested tables, arrays
+//! and floating point numbers are not supported.
+
+use std::iter::Peekable;
+use std::str::Chars;
+use super::{ConfigError, ConfigResult};
+
+/// A value of a table entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scalar {
+    Bool(bool),
+    Integer(i64),
+    String(String),
+}
+
+/// A named table, with the line it starts on
+#[derive(Debug)]
+pub struct Table {
+    pub name: String,
+    pub line: usize,
+    pub entries: Vec<(String, Scalar)>,
+}
+
+fn syntax<T>(line: usize, message: &str) -> ConfigResult<T> {
+    Err(ConfigError::Syntax(line, message.to_owned()))
+}
+
+/// The text of a string after its opening quote, up to and without the closing quote
+fn string(chars: &mut Peekable<Chars>, line: usize) -> ConfigResult<String> {
+    let mut string = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(string),
+            Some('\\') => string.push(match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some(c @ ('"' | '\\' | '/')) => c,
+                _ => return syntax(line, "invalid escape sequence"),
+            }),
+            Some('\n') | None => return syntax(line, "unterminated string"),
+            Some(c) => string.push(c),
+        }
+    }
+}
+
+/// A bare word: a number, `true`, `false` or a TOML key
+fn word(chars: &mut Peekable<Chars>) -> String {
+    let mut word = String::new();
+    while let Some(&c) = chars.peek() {
+        if !(c.is_ascii_alphanumeric() || "+-_.".contains(c)) {
+            break;
+        }
+        word.push(c);
+        chars.next();
+    }
+    word
+}
+
+/// A scalar value starting at the next character
+fn scalar(chars: &mut Peekable<Chars>, line: usize) -> ConfigResult<Scalar> {
+    if chars.peek() == Some(&'"') {
+        chars.next();
+        return Ok(Scalar::String(string(chars, line)?));
+    }
+    match word(chars).as_str() {
+        "" => syntax(line, "expected a value"),
+        "true" => Ok(Scalar::Bool(true)),
+        "false" => Ok(Scalar::Bool(false)),
+        word => match word.replace('_', "").parse() {
+            Ok(number) => Ok(Scalar::Integer(number)),
+            Err(_) => syntax(line, &format!("invalid value {}", word)),
+        },
+    }
+}
+
+/// Skip spaces and tabs
+fn blank(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|&c| c == ' ' || c == '\t') {
+        chars.next();
+    }
+}
+
+/// A key of a TOML table header or entry, bare or quoted
+fn key(chars: &mut Peekable<Chars>, line: usize) -> ConfigResult<String> {
+    if chars.peek() == Some(&'"') {
+        chars.next();
+        return string(chars, line);
+    }
+    match word(chars) {
+        ref key if key.is_empty() || key.contains('.') => syntax(line, "invalid key"),
+        key => Ok(key),
+    }
+}
+
+/// Parse TOML consisting of tables with scalar entries, e.g. `[name]` followed by `pin = 17`
+pub fn toml(text: &str) -> ConfigResult<Vec<Table>> {
+    let mut tables: Vec<Table> = Vec::new();
+    for (index, text) in text.lines().enumerate() {
+        let line = index + 1;
+        let mut chars = text.chars().peekable();
+        blank(&mut chars);
+        match chars.peek() {
+            None | Some('#') => continue,
+            Some('[') => {
+                chars.next();
+                blank(&mut chars);
+                let name = key(&mut chars, line)?;
+                blank(&mut chars);
+                if chars.next() != Some(']') {
+                    return syntax(line, "expected ] after table name");
+                }
+                tables.push(Table {
+                    name,
+                    line,
+                    entries: Vec::new(),
+                });
+            }
+            Some(_) => {
+                let name = key(&mut chars, line)?;
+                blank(&mut chars);
+                if chars.next() != Some('=') {
+                    return syntax(line, "expected = after key");
+                }
+                blank(&mut chars);
+                let value = scalar(&mut chars, line)?;
+                match tables.last_mut() {
+                    Some(table) => table.entries.push((name, value)),
+                    None => return syntax(line, "entries have to be part of a table"),
+                }
+            }
+        }
+        blank(&mut chars);
+        if chars.next().is_some_and(|c| c != '#') {
+            return syntax(line, "unexpected text at end of line");
+        }
+    }
+    Ok(tables)
+}
+
+/// A JSON reader keeping track of the current line
+struct Json<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+}
+
+impl<'a> Json<'a> {
+    /// Skip whitespace, then return the next character without consuming it
+    fn peek(&mut self) -> Option<char> {
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            if c == '\n' {
+                self.line += 1;
+            }
+            self.chars.next();
+        }
+        self.chars.peek().cloned()
+    }
+
+    fn expect(&mut self, expected: char) -> ConfigResult<()> {
+        if self.peek() == Some(expected) {
+            self.chars.next();
+            Ok(())
+        } else {
+            syntax(self.line, &format!("expected {}", expected))
+        }
+    }
+
+    /// The entries of an object, each parsed by `entry`
+    fn object<F>(&mut self, mut entry: F) -> ConfigResult<()>
+    where
+        F: FnMut(&mut Self, String, usize) -> ConfigResult<()>,
+    {
+        self.expect('{')?;
+        if self.peek() == Some('}') {
+            self.chars.next();
+            return Ok(());
+        }
+        loop {
+            self.expect('"')?;
+            let line = self.line;
+            let key = string(&mut self.chars, line)?;
+            self.expect(':')?;
+            entry(self, key, line)?;
+            match self.peek() {
+                Some(',') => self.chars.next(),
+                Some('}') => {
+                    self.chars.next();
+                    return Ok(());
+                }
+                _ => return syntax(self.line, "expected , or }"),
+            };
+        }
+    }
+}
+
+/// Parse a JSON object of objects with scalar values, e.g. `{"name": {"pin": 17}}`
+pub fn json(text: &str) -> ConfigResult<Vec<Table>> {
+    let mut json = Json {
+        chars: text.chars().peekable(),
+        line: 1,
+    };
+    let mut tables = Vec::new();
+    json.object(|json, name, line| {
+        let mut entries = Vec::new();
+        json.object(|json, key, line| {
+            json.peek();
+            entries.push((key, scalar(&mut json.chars, line)?));
+            Ok(())
+        })?;
+        tables.push(Table {
+            name,
+            line,
+            entries,
+        });
+        Ok(())
+    })?;
+    if json.peek().is_some() {
+        return syntax(json.line, "unexpected text after object");
+    }
+    Ok(tables)
+}
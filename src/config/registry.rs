@@ -0,0 +1,334 @@
+//! Opening configured pins and looking them up by name
+
+use std::{collections, fmt};
+use super::super::{GpioEdge, GpioIn, GpioOut, GpioValue};
+use dummy::{DummyError, DummyGpioIn, DummyGpioOut};
+use sysfs::{chips, GpioError, KernelTree, SysFsGpioInput, SysFsGpioOutput, SysFsTree};
+use super::{Backend, Config, ConfigError, ConfigResult, Direction, PinConfig, Polarity};
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum PinError {
+        Sysfs(err: GpioError) {
+            from()
+            description("sysfs error")
+            display("sysfs error: {}", err)
+            cause(err)
+        }
+        Dummy(err: DummyError) {
+            from()
+            description("dummy pin error")
+            display("dummy pin error: {}", err)
+            cause(err)
+        }
+    }
+}
+
+enum InputGpio {
+    Sysfs(SysFsGpioInput),
+    Dummy(DummyGpioIn),
+}
+
+impl fmt::Debug for InputGpio {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InputGpio::Sysfs(ref gpio) => f.debug_tuple("Sysfs").field(gpio).finish(),
+            InputGpio::Dummy(_) => f.debug_tuple("Dummy").finish(),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum OutputGpio {
+    Sysfs(SysFsGpioOutput),
+    Dummy(DummyGpioOut<fn(GpioValue)>),
+}
+
+/// A configured input
+///
+/// Values are inverted for active-low pins.
+#[derive(Debug)]
+pub struct NamedInput {
+    config: PinConfig,
+    gpio: InputGpio,
+}
+
+impl NamedInput {
+    /// The configuration the pin was opened with
+    #[inline]
+    pub fn config(&self) -> &PinConfig {
+        &self.config
+    }
+
+    /// The sysfs pin, e.g. to add it to a `SysFsGpioEdgeIter`
+    ///
+    /// The pin reports levels, which are not inverted for active-low pins.
+    pub fn sysfs(&self) -> Option<&SysFsGpioInput> {
+        match self.gpio {
+            InputGpio::Sysfs(ref gpio) => Some(gpio),
+            InputGpio::Dummy(_) => None,
+        }
+    }
+
+    /// The dummy pin, e.g. to change its level using `DummyGpioIn::set`
+    ///
+    /// Dummy inputs start with the level of a logical 0.
+    pub fn dummy(&self) -> Option<&DummyGpioIn> {
+        match self.gpio {
+            InputGpio::Sysfs(_) => None,
+            InputGpio::Dummy(ref gpio) => Some(gpio),
+        }
+    }
+}
+
+impl GpioIn for NamedInput {
+    type Error = PinError;
+
+    fn read_value(&self) -> Result<GpioValue, PinError> {
+        let level = match self.gpio {
+            InputGpio::Sysfs(ref gpio) => gpio.read_value()?,
+            InputGpio::Dummy(ref gpio) => gpio.read_value()?,
+        };
+        Ok(self.config.polarity.apply(level))
+    }
+
+    /// Signal logical `edge`s, i.e. the opposite edges of active-low pins
+    fn set_edge(&mut self, edge: GpioEdge) -> Result<(), PinError> {
+        let edge = match (self.config.polarity, edge) {
+            (Polarity::ActiveLow, GpioEdge::Rising) => GpioEdge::Falling,
+            (Polarity::ActiveLow, GpioEdge::Falling) => GpioEdge::Rising,
+            (_, edge) => edge,
+        };
+        match self.gpio {
+            InputGpio::Sysfs(ref mut gpio) => gpio.set_edge(edge)?,
+            InputGpio::Dummy(ref mut gpio) => gpio.set_edge(edge)?,
+        }
+        Ok(())
+    }
+}
+
+/// A configured output
+///
+/// Values are inverted for active-low pins.
+#[derive(Debug)]
+pub struct NamedOutput {
+    config: PinConfig,
+    gpio: OutputGpio,
+    value: Option<GpioValue>,
+}
+
+impl NamedOutput {
+    /// The configuration the pin was opened with
+    #[inline]
+    pub fn config(&self) -> &PinConfig {
+        &self.config
+    }
+
+    /// The logical value last set, `None` if the pin has no initial value and was not set yet
+    #[inline]
+    pub fn value(&self) -> Option<GpioValue> {
+        self.value
+    }
+}
+
+impl GpioOut for NamedOutput {
+    type Error = PinError;
+
+    #[inline]
+    fn set_low(&mut self) -> Result<(), PinError> {
+        self.set_value(GpioValue::Low)
+    }
+
+    #[inline]
+    fn set_high(&mut self) -> Result<(), PinError> {
+        self.set_value(GpioValue::High)
+    }
+
+    fn set_value<T: Into<GpioValue> + Copy>(&mut self, value: T) -> Result<(), PinError> {
+        let value = value.into();
+        let level = self.config.polarity.apply(value);
+        match self.gpio {
+            OutputGpio::Sysfs(ref mut gpio) => gpio.set_value(level)?,
+            OutputGpio::Dummy(ref mut gpio) => gpio.set_value(level)?,
+        }
+        self.value = Some(value);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+enum Named {
+    Input(NamedInput),
+    Output(NamedOutput),
+}
+
+/// The pins of a `Config`, opened and looked up by name
+///
+/// Pins taken out of the registry with `take_input` and `take_output` are no longer available
+/// by name.
+#[derive(Debug)]
+pub struct PinRegistry {
+    pins: collections::BTreeMap<String, Named>,
+}
+
+impl PinRegistry {
+    /// Open all pins of `config`, using the kernel's sysfs tree
+    #[inline]
+    pub fn open(config: &Config) -> ConfigResult<PinRegistry> {
+        Self::open_at(config, KernelTree::new())
+    }
+
+    /// Open all pins of `config`, using `tree` for sysfs pins
+    ///
+    /// Sysfs pins addressed by chip are checked for conflicts with all other sysfs pins before
+    /// any pin is opened. Pins opened before one fails are closed again.
+    ///
+    /// Outputs with an `initial` value start out driven with it, an active-low relay switched
+    /// off at start is never switched on in between:
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "fake-sysfs")]
+    /// # fn main() {
+    /// use gpio::GpioValue;
+    /// use gpio::config::{Config, PinRegistry};
+    /// use gpio::sysfs::fake::FakeSysFs;
+    ///
+    /// let config = Config::from_toml(r#"
+    ///     [door_relay]
+    ///     pin = 24
+    ///     direction = "out"
+    ///     polarity = "active-low"
+    ///     initial = 0
+    /// "#).unwrap();
+    ///
+    /// let fake = FakeSysFs::new(32).unwrap();
+    /// let mut pins = PinRegistry::open_at(&config, fake.clone()).unwrap();
+    /// assert_eq!(pins.output("door_relay").unwrap().value(), Some(GpioValue::Low));
+    /// assert_eq!(fake.history(24), vec![GpioValue::High]);
+    /// # }
+    /// # #[cfg(not(feature = "fake-sysfs"))]
+    /// # fn main() {}
+    /// ```
+    pub fn open_at<T: SysFsTree + Clone + 'static>(
+        config: &Config,
+        tree: T,
+    ) -> ConfigResult<PinRegistry> {
+        // pins addressed by chip are compared to the others once resolved, which only reads
+        // the attributes of the chips
+        let mut gpio_nums: Vec<u16> = Vec::with_capacity(config.pins().len());
+        for (index, pin) in config.pins().iter().enumerate() {
+            let gpio_num = match (pin.backend, &pin.chip) {
+                (Backend::Sysfs, Some(label)) => chips::find_at(&tree, label)
+                    .and_then(|chip| chip.gpio_num(pin.pin))
+                    .map_err(|err| ConfigError::Open(pin.name.clone(), PinError::Sysfs(err)))?,
+                _ => pin.pin,
+            };
+            let same = config.pins()[..index]
+                .iter()
+                .zip(&gpio_nums)
+                .find(|&(other, &other_num)| other.backend == pin.backend && other_num == gpio_num);
+            if let Some((first, _)) = same {
+                return Err(ConfigError::Conflict(first.name.clone(), pin.name.clone()));
+            }
+            gpio_nums.push(gpio_num);
+        }
+
+        let mut pins = collections::BTreeMap::new();
+        for (pin, &gpio_num) in config.pins().iter().zip(&gpio_nums) {
+            let named = Self::open_pin(pin, gpio_num, &tree)
+                .map_err(|err| ConfigError::Open(pin.name.clone(), err))?;
+            pins.insert(pin.name.clone(), named);
+        }
+        Ok(PinRegistry { pins })
+    }
+
+    /// Open the pin `config`, which is sysfs pin `gpio_num` if a sysfs pin
+    fn open_pin<T: SysFsTree + Clone + 'static>(
+        config: &PinConfig,
+        gpio_num: u16,
+        tree: &T,
+    ) -> Result<Named, PinError> {
+        match config.direction {
+            Direction::Input => {
+                let gpio = match config.backend {
+                    Backend::Sysfs => {
+                        InputGpio::Sysfs(SysFsGpioInput::open_at(tree.clone(), gpio_num)?)
+                    }
+                    Backend::Dummy => InputGpio::Dummy(DummyGpioIn::with_value(
+                        config.polarity.apply(GpioValue::Low),
+                    )),
+                };
+                let mut input = NamedInput {
+                    config: config.clone(),
+                    gpio,
+                };
+                input.set_edge(config.edge)?;
+                Ok(Named::Input(input))
+            }
+            Direction::Output => {
+                let gpio = match config.backend {
+                    Backend::Sysfs => OutputGpio::Sysfs(match config.initial {
+                        // direction and level are written at once, so the pin never glitches
+                        Some(initial) => SysFsGpioOutput::open_with_value_at(
+                            tree.clone(),
+                            gpio_num,
+                            config.polarity.apply(initial),
+                        )?,
+                        None => SysFsGpioOutput::open_at(tree.clone(), gpio_num)?,
+                    }),
+                    Backend::Dummy => OutputGpio::Dummy(DummyGpioOut::new(drop)),
+                };
+                Ok(Named::Output(NamedOutput {
+                    config: config.clone(),
+                    gpio,
+                    value: config.initial,
+                }))
+            }
+        }
+    }
+
+    /// The names of all pins in the registry, sorted
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.pins.keys().map(String::as_str)
+    }
+
+    /// The input named `name`
+    pub fn input(&self, name: &str) -> ConfigResult<&NamedInput> {
+        match self.pins.get(name) {
+            Some(Named::Input(input)) => Ok(input),
+            Some(Named::Output(_)) => {
+                Err(ConfigError::Direction(name.to_owned(), Direction::Output))
+            }
+            None => Err(ConfigError::Unknown(name.to_owned())),
+        }
+    }
+
+    /// The output named `name`
+    pub fn output(&mut self, name: &str) -> ConfigResult<&mut NamedOutput> {
+        match self.pins.get_mut(name) {
+            Some(Named::Output(output)) => Ok(output),
+            Some(Named::Input(_)) => {
+                Err(ConfigError::Direction(name.to_owned(), Direction::Input))
+            }
+            None => Err(ConfigError::Unknown(name.to_owned())),
+        }
+    }
+
+    /// Take the input named `name` out of the registry, e.g. to move it to another thread
+    pub fn take_input(&mut self, name: &str) -> ConfigResult<NamedInput> {
+        self.input(name)?;
+        match self.pins.remove(name) {
+            Some(Named::Input(input)) => Ok(input),
+            _ => unreachable!("checked to be an input"),
+        }
+    }
+
+    /// Take the output named `name` out of the registry, e.g. to move it to another thread
+    pub fn take_output(&mut self, name: &str) -> ConfigResult<NamedOutput> {
+        self.output(name)?;
+        match self.pins.remove(name) {
+            Some(Named::Output(output)) => Ok(output),
+            _ => unreachable!("checked to be an output"),
+        }
+    }
+}
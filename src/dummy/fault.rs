@@ -0,0 +1,202 @@
+//! Errors and fault injection for dummy pins
+
+use std::{fmt, sync, time};
+use super::super::GpioValue;
+use clock::{Clock, SystemClock};
+
+quick_error! {
+    /// Errors of dummy pins
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum DummyError {
+        /// A failure injected on the given call using `Faults::fail_on`
+        FailedCall(call: u64) {
+            description("injected failure")
+            display("injected failure on call {}", call)
+        }
+        /// A failure injected at random using `Faults::probability`
+        Random(call: u64) {
+            description("injected random failure")
+            display("injected random failure on call {}", call)
+        }
+        /// A failure injected using `Faults::fail_between`, at the given time since the faults
+        /// were created
+        Window(elapsed: time::Duration) {
+            description("injected failure within a failure window")
+            display("injected failure within a failure window at {:?}", elapsed)
+        }
+        /// A failure recorded by `record::Recording` and returned by `Replay`
+        Replayed(message: String) {
+            description("replayed failure")
+            display("replayed failure: {}", message)
+        }
+        /// An access of a replayed pin that does not match the log
+        Diverged(reason: String) {
+            description("replay diverged from the log")
+            display("replay diverged from the log: {}", reason)
+        }
+        /// No edge occurred before the timeout of an edge iterator
+        Timeout {
+            description("timed out waiting for an edge")
+        }
+    }
+}
+
+struct FaultState {
+    clock: Box<dyn Clock>,
+    start: time::Instant,
+    calls: u64,
+    fail_on: Vec<u64>,
+    probability: f64,
+    rng: u64,
+    windows: Vec<(time::Duration, time::Duration)>,
+    stuck: Option<GpioValue>,
+}
+
+impl FaultState {
+    /// xorshift64*, good enough to scatter failures and reproducible given the seed
+    fn roll(&mut self) -> f64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let bits = self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11;
+        bits as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Scatter the bits of `seed`, so seeds differing in a single bit give unrelated sequences
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Failures injected into dummy pins
+///
+/// Every access of a pin (reading, writing or setting its edge) counts as a call, starting at 1.
+/// A call fails if any of the configured conditions applies. Clones share the same configuration
+/// and call counter, so one set of faults can be attached to multiple pins.
+#[derive(Clone)]
+pub struct Faults {
+    state: sync::Arc<sync::Mutex<FaultState>>,
+}
+
+impl fmt::Debug for Faults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state();
+        f.debug_struct("Faults")
+            .field("calls", &state.calls)
+            .field("fail_on", &state.fail_on)
+            .field("probability", &state.probability)
+            .field("windows", &state.windows)
+            .field("stuck", &state.stuck)
+            .finish()
+    }
+}
+
+impl Faults {
+    /// Create a configuration that does not inject any faults yet
+    #[inline]
+    pub fn new() -> Faults {
+        Self::with_clock(SystemClock)
+    }
+
+    /// Create a configuration whose failure windows are measured using `clock`
+    pub fn with_clock<C: Clock + 'static>(clock: C) -> Faults {
+        Faults {
+            state: sync::Arc::new(sync::Mutex::new(FaultState {
+                start: clock.now(),
+                clock: Box::new(clock),
+                calls: 0,
+                fail_on: Vec::new(),
+                probability: 0.0,
+                rng: 0x9E37_79B9_7F4A_7C15,
+                windows: Vec::new(),
+                stuck: None,
+            })),
+        }
+    }
+
+    #[inline]
+    fn state(&self) -> sync::MutexGuard<'_, FaultState> {
+        self.state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// Fail the `call`-th call
+    pub fn fail_on(self, call: u64) -> Self {
+        self.state().fail_on.push(call);
+        self
+    }
+
+    /// Fail every call with the given probability between 0 and 1
+    pub fn probability(self, probability: f64) -> Self {
+        self.state().probability = probability;
+        self
+    }
+
+    /// Seed the random number generator used for `probability`
+    ///
+    /// The same seed always results in the same calls failing.
+    pub fn seed(self, seed: u64) -> Self {
+        // xorshift gets stuck at zero, which only a single seed is mixed into
+        self.state().rng = splitmix64(seed).max(1);
+        self
+    }
+
+    /// Fail all calls from `from` until `until` after the faults were created
+    pub fn fail_between(self, from: time::Duration, until: time::Duration) -> Self {
+        self.state().windows.push((from, until));
+        self
+    }
+
+    /// Make pins stuck at `value`
+    ///
+    /// Inputs read `value` regardless of their actual value, outputs pass on `value` regardless
+    /// of what is written to them.
+    pub fn stuck_at<V: Into<GpioValue>>(self, value: V) -> Self {
+        self.state().stuck = Some(value.into());
+        self
+    }
+
+    /// Number of calls so far
+    #[inline]
+    pub fn calls(&self) -> u64 {
+        self.state().calls
+    }
+
+    /// Count a call and decide whether it fails
+    pub(crate) fn call(&self) -> Result<(), DummyError> {
+        let mut state = self.state();
+        state.calls += 1;
+        let call = state.calls;
+
+        if state.fail_on.contains(&call) {
+            return Err(DummyError::FailedCall(call));
+        }
+        let elapsed = state.clock.now().duration_since(state.start);
+        if state
+            .windows
+            .iter()
+            .any(|&(from, until)| elapsed >= from && elapsed < until)
+        {
+            return Err(DummyError::Window(elapsed));
+        }
+        if state.probability > 0.0 && state.roll() < state.probability {
+            return Err(DummyError::Random(call));
+        }
+        Ok(())
+    }
+
+    /// The value a pin actually has if its real value is `value`
+    #[inline]
+    pub(crate) fn apply(&self, value: GpioValue) -> GpioValue {
+        self.state().stuck.unwrap_or(value)
+    }
+}
+
+impl Default for Faults {
+    #[inline]
+    fn default() -> Faults {
+        Faults::new()
+    }
+}
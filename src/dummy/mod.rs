@@ -0,0 +1,692 @@
+//! GPIO dummy input/output
+//!
+//! The dummy module can be used instead of a GPIO implementation tied to
+//! hardware to run unit tests or otherwise provide means to test an
+//! application when no embedded device is around.
+//!
+//! It supports the same interface as other GPIOs and its input and output
+//! behaviour can be configured in a flexible manner.
+//!
+//! ## Example
+//!
+//! The `DummyGpioIn` reads values from a callback:
+//!
+//! ```rust
+//! use std::time;
+//! use gpio::{GpioIn, GpioValue};
+//! use gpio::dummy::DummyGpioIn;
+//!
+//! // a simple dummy gpio that is always `true`/`High`
+//! let mut dg = DummyGpioIn::new(|| true);
+//! assert_eq!(GpioValue::High, dg.read_value().unwrap());
+//!
+//! // another example that flips every second
+//! let mut timed_gpio = DummyGpioIn::new(|| {
+//!     std::time::SystemTime::now()
+//!         .duration_since(time::UNIX_EPOCH)
+//!         .unwrap()
+//!         .as_secs() % 2 == 0
+//! });
+//! println!("timed: {:?}", timed_gpio.read_value().unwrap());
+//! ```
+//!
+//! Timing is measured through a `Clock`. Using a `VirtualClock`, a timed script can be played back
+//! through the edge iterator without waiting for it:
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use gpio::{GpioEdge, GpioIn};
+//! use gpio::clock::{Clock, VirtualClock};
+//! use gpio::dummy::{DummyEdgeIter, DummyGpioIn};
+//!
+//! let clock = VirtualClock::new();
+//! let ms = Duration::from_millis;
+//! let mut dg = DummyGpioIn::scripted(
+//!     clock.clone(),
+//!     vec![(ms(0), false), (ms(500), true), (ms(510), false)],
+//! );
+//! dg.set_edge(GpioEdge::Rising).unwrap();
+//!
+//! let start = clock.now();
+//! let mut edges = DummyEdgeIter::with_clock(clock.clone()).unwrap();
+//! edges.timeout_ms(1000).add(&dg).unwrap();
+//!
+//! assert!(edges.next().unwrap().is_ok());
+//! assert_eq!(clock.now() - start, ms(500));
+//!
+//! // there is no further rising edge, the iterator times out after one virtual second
+//! assert!(edges.next().unwrap().is_err());
+//! ```
+//!
+//! Captured traces are played back from a `Timeline`, loaded from CSV (e.g. exported by a logic
+//! analyzer) or from a Value Change Dump. Timelines can be looped and sped up or slowed down:
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use gpio::{GpioEdge, GpioIn};
+//! use gpio::clock::{Clock, VirtualClock};
+//! use gpio::dummy::{DummyEdgeIter, DummyGpioIn, Timeline};
+//!
+//! let csv = "Time [s],Channel 0\n\
+//!            0.000,1\n\
+//!            0.100,0\n\
+//!            0.102,1\n\
+//!            0.200,1\n";
+//! let timeline = Timeline::from_csv(csv.as_bytes())
+//!     .unwrap()
+//!     .looping(true)
+//!     .speed(2.0)
+//!     .unwrap();
+//! assert_eq!(timeline.duration(), Duration::from_millis(100));
+//!
+//! let clock = VirtualClock::new();
+//! let mut line = DummyGpioIn::playback(clock.clone(), timeline);
+//! line.set_edge(GpioEdge::Falling).unwrap();
+//!
+//! let start = clock.now();
+//! let mut edges = DummyEdgeIter::with_clock(clock.clone()).unwrap();
+//! edges.add(&line).unwrap();
+//!
+//! // the 2 ms pulse is played back as a 1 ms pulse, every 100 ms
+//! for n in 0..3 {
+//!     assert!(edges.next().unwrap().is_ok());
+//!     assert_eq!(clock.now() - start, Duration::from_millis(50 + n * 100));
+//! }
+//! ```
+//!
+//! Pins created with `DummyGpioIn::with_value` are changed using `set`. Waiting edge iterators
+//! are woken up immediately and see every change, even pulses that are shorter than a poll:
+//!
+//! ```rust
+//! use gpio::{GpioEdge, GpioIn, GpioValue};
+//! use gpio::dummy::{DummyEdgeIter, DummyGpioIn};
+//!
+//! let line = DummyGpioIn::with_value(false);
+//! let mut irq = line.clone();
+//! irq.set_edge(GpioEdge::Rising).unwrap();
+//!
+//! let mut edges = DummyEdgeIter::new().unwrap();
+//! edges.timeout_ms(10).add(&irq).unwrap();
+//!
+//! line.set(true);
+//! line.set(false);
+//! assert_eq!(irq.read_value(), Ok(GpioValue::Low));
+//! assert!(edges.next().unwrap().is_ok());
+//! assert!(edges.next().unwrap().is_err());
+//! ```
+//!
+//! Output can simple be swallowed by a dummy output port:
+//!
+//! ```rust
+//! use gpio::{GpioOut};
+//! use gpio::dummy::DummyGpioOut;
+//!
+//! let mut dg = DummyGpioOut::new(|_| ());
+//! dg.set_value(true);
+//! ```
+//!
+//! To test error handling, failures can be injected into dummy pins. Calls fail on a given count,
+//! at random or during a time window, and pins can be stuck at a value:
+//!
+//! ```rust
+//! use gpio::{GpioIn, GpioOut, GpioValue};
+//! use gpio::dummy::{DummyError, DummyGpioIn, DummyGpioOut, Faults};
+//!
+//! let mut relay = DummyGpioOut::new(|_| ()).faults(Faults::new().fail_on(2));
+//! assert!(relay.set_high().is_ok());
+//! assert_eq!(relay.set_low(), Err(DummyError::FailedCall(2)));
+//!
+//! // a retry succeeds
+//! assert!(relay.set_low().is_ok());
+//!
+//! let flaky = Faults::new().probability(0.5).seed(42);
+//! let sensor = DummyGpioIn::with_value(true).faults(flaky.clone());
+//! let failures = (0..1000).filter(|_| sensor.read_value().is_err()).count();
+//! assert!(failures > 400 && failures < 600);
+//! assert_eq!(flaky.calls(), 1000);
+//!
+//! let shorted = DummyGpioIn::with_value(true).faults(Faults::new().stuck_at(false));
+//! assert_eq!(shorted.read_value(), Ok(GpioValue::Low));
+//! ```
+//!
+//! Dummy pins can stand in for real ones in threaded code. Here, an output is toggled in the
+//! background, its values are passed on to an input through a channel:
+//!
+//! ```rust
+//! use std::sync::{mpsc, Mutex};
+//! use std::thread;
+//! use gpio::{GpioIn, GpioOut, GpioValue};
+//! use gpio::dummy::{DummyGpioIn, DummyGpioOut};
+//!
+//! let (tx, rx) = mpsc::channel();
+//! let tx = Mutex::new(tx);
+//! let mut out = DummyGpioOut::new(move |v| tx.lock().unwrap().send(v).unwrap());
+//! let input = DummyGpioIn::with_value(false);
+//!
+//! let toggler = thread::spawn(move || {
+//!     let mut value = false;
+//!     for _ in 0..3 {
+//!         value = !value;
+//!         out.set_value(value).unwrap();
+//!     }
+//! });
+//! let follower = {
+//!     let input = input.clone();
+//!     thread::spawn(move || {
+//!         for value in rx {
+//!             input.set(value);
+//!         }
+//!     })
+//! };
+//!
+//! toggler.join().unwrap();
+//! follower.join().unwrap();
+//! assert_eq!(input.read_value(), Ok(GpioValue::High));
+//! ```
+//!
+//! To check what was written, a `RecordingGpioOut` stores all transitions with their time. A
+//! `Recorder` puts multiple outputs on a common timeline that can be saved as a Value Change Dump
+//! and viewed with tools like GTKWave:
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use gpio::{GpioOut, GpioValue};
+//! use gpio::clock::{Clock, VirtualClock};
+//! use gpio::dummy::Recorder;
+//!
+//! let clock = VirtualClock::new();
+//! let recorder = Recorder::with_clock(clock.clone());
+//! let mut clk = recorder.output("clk");
+//! let mut data = recorder.output("data");
+//!
+//! data.set_high().unwrap();
+//! clk.set_high().unwrap();
+//! clock.sleep(Duration::from_micros(10));
+//! clk.set_low().unwrap();
+//!
+//! assert_eq!(data.transitions().len(), 1);
+//! clk.assert_pulse(GpioValue::High, Duration::from_micros(10), Duration::from_micros(1));
+//!
+//! let mut vcd = Vec::new();
+//! recorder.write_vcd(&mut vcd).unwrap();
+//! ```
+//!
+//! Outputs and inputs can be connected through a `Net`, e.g. to loop back a bit-banged protocol.
+//! A net resolves multiple drivers and can be pulled up or down while not driven:
+//!
+//! ```rust
+//! use std::thread;
+//! use gpio::{GpioEdge, GpioIn, GpioOut, GpioValue, Pull};
+//! use gpio::dummy::{DummyEdgeIter, Net, NetError, Resolution};
+//!
+//! // an open-drain bus line, like I2C's SDA
+//! let sda = Net::new().resolution(Resolution::WiredAnd).pull(Pull::Up);
+//! let mut master = sda.output();
+//! let mut slave = sda.output();
+//! let probe = sda.input();
+//! assert_eq!(probe.read_value(), Ok(GpioValue::High));
+//!
+//! // the slave acknowledges from another thread
+//! let slave = thread::spawn(move || {
+//!     slave.set_low().unwrap();
+//!     slave
+//! }).join().unwrap();
+//! master.set_high().unwrap();
+//! assert_eq!(probe.read_value(), Ok(GpioValue::Low));
+//!
+//! // dropping an output releases the net
+//! drop(slave);
+//! assert_eq!(probe.read_value(), Ok(GpioValue::High));
+//!
+//! // edges of an input are waited for through its dummy pin
+//! let mut start = sda.input();
+//! start.set_edge(GpioEdge::Falling).unwrap();
+//! let mut edges = DummyEdgeIter::new().unwrap();
+//! edges.timeout_ms(10).add(start.dummy()).unwrap();
+//! master.set_low().unwrap();
+//! assert!(edges.next().unwrap().is_ok());
+//!
+//! // push-pull outputs fight over a net without a pull resistor
+//! let wire = Net::new();
+//! let mut a = wire.output();
+//! let mut b = wire.output();
+//! assert_eq!(wire.value(), Err(NetError::Floating));
+//! a.set_high().unwrap();
+//! assert_eq!(b.set_low(), Err(NetError::Conflict));
+//! ```
+
+use std::{fmt, sync, time};
+use std::collections::VecDeque;
+use super::{GpioEdge, GpioIn, GpioOut, GpioValue};
+use clock::{Clock, Notifier, SystemClock};
+use self::playback::Playback;
+
+mod fault;
+mod net;
+mod playback;
+mod recorder;
+mod replay;
+
+pub use self::fault::{DummyError, Faults};
+pub use self::net::{Net, NetError, NetGpioIn, NetGpioOut, Resolution};
+pub use self::playback::{PlaybackError, PlaybackResult, Timeline};
+pub use self::recorder::{Pulse, Recorder, RecordingGpioOut, Transition};
+pub use self::replay::{Replay, ReplayGpioIn, ReplayGpioOut};
+
+/// Dummy GPIO input pin
+///
+/// Pins are `Send` and `Sync`, so they can be moved to or shared with other threads just like
+/// the pins of other backends.
+#[derive(Clone)]
+pub struct DummyGpioIn {
+    source: Source,
+    edge: GpioEdge,
+    faults: Option<Faults>,
+}
+
+#[derive(Clone)]
+enum Source {
+    Closure(sync::Arc<dyn Fn() -> GpioValue + Send + Sync>),
+    Settable(sync::Arc<sync::Mutex<Settable>>),
+    Playback(sync::Arc<Playback>),
+}
+
+/// State of a pin that is driven through `DummyGpioIn::set`
+struct Settable {
+    value: GpioValue,
+    listeners: Vec<(sync::Weak<Listener>, usize)>,
+}
+
+/// Changes of settable pins queued for an edge iterator
+#[derive(Default)]
+struct Listener {
+    events: sync::Mutex<VecDeque<(usize, GpioValue)>>,
+    notifier: Notifier,
+}
+
+impl Listener {
+    #[inline]
+    fn events(&self) -> sync::MutexGuard<'_, VecDeque<(usize, GpioValue)>> {
+        self.events.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+}
+
+impl fmt::Debug for DummyGpioIn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let source = match self.source {
+            Source::Closure(_) => "closure",
+            Source::Settable(_) => "settable",
+            Source::Playback(_) => "playback",
+        };
+        f.debug_struct("DummyGpioIn")
+            .field("source", &source)
+            .field("edge", &self.edge)
+            .field("faults", &self.faults)
+            .finish()
+    }
+}
+
+#[inline]
+fn lock_settable(state: &sync::Mutex<Settable>) -> sync::MutexGuard<'_, Settable> {
+    state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+}
+
+impl DummyGpioIn {
+    /// Create new dummy pin that returns the value of `value` every it is read
+    pub fn new<F, V>(value: F) -> DummyGpioIn
+    where
+        V: Into<GpioValue>,
+        F: Fn() -> V + Send + Sync + 'static,
+    {
+        DummyGpioIn {
+            source: Source::Closure(sync::Arc::new(move || value().into())),
+            edge: GpioEdge::None,
+            faults: None,
+        }
+    }
+
+    /// Create a new dummy pin that reads `value` until it is changed using `set`
+    ///
+    /// Clones share the same value. Edge iterators waiting for the pin are woken up as soon as
+    /// it changes, no change is missed however short the resulting pulse is.
+    pub fn with_value<V: Into<GpioValue>>(value: V) -> DummyGpioIn {
+        DummyGpioIn {
+            source: Source::Settable(sync::Arc::new(sync::Mutex::new(Settable {
+                value: value.into(),
+                listeners: Vec::new(),
+            }))),
+            edge: GpioEdge::None,
+            faults: None,
+        }
+    }
+
+    /// Create a new dummy pin that plays back a timed script
+    ///
+    /// `script` is a list of offsets from the time of creation, as measured by `clock`, and the
+    /// value the pin changes to at that point. Before the first entry, the pin reads the first
+    /// value. Entries must be sorted by offset.
+    pub fn scripted<C, V>(clock: C, script: Vec<(time::Duration, V)>) -> DummyGpioIn
+    where
+        C: Clock + 'static,
+        V: Into<GpioValue>,
+    {
+        Self::playback(clock, Timeline::new(script))
+    }
+
+    /// Create a new dummy pin that plays back `timeline`, starting now as measured by `clock`
+    ///
+    /// Edge iterators using the same clock report every change at its exact time.
+    pub fn playback<C: Clock + 'static>(clock: C, timeline: Timeline) -> DummyGpioIn {
+        DummyGpioIn {
+            source: Source::Playback(sync::Arc::new(Playback::new(clock, timeline))),
+            edge: GpioEdge::None,
+            faults: None,
+        }
+    }
+
+    /// Change the value of a pin created with `with_value`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pin reads its value from a closure or plays back a timeline.
+    pub fn set<V: Into<GpioValue>>(&self, value: V) {
+        let state = match self.source {
+            Source::Settable(ref state) => state,
+            Source::Closure(_) | Source::Playback(_) => {
+                panic!("only dummy pins created using `with_value` can be set")
+            }
+        };
+        let value = value.into();
+        let mut state = lock_settable(state);
+        if state.value == value {
+            return;
+        }
+        state.value = value;
+
+        state.listeners.retain(|&(ref listener, index)| match listener.upgrade() {
+            Some(listener) => {
+                listener.events().push_back((index, value));
+                listener.notifier.notify();
+                true
+            }
+            None => false,
+        });
+    }
+
+    /// Inject `faults` into all accesses of the pin
+    ///
+    /// Edge iterators only see the values of stuck pins, they are not affected by failures.
+    pub fn faults(mut self, faults: Faults) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    #[inline]
+    fn call(&self) -> Result<(), DummyError> {
+        self.faults.as_ref().map_or(Ok(()), Faults::call)
+    }
+
+    #[inline]
+    fn observed(&self, value: GpioValue) -> GpioValue {
+        self.faults.as_ref().map_or(value, |f| f.apply(value))
+    }
+
+    #[inline]
+    fn is_polled(&self) -> bool {
+        match self.source {
+            Source::Closure(_) => true,
+            Source::Settable(_) | Source::Playback(_) => false,
+        }
+    }
+}
+
+impl GpioIn for DummyGpioIn {
+    type Error = DummyError;
+
+    fn read_value(&self) -> Result<GpioValue, Self::Error> {
+        self.call()?;
+        let value = match self.source {
+            Source::Closure(ref value) => value(),
+            Source::Settable(ref state) => lock_settable(state).value,
+            Source::Playback(ref playback) => playback.value_at(playback.now()),
+        };
+        Ok(self.observed(value))
+    }
+
+    fn set_edge(&mut self, edge: GpioEdge) -> Result<(), Self::Error> {
+        self.call()?;
+        self.edge = edge;
+        Ok(())
+    }
+}
+
+const POLL_INTERVAL: time::Duration = time::Duration::from_millis(1);
+
+/// A pin added to an edge iterator
+struct Watched<'a> {
+    gpio: &'a DummyGpioIn,
+    value: GpioValue,
+    /// Time up to which changes of a playback pin have been processed
+    checked: Option<time::Instant>,
+}
+
+impl<'a> Watched<'a> {
+    /// Update the last seen value, returning the pin if this is an edge to be reported
+    fn update(&mut self, value: GpioValue) -> Option<&'a DummyGpioIn> {
+        let value = self.gpio.observed(value);
+        if self.value == value {
+            return None;
+        }
+        self.value = value;
+        if self.gpio.edge.matches(value) {
+            Some(self.gpio)
+        } else {
+            None
+        }
+    }
+
+    /// The next change of a playback pin, if any
+    fn next_change(&self) -> Option<(time::Instant, GpioValue)> {
+        match self.gpio.source {
+            Source::Playback(ref playback) => self.checked.and_then(|t| playback.next_change(t)),
+            _ => None,
+        }
+    }
+}
+
+pub struct DummyEdgeIter<'a> {
+    timeout: Option<time::Duration>,
+    devs: Vec<Watched<'a>>,
+    clock: Box<dyn Clock>,
+    listener: sync::Arc<Listener>,
+}
+
+impl<'a> DummyEdgeIter<'a> {
+    pub fn new() -> Result<DummyEdgeIter<'a>, DummyError> {
+        Self::with_clock(SystemClock)
+    }
+
+    /// Create an iterator that waits and measures its timeout using `clock`
+    ///
+    /// Pins created with `with_value` wake the iterator up when they are set, playback pins
+    /// when their next change is due. Closure based pins are polled every millisecond, with a
+    /// `VirtualClock` once per virtual millisecond without any real delay.
+    pub fn with_clock<C: Clock + 'static>(clock: C) -> Result<DummyEdgeIter<'a>, DummyError> {
+        Ok(DummyEdgeIter {
+            timeout: None,
+            devs: Vec::new(),
+            clock: Box::new(clock),
+            listener: Default::default(),
+        })
+    }
+
+    pub fn timeout_ms(&mut self, timeout_ms: u64) -> &mut Self {
+        self.timeout = Some(time::Duration::from_millis(timeout_ms));
+        self
+    }
+
+    pub fn add(&mut self, dev: &'a DummyGpioIn) -> Result<&mut Self, DummyError> {
+        let (value, checked) = match dev.source {
+            Source::Closure(ref value) => (value(), None),
+            Source::Settable(ref state) => {
+                // registering under the lock makes sure no change after reading is missed
+                let mut state = lock_settable(state);
+                state
+                    .listeners
+                    .push((sync::Arc::downgrade(&self.listener), self.devs.len()));
+                (state.value, None)
+            }
+            Source::Playback(ref playback) => {
+                let now = playback.now();
+                (playback.value_at(now), Some(now))
+            }
+        };
+        self.devs.push(Watched {
+            gpio: dev,
+            value: dev.observed(value),
+            checked,
+        });
+        Ok(self)
+    }
+
+    /// Process queued and due changes and poll closure based pins, returning the first matching
+    /// edge
+    fn check(&mut self) -> Option<&'a DummyGpioIn> {
+        loop {
+            let event = self.listener.events().pop_front();
+            let (index, value) = match event {
+                Some(event) => event,
+                None => break,
+            };
+            if let Some(gpio) = self.devs[index].update(value) {
+                return Some(gpio);
+            }
+        }
+
+        // changes of playback pins are processed in order, even if several are due
+        loop {
+            let now = self.clock.now();
+            let due = self
+                .devs
+                .iter()
+                .enumerate()
+                .filter_map(|(index, dev)| dev.next_change().map(|(t, v)| (t, v, index)))
+                .filter(|&(t, _, _)| t <= now)
+                .min_by_key(|&(t, _, _)| t);
+            let (at, value, index) = match due {
+                Some(due) => due,
+                None => break,
+            };
+            let dev = &mut self.devs[index];
+            dev.checked = Some(at);
+            if let Some(gpio) = dev.update(value) {
+                return Some(gpio);
+            }
+        }
+
+        for dev in &mut self.devs {
+            let value = match dev.gpio.source {
+                Source::Closure(ref value) => value(),
+                Source::Settable(_) | Source::Playback(_) => continue,
+            };
+            if let Some(gpio) = dev.update(value) {
+                return Some(gpio);
+            }
+        }
+        None
+    }
+}
+
+impl<'a> Iterator for DummyEdgeIter<'a> {
+    type Item = Result<&'a DummyGpioIn, DummyError>;
+
+    fn next(&mut self) -> Option<Result<&'a DummyGpioIn, DummyError>> {
+        let start = self.clock.now();
+        loop {
+            let generation = self.listener.notifier.generation();
+            if let Some(gpio) = self.check() {
+                return Some(Ok(gpio));
+            }
+
+            let now = self.clock.now();
+            let elapsed = now.duration_since(start);
+            let mut wait = match self.timeout {
+                Some(to) if elapsed >= to => return Some(Err(DummyError::Timeout)),
+                Some(to) => Some(to - elapsed),
+                None => None,
+            };
+            let mut limit = |d: time::Duration| wait = Some(wait.map_or(d, |w| w.min(d)));
+            if self.devs.iter().any(|dev| dev.gpio.is_polled()) {
+                limit(POLL_INTERVAL);
+            }
+            let next_change = self
+                .devs
+                .iter()
+                .filter_map(Watched::next_change)
+                .map(|(t, _)| t)
+                .min();
+            if let Some(next) = next_change {
+                limit(next.saturating_duration_since(now));
+            }
+            self.clock.wait(&self.listener.notifier, generation, wait);
+        }
+    }
+}
+
+/// Dummy GPIO output pin
+///
+/// The pin is `Send` and `Sync` if `dest` is, e.g. when it only captures a channel sender or an
+/// `Arc`.
+#[derive(Debug)]
+pub struct DummyGpioOut<F> {
+    dest: F,
+    faults: Option<Faults>,
+}
+
+impl<F> DummyGpioOut<F> {
+    /// Creates a new dummy pin that passes all set values to `dest`.
+    pub fn new(dest: F) -> DummyGpioOut<F> {
+        DummyGpioOut { dest, faults: None }
+    }
+
+    /// Inject `faults` into all writes to the pin
+    ///
+    /// Failed writes are not passed to `dest`.
+    pub fn faults(mut self, faults: Faults) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+}
+
+impl<F> DummyGpioOut<F>
+where
+    F: Fn(GpioValue),
+{
+    fn write(&self, value: GpioValue) -> Result<(), DummyError> {
+        match self.faults {
+            Some(ref faults) => {
+                faults.call()?;
+                (self.dest)(faults.apply(value));
+            }
+            None => (self.dest)(value),
+        }
+        Ok(())
+    }
+}
+
+impl<F> GpioOut for DummyGpioOut<F>
+where
+    F: Fn(GpioValue),
+{
+    type Error = DummyError;
+
+    #[inline]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.write(GpioValue::Low)
+    }
+
+    #[inline]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.write(GpioValue::High)
+    }
+}
@@ -0,0 +1,267 @@
+//! Virtual wires between dummy pins
+
+use std::sync;
+use super::DummyGpioIn;
+use super::super::{GpioEdge, GpioIn, GpioOut, GpioValue, Pull};
+
+quick_error! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum NetError {
+        Conflict {
+            description("net is driven high and low at the same time")
+            display("Net is driven high and low at the same time")
+        }
+        Floating {
+            description("net is neither driven nor pulled")
+            display("Net is neither driven nor pulled")
+        }
+    }
+}
+
+/// How a net resolves multiple drivers
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Resolution {
+    /// Push-pull outputs, driving different values at the same time is an error
+    Exclusive,
+    /// The net is low if any driver is low, e.g. open-drain outputs like I2C
+    WiredAnd,
+    /// The net is high if any driver is high
+    WiredOr,
+}
+
+impl Resolution {
+    /// The value of a line with the values of its active `drivers`, or `pull` if there are none
+    pub(crate) fn resolve<I>(self, pull: Pull, drivers: I) -> Result<GpioValue, NetError>
+    where
+        I: IntoIterator<Item = GpioValue>,
+    {
+        let mut driven = drivers.into_iter();
+
+        let first = match driven.next() {
+            Some(v) => v,
+            None => {
+                return match pull {
+                    Pull::None => Err(NetError::Floating),
+                    Pull::Up => Ok(GpioValue::High),
+                    Pull::Down => Ok(GpioValue::Low),
+                }
+            }
+        };
+
+        driven.try_fold(first, |acc, v| match (self, acc, v) {
+            (_, a, b) if a == b => Ok(a),
+            (Resolution::Exclusive, _, _) => Err(NetError::Conflict),
+            (Resolution::WiredAnd, _, _) => Ok(GpioValue::Low),
+            (Resolution::WiredOr, _, _) => Ok(GpioValue::High),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct NetState {
+    resolution: Resolution,
+    pull: Pull,
+    /// Values of all drivers, `None` if not driving
+    drivers: Vec<Option<GpioValue>>,
+    /// Follows the value of the net, waking up edge iterators of its inputs
+    pin: DummyGpioIn,
+}
+
+impl NetState {
+    #[inline]
+    fn value(&self) -> Result<GpioValue, NetError> {
+        self.resolution.resolve(self.pull, self.drivers.iter().filter_map(|d| *d))
+    }
+
+    /// Pass a changed value on to the inputs, keeping the last value while there is none
+    fn update(&self) {
+        if let Ok(value) = self.value() {
+            self.pin.set(value);
+        }
+    }
+}
+
+/// A virtual wire connecting dummy outputs and inputs
+///
+/// Every output handed out by `output` is a driver of the net, every input created by `input`
+/// reads the value resulting from all drivers. Clones refer to the same net and all handles can
+/// be sent to other threads.
+#[derive(Debug, Clone)]
+pub struct Net {
+    state: sync::Arc<sync::Mutex<NetState>>,
+}
+
+impl Net {
+    /// Create a new net with push-pull drivers and no pull resistor
+    pub fn new() -> Net {
+        Net {
+            state: sync::Arc::new(sync::Mutex::new(NetState {
+                resolution: Resolution::Exclusive,
+                pull: Pull::None,
+                drivers: Vec::new(),
+                pin: DummyGpioIn::with_value(GpioValue::Low),
+            })),
+        }
+    }
+
+    #[inline]
+    fn state(&self) -> sync::MutexGuard<'_, NetState> {
+        self.state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// Set how multiple drivers are resolved
+    pub fn resolution(self, resolution: Resolution) -> Self {
+        {
+            let mut state = self.state();
+            state.resolution = resolution;
+            state.update();
+        }
+        self
+    }
+
+    /// Set the value of the net while it is not driven
+    pub fn pull(self, pull: Pull) -> Self {
+        {
+            let mut state = self.state();
+            state.pull = pull;
+            state.update();
+        }
+        self
+    }
+
+    /// Create a new driver of the net, which is not driving until its value is set
+    pub fn output(&self) -> NetGpioOut {
+        let mut state = self.state();
+        state.drivers.push(None);
+        NetGpioOut {
+            net: self.clone(),
+            index: state.drivers.len() - 1,
+        }
+    }
+
+    /// Create a new input reading the net
+    pub fn input(&self) -> NetGpioIn {
+        NetGpioIn {
+            net: self.clone(),
+            pin: self.state().pin.clone(),
+        }
+    }
+
+    /// The current value of the net
+    #[inline]
+    pub fn value(&self) -> Result<GpioValue, NetError> {
+        self.state().value()
+    }
+}
+
+impl Default for Net {
+    #[inline]
+    fn default() -> Net {
+        Net::new()
+    }
+}
+
+/// Dummy GPIO output driving a `Net`
+///
+/// The driver is released when the output is dropped.
+#[derive(Debug)]
+pub struct NetGpioOut {
+    net: Net,
+    index: usize,
+}
+
+impl NetGpioOut {
+    #[inline]
+    fn drive(&mut self, value: Option<GpioValue>) -> Result<(), NetError> {
+        let mut state = self.net.state();
+        state.drivers[self.index] = value;
+        state.update();
+        match state.value() {
+            Err(NetError::Conflict) => Err(NetError::Conflict),
+            _ => Ok(()),
+        }
+    }
+
+    /// Stop driving the net, i.e. switch the output to high impedance
+    #[inline]
+    pub fn release(&mut self) {
+        self.drive(None).ok();
+    }
+
+    /// The net this output drives
+    #[inline]
+    pub fn net(&self) -> &Net {
+        &self.net
+    }
+}
+
+impl GpioOut for NetGpioOut {
+    type Error = NetError;
+
+    /// Drive the net low, failing if this causes a conflict
+    ///
+    /// The value is applied even if it causes a conflict.
+    #[inline]
+    fn set_low(&mut self) -> Result<(), NetError> {
+        self.drive(Some(GpioValue::Low))
+    }
+
+    /// Drive the net high, failing if this causes a conflict
+    ///
+    /// The value is applied even if it causes a conflict.
+    #[inline]
+    fn set_high(&mut self) -> Result<(), NetError> {
+        self.drive(Some(GpioValue::High))
+    }
+}
+
+impl Drop for NetGpioOut {
+    #[inline]
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+/// Dummy GPIO input reading a `Net`
+///
+/// Edges are waited for using a `DummyEdgeIter` on the pin returned by `dummy`. Only changes
+/// between high and low are edges, a net that floats or has conflicting drivers keeps the value
+/// it had before.
+#[derive(Debug, Clone)]
+pub struct NetGpioIn {
+    net: Net,
+    pin: DummyGpioIn,
+}
+
+impl NetGpioIn {
+    /// The net this input reads
+    #[inline]
+    pub fn net(&self) -> &Net {
+        &self.net
+    }
+
+    /// A dummy pin following the net, with the edge setting of this input
+    ///
+    /// Add it to a `DummyEdgeIter` to wait for the edges of the input.
+    #[inline]
+    pub fn dummy(&self) -> &DummyGpioIn {
+        &self.pin
+    }
+}
+
+impl GpioIn for NetGpioIn {
+    type Error = NetError;
+
+    /// Read the net, failing if it is floating or driven high and low at once
+    #[inline]
+    fn read_value(&self) -> Result<GpioValue, NetError> {
+        self.net.value()
+    }
+
+    /// Set the edges reported for the pin returned by `dummy`
+    #[inline]
+    fn set_edge(&mut self, edge: GpioEdge) -> Result<(), NetError> {
+        self.pin.edge = edge;
+        Ok(())
+    }
+}
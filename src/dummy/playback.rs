@@ -0,0 +1,384 @@
+//! Timelines played back by dummy inputs
+
+use std::{fs, io, path, time};
+use std::io::{BufRead, Read};
+use super::super::GpioValue;
+use clock::Clock;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum PlaybackError {
+        Io(err: io::Error) {
+            from()
+            description("io error")
+            display("I/O error: {}", err)
+            cause(err)
+        }
+        Parse(line: usize, reason: String) {
+            description("invalid timeline")
+            display("invalid timeline in line {}: {}", line, reason)
+        }
+        UnknownSignal(name: String) {
+            description("signal not found in value change dump")
+            display("signal {:?} not found in value change dump", name)
+        }
+        Empty {
+            description("timeline does not contain any values")
+        }
+        InvalidSpeed(factor: f64) {
+            description("invalid playback speed")
+            display("invalid playback speed {}, must be positive and finite", factor)
+        }
+    }
+}
+
+pub type PlaybackResult<T> = Result<T, PlaybackError>;
+
+/// A list of value changes to be played back by a `DummyGpioIn`
+///
+/// Offsets are measured from the start of playback. Before the first change, the first value is
+/// played back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timeline {
+    changes: Vec<(time::Duration, GpioValue)>,
+    end: time::Duration,
+    looping: bool,
+}
+
+fn parse_value(value: &str) -> Option<GpioValue> {
+    match value.to_ascii_lowercase().as_str() {
+        "0" | "low" | "false" => Some(GpioValue::Low),
+        "1" | "high" | "true" => Some(GpioValue::High),
+        _ => None,
+    }
+}
+
+/// Seconds per tick of a VCD `$timescale` such as `10 us`
+fn parse_timescale(timescale: &str) -> Option<f64> {
+    let split = timescale
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(timescale.len());
+    let (number, unit) = timescale.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let unit = match unit.trim() {
+        "s" => 1.0,
+        "ms" => 1e-3,
+        "us" => 1e-6,
+        "ns" => 1e-9,
+        "ps" => 1e-12,
+        "fs" => 1e-15,
+        _ => return None,
+    };
+    Some(number * unit)
+}
+
+impl Timeline {
+    /// Create a timeline from a list of offsets and the value the pin changes to at that point
+    ///
+    /// Entries must be sorted by offset. The timeline ends with the last change.
+    pub fn new<V: Into<GpioValue>>(changes: Vec<(time::Duration, V)>) -> Timeline {
+        let changes: Vec<_> = changes.into_iter().map(|(t, v)| (t, v.into())).collect();
+        Timeline {
+            end: changes.last().map_or(Default::default(), |&(t, _)| t),
+            changes,
+            looping: false,
+        }
+    }
+
+    /// Read a timeline from comma, semicolon or tab separated values
+    ///
+    /// The first column holds the time in seconds, the second the value (`0`/`1`, `low`/`high`
+    /// or `false`/`true`), further columns are ignored. Offsets are relative to the first row.
+    /// A header row, empty lines and lines starting with `#` are skipped, e.g. for the exports
+    /// of logic analyzers.
+    pub fn from_csv<R: BufRead>(reader: R) -> PlaybackResult<Timeline> {
+        let mut first = None;
+        let mut header = false;
+        let mut changes = Vec::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split(&[',', ';', '\t'][..]).map(str::trim);
+            let time = fields.next().and_then(|t| t.parse::<f64>().ok());
+            let time = match (time, first) {
+                (Some(time), _) => time,
+                // the first row may be a header
+                (None, None) if !header => {
+                    header = true;
+                    continue;
+                }
+                (None, _) => {
+                    return Err(PlaybackError::Parse(index + 1, format!("invalid time: {}", line)))
+                }
+            };
+            let value = fields.next().and_then(parse_value).ok_or_else(|| {
+                PlaybackError::Parse(index + 1, format!("invalid value: {}", line))
+            })?;
+
+            let first = *first.get_or_insert(time);
+            if time - first < 0.0 {
+                return Err(PlaybackError::Parse(index + 1, "time goes backwards".to_owned()));
+            }
+            // not a number, infinite or too far from the first time
+            let offset = time::Duration::try_from_secs_f64(time - first).map_err(|_| {
+                PlaybackError::Parse(index + 1, format!("invalid time: {}", line))
+            })?;
+            if changes.last().is_some_and(|&(t, _)| offset < t) {
+                return Err(PlaybackError::Parse(index + 1, "time goes backwards".to_owned()));
+            }
+            changes.push((offset, value));
+        }
+
+        if changes.is_empty() {
+            return Err(PlaybackError::Empty);
+        }
+        Ok(Timeline::new(changes))
+    }
+
+    /// Read a timeline from a CSV file, see `from_csv`
+    pub fn load_csv<P: AsRef<path::Path>>(path: P) -> PlaybackResult<Timeline> {
+        Self::from_csv(io::BufReader::new(fs::File::open(path)?))
+    }
+
+    /// Read the single-bit signal named `signal` from a Value Change Dump
+    ///
+    /// Offsets are relative to time zero of the dump, unknown and high impedance values are
+    /// skipped. The timeline ends at the last timestamp of the dump, which may be after the last
+    /// change of the signal.
+    pub fn from_vcd<R: Read>(mut reader: R, signal: &str) -> PlaybackResult<Timeline> {
+        let mut vcd = String::new();
+        reader.read_to_string(&mut vcd)?;
+
+        let line_of = |token: &str| {
+            // tokens are slices of `vcd`, their position tells the line
+            let offset = token.as_ptr() as usize - vcd.as_ptr() as usize;
+            vcd[..offset].lines().count().max(1)
+        };
+
+        let mut tick = 1e-9;
+        let mut id = None;
+        let mut now = time::Duration::from_secs(0);
+        let mut end = now;
+        let mut changes = Vec::new();
+        let mut tokens = vcd.split_whitespace();
+
+        while let Some(token) = tokens.next() {
+            match token {
+                "$dumpvars" | "$dumpall" | "$dumpon" | "$dumpoff" | "$end" => (),
+                "$timescale" => {
+                    let timescale: String = tokens.by_ref().take_while(|&t| t != "$end").collect();
+                    tick = parse_timescale(&timescale).ok_or_else(|| {
+                        PlaybackError::Parse(
+                            line_of(token),
+                            format!("invalid timescale: {}", timescale),
+                        )
+                    })?;
+                }
+                "$var" => {
+                    let var: Vec<_> = tokens.by_ref().take_while(|&t| t != "$end").collect();
+                    if var.len() >= 4 && var[3] == signal {
+                        if var[1] != "1" {
+                            return Err(PlaybackError::Parse(
+                                line_of(token),
+                                format!("signal {:?} is {} bits wide", signal, var[1]),
+                            ));
+                        }
+                        id = Some(var[2]);
+                    }
+                }
+                _ if token.starts_with('$') => {
+                    tokens.by_ref().take_while(|&t| t != "$end").for_each(drop);
+                }
+                _ if token.starts_with('#') => {
+                    let invalid =
+                        || PlaybackError::Parse(line_of(token), format!("invalid time: {}", token));
+                    let ticks: u64 = token[1..].parse().map_err(|_| invalid())?;
+                    now = time::Duration::try_from_secs_f64(ticks as f64 * tick)
+                        .map_err(|_| invalid())?;
+                    end = end.max(now);
+                }
+                _ if token.starts_with(|c| "bBrR".contains(c)) => {
+                    // vector or real value, followed by the identifier
+                    tokens.next();
+                }
+                _ => {
+                    // the value is one character, which is not ASCII in malformed files
+                    let split = token.char_indices().nth(1).map_or(token.len(), |(i, _)| i);
+                    let (value, var) = token.split_at(split);
+                    let value = match value {
+                        "0" => GpioValue::Low,
+                        "1" => GpioValue::High,
+                        "x" | "X" | "z" | "Z" => continue,
+                        _ => {
+                            return Err(PlaybackError::Parse(
+                                line_of(token),
+                                format!("unexpected token: {}", token),
+                            ))
+                        }
+                    };
+                    if Some(var) == id {
+                        changes.push((now, value));
+                    }
+                }
+            }
+        }
+
+        if id.is_none() {
+            return Err(PlaybackError::UnknownSignal(signal.to_owned()));
+        }
+        if changes.is_empty() {
+            return Err(PlaybackError::Empty);
+        }
+        Ok(Timeline::new(changes).end(end))
+    }
+
+    /// Read the signal named `signal` from a Value Change Dump file, see `from_vcd`
+    pub fn load_vcd<P: AsRef<path::Path>>(path: P, signal: &str) -> PlaybackResult<Timeline> {
+        Self::from_vcd(io::BufReader::new(fs::File::open(path)?), signal)
+    }
+
+    /// Set the length of the timeline, i.e. the period when looping
+    ///
+    /// The length is never shorter than the offset of the last change.
+    pub fn end(mut self, end: time::Duration) -> Self {
+        self.end = self.changes.last().map_or(end, |&(t, _)| end.max(t));
+        self
+    }
+
+    /// Play the timeline back `factor` times as fast
+    ///
+    /// Fails if `factor` is not positive and finite, or so small the timeline gets too long.
+    pub fn speed(mut self, factor: f64) -> PlaybackResult<Self> {
+        if !(factor > 0.0 && factor.is_finite()) {
+            return Err(PlaybackError::InvalidSpeed(factor));
+        }
+        let scale = |offset: time::Duration| {
+            time::Duration::try_from_secs_f64(offset.as_secs_f64() / factor)
+                .map_err(|_| PlaybackError::InvalidSpeed(factor))
+        };
+        for change in &mut self.changes {
+            change.0 = scale(change.0)?;
+        }
+        self.end = scale(self.end)?;
+        Ok(self)
+    }
+
+    /// Repeat the timeline when its end is reached
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// All changes of the timeline
+    #[inline]
+    pub fn changes(&self) -> &[(time::Duration, GpioValue)] {
+        &self.changes
+    }
+
+    /// The length of the timeline
+    #[inline]
+    pub fn duration(&self) -> time::Duration {
+        self.end
+    }
+
+    #[inline]
+    fn first_value(&self) -> GpioValue {
+        self.changes.first().map_or(GpioValue::Low, |&(_, v)| v)
+    }
+
+    #[inline]
+    fn is_looping(&self) -> bool {
+        self.looping && self.end > time::Duration::from_secs(0)
+    }
+
+    /// Split an offset from the start of playback into the start of its cycle and the offset
+    /// within the cycle
+    fn cycle(&self, offset: time::Duration) -> (time::Duration, time::Duration) {
+        if !self.is_looping() {
+            return (Default::default(), offset);
+        }
+        let period = self.end.as_nanos();
+        let within = offset.as_nanos() % period;
+        (
+            time::Duration::from_nanos((offset.as_nanos() - within) as u64),
+            time::Duration::from_nanos(within as u64),
+        )
+    }
+
+    /// The value at `offset` from the start of playback
+    pub fn value_at(&self, offset: time::Duration) -> GpioValue {
+        let (_, within) = self.cycle(offset);
+        let idx = self.changes.iter().take_while(|&&(t, _)| t <= within).count();
+        self.changes
+            .get(idx.saturating_sub(1))
+            .map_or(self.first_value(), |&(_, v)| v)
+    }
+
+    /// The first change of the value after `offset` and the new value
+    pub fn next_change(&self, offset: time::Duration) -> Option<(time::Duration, GpioValue)> {
+        let (cycle, within) = self.cycle(offset);
+        let mut value = self.value_at(offset);
+
+        let current = self.changes.iter().map(|&(t, v)| (cycle + t, v));
+        let candidates: Box<dyn Iterator<Item = _>> = if self.is_looping() {
+            // every cycle starts with the first value
+            let next = cycle + self.end;
+            let next_cycle = Some((next, self.first_value()))
+                .into_iter()
+                .chain(self.changes.iter().map(move |&(t, v)| (next + t, v)));
+            Box::new(current.chain(next_cycle))
+        } else {
+            Box::new(current)
+        };
+
+        for (t, v) in candidates {
+            if t <= cycle + within {
+                continue;
+            }
+            if v != value {
+                return Some((t, v));
+            }
+            value = v;
+        }
+        None
+    }
+}
+
+/// A timeline being played back according to a clock
+pub(super) struct Playback {
+    clock: Box<dyn Clock>,
+    start: time::Instant,
+    timeline: Timeline,
+}
+
+impl Playback {
+    pub(super) fn new<C: Clock + 'static>(clock: C, timeline: Timeline) -> Playback {
+        Playback {
+            start: clock.now(),
+            clock: Box::new(clock),
+            timeline,
+        }
+    }
+
+    #[inline]
+    pub(super) fn now(&self) -> time::Instant {
+        self.clock.now()
+    }
+
+    #[inline]
+    pub(super) fn value_at(&self, at: time::Instant) -> GpioValue {
+        self.timeline.value_at(at.duration_since(self.start))
+    }
+
+    #[inline]
+    pub(super) fn next_change(&self, after: time::Instant) -> Option<(time::Instant, GpioValue)> {
+        self.timeline
+            .next_change(after.duration_since(self.start))
+            .map(|(t, v)| (self.start + t, v))
+    }
+}
@@ -0,0 +1,310 @@
+//! Recording dummy outputs
+
+use std::{fmt, fs, io, path, sync, time};
+use std::io::Write;
+use super::super::{GpioOut, GpioValue};
+use clock::{Clock, SystemClock};
+
+/// A change of an output's value
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Transition {
+    /// Time since the recorder was created
+    pub time: time::Duration,
+    /// The new value
+    pub value: GpioValue,
+}
+
+/// A pulse, i.e. the time between two transitions
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Pulse {
+    /// Time since the recorder was created
+    pub start: time::Duration,
+    /// Time until the output changed back
+    pub width: time::Duration,
+    /// Value of the output during the pulse
+    pub level: GpioValue,
+}
+
+#[derive(Debug)]
+struct Trace {
+    name: String,
+    transitions: Vec<Transition>,
+}
+
+struct RecorderState {
+    clock: Box<dyn Clock>,
+    start: time::Instant,
+    traces: Vec<Trace>,
+}
+
+impl fmt::Debug for RecorderState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RecorderState")
+            .field("start", &self.start)
+            .field("traces", &self.traces)
+            .finish()
+    }
+}
+
+/// Records the waveforms of multiple outputs on a common timeline
+///
+/// Clones share the same recording.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    state: sync::Arc<sync::Mutex<RecorderState>>,
+}
+
+impl Recorder {
+    /// Create a new recorder, its timeline starts now
+    #[inline]
+    pub fn new() -> Recorder {
+        Self::with_clock(SystemClock)
+    }
+
+    /// Create a new recorder that timestamps transitions using `clock`
+    pub fn with_clock<C: Clock + 'static>(clock: C) -> Recorder {
+        Recorder {
+            state: sync::Arc::new(sync::Mutex::new(RecorderState {
+                start: clock.now(),
+                clock: Box::new(clock),
+                traces: Vec::new(),
+            })),
+        }
+    }
+
+    #[inline]
+    fn state(&self) -> sync::MutexGuard<'_, RecorderState> {
+        self.state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// Create an output whose values are recorded under `name`
+    ///
+    /// Outputs created with the same name share a single trace.
+    pub fn output(&self, name: &str) -> RecordingGpioOut {
+        let mut state = self.state();
+        let index = match state.traces.iter().position(|t| t.name == name) {
+            Some(index) => index,
+            None => {
+                state.traces.push(Trace {
+                    name: name.to_owned(),
+                    transitions: Vec::new(),
+                });
+                state.traces.len() - 1
+            }
+        };
+
+        RecordingGpioOut {
+            recorder: self.clone(),
+            index,
+        }
+    }
+
+    fn record(&self, index: usize, value: GpioValue) {
+        let mut state = self.state();
+        let time = state.clock.now().duration_since(state.start);
+        let transitions = &mut state.traces[index].transitions;
+
+        if transitions.last().is_none_or(|t| t.value != value) {
+            transitions.push(Transition { time, value });
+        }
+    }
+
+    /// The names of all recorded outputs
+    pub fn names(&self) -> Vec<String> {
+        self.state().traces.iter().map(|t| t.name.clone()).collect()
+    }
+
+    /// All transitions of the output `name`, the first one being the initial value
+    pub fn transitions(&self, name: &str) -> Vec<Transition> {
+        self.state()
+            .traces
+            .iter()
+            .find(|t| t.name == name)
+            .map(|t| t.transitions.clone())
+            .unwrap_or_default()
+    }
+
+    /// All completed pulses of the output `name` with the given level
+    pub fn pulses(&self, name: &str, level: GpioValue) -> Vec<Pulse> {
+        self.transitions(name)
+            .windows(2)
+            .filter(|w| w[0].value == level)
+            .map(|w| Pulse {
+                start: w[0].time,
+                width: w[1].time - w[0].time,
+                level,
+            })
+            .collect()
+    }
+
+    /// Whether a pulse of `width` ± `tolerance` with the given level occurred on `name`
+    pub fn has_pulse(
+        &self,
+        name: &str,
+        level: GpioValue,
+        width: time::Duration,
+        tolerance: time::Duration,
+    ) -> bool {
+        let min = width.checked_sub(tolerance).unwrap_or_default();
+        let max = width + tolerance;
+        self.pulses(name, level)
+            .iter()
+            .any(|p| p.width >= min && p.width <= max)
+    }
+
+    /// Panic unless a pulse of `width` ± `tolerance` with the given level occurred on `name`
+    ///
+    /// The panic message lists all recorded pulses of that level.
+    pub fn assert_pulse(
+        &self,
+        name: &str,
+        level: GpioValue,
+        width: time::Duration,
+        tolerance: time::Duration,
+    ) {
+        if !self.has_pulse(name, level, width, tolerance) {
+            panic!(
+                "no {:?} pulse of {:?} ± {:?} on {:?}, recorded pulses: {:?}",
+                level,
+                width,
+                tolerance,
+                name,
+                self.pulses(name, level)
+            );
+        }
+    }
+
+    /// Write all traces as a Value Change Dump, e.g. to be viewed with GTKWave
+    pub fn write_vcd<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let state = self.state();
+
+        writeln!(out, "$timescale 1ns $end")?;
+        writeln!(out, "$scope module gpio $end")?;
+        for (index, trace) in state.traces.iter().enumerate() {
+            writeln!(
+                out,
+                "$var wire 1 {} {} $end",
+                VcdId(index),
+                trace.name.replace(char::is_whitespace, "_")
+            )?;
+        }
+        writeln!(out, "$upscope $end")?;
+        writeln!(out, "$enddefinitions $end")?;
+
+        // all outputs are undefined until they are first set
+        writeln!(out, "$dumpvars")?;
+        for index in 0..state.traces.len() {
+            writeln!(out, "x{}", VcdId(index))?;
+        }
+        writeln!(out, "$end")?;
+
+        let mut changes: Vec<_> = state
+            .traces
+            .iter()
+            .enumerate()
+            .flat_map(|(index, trace)| trace.transitions.iter().map(move |t| (t, index)))
+            .collect();
+        changes.sort_by_key(|&(t, _)| t.time);
+
+        let mut current = None;
+        for (t, index) in changes {
+            let ns = t.time.as_nanos();
+            if current != Some(ns) {
+                writeln!(out, "#{}", ns)?;
+                current = Some(ns);
+            }
+            writeln!(out, "{}{}", u8::from(t.value), VcdId(index))?;
+        }
+        Ok(())
+    }
+
+    /// Save all traces as a Value Change Dump file
+    pub fn save_vcd<P: AsRef<path::Path>>(&self, path: P) -> io::Result<()> {
+        let mut out = io::BufWriter::new(fs::File::create(path)?);
+        self.write_vcd(&mut out)?;
+        out.flush()
+    }
+}
+
+impl Default for Recorder {
+    #[inline]
+    fn default() -> Recorder {
+        Recorder::new()
+    }
+}
+
+/// Identifier of a VCD variable, made of printable ASCII characters
+struct VcdId(usize);
+
+impl fmt::Display for VcdId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut n = self.0;
+        loop {
+            write!(f, "{}", (b'!' + (n % 94) as u8) as char)?;
+            n /= 94;
+            if n == 0 {
+                return Ok(());
+            }
+            n -= 1;
+        }
+    }
+}
+
+/// Dummy GPIO output pin that records its waveform
+#[derive(Debug, Clone)]
+pub struct RecordingGpioOut {
+    recorder: Recorder,
+    index: usize,
+}
+
+impl RecordingGpioOut {
+    /// Create an output with its own recorder
+    pub fn new(name: &str) -> RecordingGpioOut {
+        Recorder::new().output(name)
+    }
+
+    /// The recorder this output belongs to
+    #[inline]
+    pub fn recorder(&self) -> &Recorder {
+        &self.recorder
+    }
+
+    /// The name of the output
+    pub fn name(&self) -> String {
+        self.recorder.state().traces[self.index].name.clone()
+    }
+
+    /// All transitions of this output
+    pub fn transitions(&self) -> Vec<Transition> {
+        self.recorder.state().traces[self.index].transitions.clone()
+    }
+
+    /// All completed pulses of this output with the given level
+    #[inline]
+    pub fn pulses(&self, level: GpioValue) -> Vec<Pulse> {
+        self.recorder.pulses(&self.name(), level)
+    }
+
+    /// Panic unless a pulse of `width` ± `tolerance` with the given level occurred
+    #[inline]
+    pub fn assert_pulse(&self, level: GpioValue, width: time::Duration, tolerance: time::Duration) {
+        self.recorder
+            .assert_pulse(&self.name(), level, width, tolerance)
+    }
+}
+
+impl GpioOut for RecordingGpioOut {
+    type Error = ();
+
+    #[inline]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.recorder.record(self.index, GpioValue::Low);
+        Ok(())
+    }
+
+    #[inline]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.recorder.record(self.index, GpioValue::High);
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+//! Replaying event logs of recorded pins
+
+use std::{collections, fs, io, path, sync};
+use super::super::{GpioEdge, GpioIn, GpioOut, GpioValue};
+use super::DummyError;
+use record::{self, Event, LogEntry, LogResult};
+
+/// Replays an event log written by `record::Recording`
+///
+/// Every pin of the log is replayed by a dummy pin of the same name. Each access of a pin
+/// consumes the next event of that pin and returns its recorded result, independent of timing.
+/// Accesses that differ from the log fail with `DummyError::Diverged`. Clones share the same
+/// position in the log.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pins: sync::Arc<sync::Mutex<collections::HashMap<String, collections::VecDeque<LogEntry>>>>,
+}
+
+impl Replay {
+    /// Create a replay of `entries`
+    pub fn new(entries: Vec<LogEntry>) -> Replay {
+        let mut pins = collections::HashMap::new();
+        for entry in entries {
+            pins.entry(entry.pin.clone())
+                .or_insert_with(collections::VecDeque::new)
+                .push_back(entry);
+        }
+        Replay {
+            pins: sync::Arc::new(sync::Mutex::new(pins)),
+        }
+    }
+
+    /// Load a replay from an event log file
+    pub fn load<P: AsRef<path::Path>>(path: P) -> LogResult<Replay> {
+        let entries = record::read_log(io::BufReader::new(fs::File::open(path)?))?;
+        Ok(Self::new(entries))
+    }
+
+    #[inline]
+    fn pins(
+        &self,
+    ) -> sync::MutexGuard<'_, collections::HashMap<String, collections::VecDeque<LogEntry>>> {
+        self.pins.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// An input replaying the events of `name`
+    pub fn input(&self, name: &str) -> ReplayGpioIn {
+        ReplayGpioIn {
+            replay: self.clone(),
+            name: name.to_owned(),
+        }
+    }
+
+    /// An output replaying the events of `name`
+    pub fn output(&self, name: &str) -> ReplayGpioOut {
+        ReplayGpioOut {
+            replay: self.clone(),
+            name: name.to_owned(),
+        }
+    }
+
+    /// Number of events of `name` that have not been replayed yet
+    pub fn remaining(&self, name: &str) -> usize {
+        self.pins().get(name).map_or(0, collections::VecDeque::len)
+    }
+
+    /// Whether all events of all pins have been replayed
+    pub fn is_finished(&self) -> bool {
+        self.pins().values().all(collections::VecDeque::is_empty)
+    }
+
+    /// Consume the next event of `name` if `expected` accepts it
+    fn next<T, F>(&self, name: &str, access: &str, expected: F) -> Result<T, DummyError>
+    where
+        F: FnOnce(&Event) -> Option<Result<T, String>>,
+    {
+        let mut pins = self.pins();
+        let events = pins.get_mut(name);
+        let entry = match events.as_ref().and_then(|e| e.front()) {
+            Some(entry) => entry,
+            None => {
+                return Err(DummyError::Diverged(format!(
+                    "{} on {}, but the log has no further events",
+                    access, name
+                )))
+            }
+        };
+        let result = match expected(&entry.event) {
+            Some(result) => result,
+            None => {
+                return Err(DummyError::Diverged(format!(
+                    "{} on {}, but the log continues with {}",
+                    access, name, entry
+                )))
+            }
+        };
+        events.map(collections::VecDeque::pop_front);
+        result.map_err(DummyError::Replayed)
+    }
+}
+
+/// Dummy input replaying a recorded pin
+#[derive(Debug, Clone)]
+pub struct ReplayGpioIn {
+    replay: Replay,
+    name: String,
+}
+
+impl GpioIn for ReplayGpioIn {
+    type Error = DummyError;
+
+    fn read_value(&self) -> Result<GpioValue, Self::Error> {
+        self.replay.next(&self.name, "read", |event| match *event {
+            Event::Read(ref result) => Some(result.clone()),
+            _ => None,
+        })
+    }
+
+    fn set_edge(&mut self, edge: GpioEdge) -> Result<(), Self::Error> {
+        let access = format!("setting edge {:?}", edge);
+        self.replay.next(&self.name, &access, |event| match *event {
+            Event::Edge(recorded, ref result) if recorded == edge => Some(result.clone()),
+            _ => None,
+        })
+    }
+}
+
+/// Dummy output replaying a recorded pin
+#[derive(Debug, Clone)]
+pub struct ReplayGpioOut {
+    replay: Replay,
+    name: String,
+}
+
+impl ReplayGpioOut {
+    fn write(&self, value: GpioValue) -> Result<(), DummyError> {
+        let access = format!("writing {:?}", value);
+        self.replay.next(&self.name, &access, |event| match *event {
+            Event::Write(recorded, ref result) if recorded == value => Some(result.clone()),
+            _ => None,
+        })
+    }
+}
+
+impl GpioOut for ReplayGpioOut {
+    type Error = DummyError;
+
+    #[inline]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.write(GpioValue::Low)
+    }
+
+    #[inline]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.write(GpioValue::High)
+    }
+}
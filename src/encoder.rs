@@ -0,0 +1,254 @@
+//! Rotary encoders
+//!
+//! Incremental rotary encoders output two square waves (`A` and `B`) in quadrature, the phase
+//! between them indicating the direction of rotation. `QuadratureDecoder` turns the sequence of
+//! `A`/`B` values into a position using a full transition table: contact bounce on a single
+//! channel results in steps back and forth that cancel each other out, while impossible
+//! transitions (both channels changing at once) are counted as errors and otherwise ignored.
+//!
+//! Most mechanical encoders run through several quadrature steps between two detents, which is
+//! why the decoder also counts detent steps, the unit the user actually perceives.
+//!
+//! `RotaryEncoder` ties a decoder to two inputs and an optional push-button and is fed with the
+//! pins returned by an edge iterator such as `SysFsGpioEdgeIter` or `DummyEdgeIter`.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use gpio::GpioValue;
+//! use gpio::dummy::DummyGpioIn;
+//! use gpio::encoder::{Direction, EncoderEvent, RotaryEncoder};
+//!
+//! // a scripted encoder: A leads B for one detent, with a bounce on A in between
+//! let script = [(0, 0), (1, 0), (0, 0), (1, 0), (1, 1), (0, 1), (0, 0)];
+//! let a = DummyGpioIn::with_value(0);
+//! let b = DummyGpioIn::with_value(0);
+//! let button = DummyGpioIn::new(|| true);
+//!
+//! let mut encoder = RotaryEncoder::new(&a, &b, 4)
+//!     .unwrap()
+//!     .with_button(&button, GpioValue::Low)
+//!     .unwrap();
+//!
+//! // normally, `triggered` is the pin returned by the edge iterator
+//! let mut events = Vec::new();
+//! for i in 1..script.len() {
+//!     a.set(script[i].0);
+//!     b.set(script[i].1);
+//!     let triggered = if script[i].0 != script[i - 1].0 { &a } else { &b };
+//!     events.extend(encoder.handle_edge(triggered).unwrap());
+//! }
+//!
+//! assert_eq!(events, vec![EncoderEvent::Step(Direction::Clockwise)]);
+//! assert_eq!(encoder.decoder().detents(), 1);
+//! assert_eq!(encoder.decoder().position(), 4);
+//! ```
+
+use std::ptr;
+use super::{GpioIn, GpioValue};
+
+/// Direction of rotation
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Direction {
+    /// `A` leads `B`, counted as positive
+    Clockwise,
+    /// `B` leads `A`, counted as negative
+    CounterClockwise,
+}
+
+// Position change indexed by `previous state << 2 | new state`, with `state = A << 1 | B`.
+// Transitions changing both channels at once are invalid and result in no change.
+const TRANSITIONS: [i8; 16] = [0, -1, 1, 0, 1, 0, 0, -1, -1, 0, 0, 1, 0, 1, -1, 0];
+
+/// Decodes quadrature signals into a position
+#[derive(Debug, Clone)]
+pub struct QuadratureDecoder {
+    state: Option<u8>,
+    position: i64,
+    steps_per_detent: u8,
+    partial: i16,
+    detents: i64,
+    errors: u64,
+    direction: Option<Direction>,
+}
+
+impl QuadratureDecoder {
+    /// Create a new decoder
+    ///
+    /// `steps_per_detent` is the number of quadrature steps between two detents, usually 4, 2 or
+    /// 1. Encoders without detents can use 1 to have every step reported as detent step.
+    pub fn new(steps_per_detent: u8) -> QuadratureDecoder {
+        QuadratureDecoder {
+            state: None,
+            position: 0,
+            steps_per_detent: steps_per_detent.max(1),
+            partial: 0,
+            detents: 0,
+            errors: 0,
+            direction: None,
+        }
+    }
+
+    /// Feed the current values of both channels into the decoder
+    ///
+    /// Returns the direction of a detent step, if one was completed by this update. The first
+    /// update only establishes the initial state.
+    pub fn update<A, B>(&mut self, a: A, b: B) -> Option<Direction>
+    where
+        A: Into<GpioValue>,
+        B: Into<GpioValue>,
+    {
+        let new = (u8::from(a.into()) << 1) | u8::from(b.into());
+        let old = self.state.replace(new)?;
+
+        if old ^ new == 0b11 {
+            self.errors += 1;
+            return None;
+        }
+
+        let delta = TRANSITIONS[((old << 2) | new) as usize];
+        if delta == 0 {
+            return None;
+        }
+
+        self.position += i64::from(delta);
+        self.partial += i16::from(delta);
+        self.direction = Some(if delta > 0 {
+            Direction::Clockwise
+        } else {
+            Direction::CounterClockwise
+        });
+
+        if self.partial >= i16::from(self.steps_per_detent) {
+            self.partial = 0;
+            self.detents += 1;
+            Some(Direction::Clockwise)
+        } else if self.partial <= -i16::from(self.steps_per_detent) {
+            self.partial = 0;
+            self.detents -= 1;
+            Some(Direction::CounterClockwise)
+        } else {
+            None
+        }
+    }
+
+    /// The position in quadrature steps
+    #[inline]
+    pub fn position(&self) -> i64 {
+        self.position
+    }
+
+    /// The position in detent steps
+    #[inline]
+    pub fn detents(&self) -> i64 {
+        self.detents
+    }
+
+    /// The direction of the most recent step, if any
+    #[inline]
+    pub fn direction(&self) -> Option<Direction> {
+        self.direction
+    }
+
+    /// The number of invalid transitions seen, i.e. steps that were missed
+    #[inline]
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// Reset position and detent count to zero
+    pub fn reset(&mut self) {
+        self.position = 0;
+        self.partial = 0;
+        self.detents = 0;
+    }
+}
+
+/// An event reported by a `RotaryEncoder`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum EncoderEvent {
+    /// The encoder was turned by one detent
+    Step(Direction),
+    /// The push-button was pressed
+    Pressed,
+    /// The push-button was released
+    Released,
+}
+
+/// A rotary encoder connected to two inputs and an optional push-button
+#[derive(Debug)]
+pub struct RotaryEncoder<'a, P: 'a> {
+    a: &'a P,
+    b: &'a P,
+    button: Option<(&'a P, GpioValue, bool)>,
+    decoder: QuadratureDecoder,
+}
+
+impl<'a, P: GpioIn> RotaryEncoder<'a, P> {
+    /// Create a new encoder, reading the initial state of both channels
+    ///
+    /// Both inputs should be configured to report both edges and added to the edge iterator
+    /// that is used to drive the encoder.
+    pub fn new(a: &'a P, b: &'a P, steps_per_detent: u8) -> Result<Self, P::Error> {
+        let mut decoder = QuadratureDecoder::new(steps_per_detent);
+        decoder.update(a.read_value()?, b.read_value()?);
+        Ok(RotaryEncoder {
+            a,
+            b,
+            button: None,
+            decoder,
+        })
+    }
+
+    /// Add a push-button, reading `pressed` while it is held down
+    pub fn with_button(mut self, button: &'a P, pressed: GpioValue) -> Result<Self, P::Error> {
+        let is_pressed = button.read_value()? == pressed;
+        self.button = Some((button, pressed, is_pressed));
+        Ok(self)
+    }
+
+    /// Process an edge on `triggered`
+    ///
+    /// Edges on pins not belonging to the encoder are ignored, allowing the encoder to share an
+    /// edge iterator with other inputs.
+    pub fn handle_edge(&mut self, triggered: &P) -> Result<Option<EncoderEvent>, P::Error> {
+        if ptr::eq(triggered, self.a) || ptr::eq(triggered, self.b) {
+            let (a, b) = (self.a.read_value()?, self.b.read_value()?);
+            return Ok(self.decoder.update(a, b).map(EncoderEvent::Step));
+        }
+
+        if let Some((button, pressed, ref mut was_pressed)) = self.button {
+            if ptr::eq(triggered, button) {
+                let is_pressed = button.read_value()? == pressed;
+                if is_pressed != *was_pressed {
+                    *was_pressed = is_pressed;
+                    return Ok(Some(if is_pressed {
+                        EncoderEvent::Pressed
+                    } else {
+                        EncoderEvent::Released
+                    }));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Whether the push-button is currently held down, as of the last processed edge
+    #[inline]
+    pub fn is_pressed(&self) -> bool {
+        self.button.is_some_and(|(_, _, pressed)| pressed)
+    }
+
+    /// The underlying decoder
+    #[inline]
+    pub fn decoder(&self) -> &QuadratureDecoder {
+        &self.decoder
+    }
+
+    /// The underlying decoder, e.g. to reset it
+    #[inline]
+    pub fn decoder_mut(&mut self) -> &mut QuadratureDecoder {
+        &mut self.decoder
+    }
+}
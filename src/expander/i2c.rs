@@ -0,0 +1,290 @@
+//! I2C transports for expander chips
+//!
+//! `LinuxI2cBus` talks to devices through the Linux
+//! [i2c-dev](https://www.kernel.org/doc/Documentation/i2c/dev-interface) interface, while
+//! `FakeI2cBus` routes transfers to in-memory device models for tests.
+
+use nix::libc;
+use std::{collections, fs, io, path, sync};
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+use super::nix_to_io;
+
+// I2C_SLAVE from linux/i2c-dev.h
+ioctl!(bad write_int i2c_set_slave with 0x0703);
+
+/// A bus that transfers bytes to and from I2C devices
+pub trait I2cBus {
+    /// Write `data` to the device at `addr`
+    fn write(&mut self, addr: u16, data: &[u8]) -> io::Result<()>;
+
+    /// Fill `buf` with data read from the device at `addr`
+    fn read(&mut self, addr: u16, buf: &mut [u8]) -> io::Result<()>;
+
+    /// Write `data` to the device at `addr`, then read back into `buf`
+    ///
+    /// Commonly used to select a register and read its contents.
+    #[inline]
+    fn write_read(&mut self, addr: u16, data: &[u8], buf: &mut [u8]) -> io::Result<()> {
+        self.write(addr, data)?;
+        self.read(addr, buf)
+    }
+}
+
+/// I2C bus accessed through a Linux `/dev/i2c-N` device
+///
+/// Transfers are issued as separate read and write calls, a register read therefore results in
+/// a stop condition between writing the register address and reading the value. This is
+/// supported by the expander chips in this crate.
+#[derive(Debug)]
+pub struct LinuxI2cBus {
+    fp: fs::File,
+    addr: Option<u16>,
+}
+
+impl LinuxI2cBus {
+    /// Open the I2C bus `/dev/i2c-<bus>`
+    #[inline]
+    pub fn open(bus: u8) -> io::Result<LinuxI2cBus> {
+        Self::open_path(format!("/dev/i2c-{}", bus))
+    }
+
+    /// Open an I2C bus device by path
+    pub fn open_path<P: AsRef<path::Path>>(path: P) -> io::Result<LinuxI2cBus> {
+        Ok(LinuxI2cBus {
+            fp: fs::OpenOptions::new().read(true).write(true).open(path)?,
+            addr: None,
+        })
+    }
+
+    #[inline]
+    fn select(&mut self, addr: u16) -> io::Result<()> {
+        if self.addr != Some(addr) {
+            unsafe { i2c_set_slave(self.fp.as_raw_fd(), libc::c_int::from(addr)) }
+                .map_err(nix_to_io)?;
+            self.addr = Some(addr);
+        }
+        Ok(())
+    }
+}
+
+impl I2cBus for LinuxI2cBus {
+    fn write(&mut self, addr: u16, data: &[u8]) -> io::Result<()> {
+        self.select(addr)?;
+        self.fp.write_all(data)
+    }
+
+    fn read(&mut self, addr: u16, buf: &mut [u8]) -> io::Result<()> {
+        self.select(addr)?;
+        self.fp.read_exact(buf)
+    }
+}
+
+/// A device model attached to a `FakeI2cBus`
+pub trait FakeI2cDevice {
+    /// Handle data written to the device
+    fn write(&mut self, data: &[u8]) -> io::Result<()>;
+
+    /// Answer a read from the device
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<()>;
+}
+
+type DeviceMap = collections::HashMap<u16, Box<dyn FakeI2cDevice + Send>>;
+
+/// In-memory I2C bus for tests
+///
+/// Transfers are routed to the device model attached at the target address; transfers to an
+/// address without a device fail with `ENXIO`, like an unacknowledged transfer on Linux.
+/// Clones share the same devices.
+#[derive(Clone, Default)]
+pub struct FakeI2cBus {
+    devices: sync::Arc<sync::Mutex<DeviceMap>>,
+}
+
+impl FakeI2cBus {
+    /// Create a new bus without any devices attached
+    #[inline]
+    pub fn new() -> FakeI2cBus {
+        Default::default()
+    }
+
+    /// Attach a device at `addr`, replacing any previously attached device
+    pub fn attach<D>(&self, addr: u16, device: D)
+    where
+        D: FakeI2cDevice + Send + 'static,
+    {
+        self.devices
+            .lock()
+            .unwrap_or_else(sync::PoisonError::into_inner)
+            .insert(addr, Box::new(device));
+    }
+
+    #[inline]
+    fn with_device<F, T>(&self, addr: u16, f: F) -> io::Result<T>
+    where
+        F: FnOnce(&mut (dyn FakeI2cDevice + Send)) -> io::Result<T>,
+    {
+        let mut devices = self.devices
+            .lock()
+            .unwrap_or_else(sync::PoisonError::into_inner);
+        match devices.get_mut(&addr) {
+            Some(dev) => f(dev.as_mut()),
+            None => Err(io::Error::from_raw_os_error(libc::ENXIO)),
+        }
+    }
+}
+
+impl I2cBus for FakeI2cBus {
+    #[inline]
+    fn write(&mut self, addr: u16, data: &[u8]) -> io::Result<()> {
+        self.with_device(addr, |dev| dev.write(data))
+    }
+
+    #[inline]
+    fn read(&mut self, addr: u16, buf: &mut [u8]) -> io::Result<()> {
+        self.with_device(addr, |dev| dev.read(buf))
+    }
+}
+
+#[derive(Debug)]
+struct RegisterState {
+    regs: Vec<u8>,
+    pointer: usize,
+}
+
+/// Fake device with a file of 8-bit registers
+///
+/// The first byte of every write selects the register, following bytes are written to
+/// consecutive registers. Reads start at the selected register and advance as well. Clones share
+/// the same registers, allowing tests to inspect and modify them while attached to a bus.
+#[derive(Debug, Clone)]
+pub struct FakeRegisters {
+    state: sync::Arc<sync::Mutex<RegisterState>>,
+}
+
+impl FakeRegisters {
+    /// Create `size` registers, all initialized to zero
+    pub fn new(size: usize) -> FakeRegisters {
+        FakeRegisters {
+            state: sync::Arc::new(sync::Mutex::new(RegisterState {
+                regs: vec![0; size],
+                pointer: 0,
+            })),
+        }
+    }
+
+    #[inline]
+    fn state(&self) -> sync::MutexGuard<'_, RegisterState> {
+        self.state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// Read a register
+    #[inline]
+    pub fn get(&self, reg: u8) -> u8 {
+        self.state().regs[reg as usize]
+    }
+
+    /// Set a register
+    #[inline]
+    pub fn set(&self, reg: u8, value: u8) {
+        self.state().regs[reg as usize] = value;
+    }
+}
+
+impl FakeI2cDevice for FakeRegisters {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut state = self.state();
+        if let Some((&reg, values)) = data.split_first() {
+            if reg as usize + values.len() > state.regs.len() {
+                return Err(io::Error::from_raw_os_error(libc::EREMOTEIO));
+            }
+            state.pointer = reg as usize;
+            for &val in values {
+                let ptr = state.pointer;
+                state.regs[ptr] = val;
+                state.pointer += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        let mut state = self.state();
+        let start = state.pointer;
+        if start + buf.len() > state.regs.len() {
+            return Err(io::Error::from_raw_os_error(libc::EREMOTEIO));
+        }
+        buf.copy_from_slice(&state.regs[start..start + buf.len()]);
+        state.pointer += buf.len();
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct LatchState {
+    latch: u8,
+    inputs: u8,
+}
+
+/// Fake device with a single quasi-bidirectional 8-bit port
+///
+/// Models chips like the PCF8574: writes set an output latch, reads return the latch combined
+/// with the externally applied input levels, i.e. a pin reads high only if both its latch bit
+/// and its input are high. Clones share the same state.
+#[derive(Debug, Clone)]
+pub struct FakeLatch {
+    state: sync::Arc<sync::Mutex<LatchState>>,
+}
+
+impl FakeLatch {
+    /// Create a new port, with the latch and all inputs high
+    pub fn new() -> FakeLatch {
+        FakeLatch {
+            state: sync::Arc::new(sync::Mutex::new(LatchState {
+                latch: 0xFF,
+                inputs: 0xFF,
+            })),
+        }
+    }
+
+    #[inline]
+    fn state(&self) -> sync::MutexGuard<'_, LatchState> {
+        self.state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// The last value written to the device
+    #[inline]
+    pub fn latch(&self) -> u8 {
+        self.state().latch
+    }
+
+    /// Set the levels applied externally to the pins
+    #[inline]
+    pub fn set_inputs(&self, inputs: u8) {
+        self.state().inputs = inputs;
+    }
+}
+
+impl Default for FakeLatch {
+    #[inline]
+    fn default() -> FakeLatch {
+        FakeLatch::new()
+    }
+}
+
+impl FakeI2cDevice for FakeLatch {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        if let Some(&val) = data.last() {
+            self.state().latch = val;
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        let state = self.state();
+        for b in buf.iter_mut() {
+            *b = state.latch & state.inputs;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+//! Microchip MCP23017 16-bit I2C expander
+//!
+//! See the `mcp23x17` module for the pin numbering and register definitions.
+
+use super::ExpanderResult;
+use super::i2c::I2cBus;
+use super::mcp23x17::{Mcp23x17, RegisterBus};
+
+/// Register access to an MCP23017 through an I2C bus
+#[derive(Debug)]
+pub struct I2cRegisters<B> {
+    bus: B,
+    addr: u16,
+}
+
+impl<B: I2cBus> RegisterBus for I2cRegisters<B> {
+    fn write_regs(&mut self, reg: u8, values: &[u8]) -> ExpanderResult<()> {
+        let mut buf = Vec::with_capacity(values.len() + 1);
+        buf.push(reg);
+        buf.extend_from_slice(values);
+        self.bus.write(self.addr, &buf)?;
+        Ok(())
+    }
+
+    #[inline]
+    fn read_regs(&mut self, reg: u8, buf: &mut [u8]) -> ExpanderResult<()> {
+        self.bus.write_read(self.addr, &[reg], buf)?;
+        Ok(())
+    }
+}
+
+/// MCP23017 chip handle
+pub type Mcp23017<B> = Mcp23x17<I2cRegisters<B>>;
+
+impl<B: I2cBus> Mcp23x17<I2cRegisters<B>> {
+    /// Initialize the MCP23017 at address `addr` (`0x20` to `0x27`)
+    ///
+    /// All pins are reset to inputs without pull-ups and interrupts are disabled.
+    #[inline]
+    pub fn new(bus: B, addr: u16) -> ExpanderResult<Mcp23017<B>> {
+        Mcp23x17::with_registers(I2cRegisters { bus, addr }, 0)
+    }
+}
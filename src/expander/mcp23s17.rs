@@ -0,0 +1,162 @@
+//! Microchip MCP23S17 16-bit SPI expander
+//!
+//! Up to eight MCP23S17 can share a single chip select, each one is selected by the address
+//! configured on its `A0`-`A2` pins. See the `mcp23x17` module for the pin numbering and
+//! register definitions.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use gpio::{GpioOut, GpioValue};
+//! use gpio::expander::Expander;
+//! use gpio::expander::mcp23s17::{FakeMcp23s17, Mcp23s17};
+//! use gpio::expander::mcp23x17::{GPIOB, OLATA, OLATB};
+//! use gpio::expander::spi::FakeSpiBus;
+//!
+//! // two chips with hardware addresses 0 and 3 on the same chip select
+//! let bus = FakeSpiBus::new();
+//! let dev0 = FakeMcp23s17::new(0);
+//! let dev3 = FakeMcp23s17::new(3);
+//! bus.attach(dev0.clone());
+//! bus.attach(dev3.clone());
+//!
+//! let chip0 = Mcp23s17::new(bus.clone(), 0).unwrap();
+//! let chip3 = Mcp23s17::new(bus.clone(), 3).unwrap();
+//!
+//! // set the lower nibble of port A on chip 3 in a single transfer
+//! let transfers = bus.transfer_count();
+//! chip3.write_masked(0x000F, 0x0005).unwrap();
+//! assert_eq!(bus.transfer_count(), transfers + 1);
+//! assert_eq!(dev3.registers().get(OLATA), 0x05);
+//! assert_eq!(dev0.registers().get(OLATA), 0x00);
+//!
+//! // writing values that are already latched does not cause any bus traffic
+//! chip3.write_masked(0x000F, 0x0005).unwrap();
+//! assert_eq!(bus.transfer_count(), transfers + 1);
+//!
+//! // single pins are available through the usual traits
+//! let mut out = chip0.output(15).unwrap();
+//! out.set_high().unwrap();
+//! assert_eq!(dev0.registers().get(OLATB), 0x80);
+//!
+//! dev0.registers().set(GPIOB, 0x80);
+//! assert_eq!(chip0.read_pin(15).unwrap(), GpioValue::High);
+//! ```
+
+use super::ExpanderResult;
+use super::i2c::FakeRegisters;
+use super::mcp23x17::{Mcp23x17, RegisterBus, IOCON, IOCON_HAEN, IOCON_MIRROR, REGISTER_COUNT};
+use super::spi::{FakeSpiDevice, SpiBus};
+
+const OPCODE: u8 = 0x40;
+const OPCODE_READ: u8 = 0x01;
+
+#[inline]
+fn opcode(hw_addr: u8, read: bool) -> u8 {
+    OPCODE | ((hw_addr & 0x07) << 1) | if read { OPCODE_READ } else { 0 }
+}
+
+/// Register access to an MCP23S17 through an SPI bus
+#[derive(Debug)]
+pub struct SpiRegisters<B> {
+    bus: B,
+    hw_addr: u8,
+}
+
+impl<B: SpiBus> RegisterBus for SpiRegisters<B> {
+    fn write_regs(&mut self, reg: u8, values: &[u8]) -> ExpanderResult<()> {
+        let mut tx = Vec::with_capacity(values.len() + 2);
+        tx.push(opcode(self.hw_addr, false));
+        tx.push(reg);
+        tx.extend_from_slice(values);
+        let mut rx = vec![0; tx.len()];
+        self.bus.transfer(&tx, &mut rx)?;
+        Ok(())
+    }
+
+    fn read_regs(&mut self, reg: u8, buf: &mut [u8]) -> ExpanderResult<()> {
+        let mut tx = vec![0; buf.len() + 2];
+        tx[0] = opcode(self.hw_addr, true);
+        tx[1] = reg;
+        let mut rx = vec![0; tx.len()];
+        self.bus.transfer(&tx, &mut rx)?;
+        buf.copy_from_slice(&rx[2..]);
+        Ok(())
+    }
+}
+
+/// MCP23S17 chip handle
+pub type Mcp23s17<B> = Mcp23x17<SpiRegisters<B>>;
+
+impl<B: SpiBus> Mcp23x17<SpiRegisters<B>> {
+    /// Initialize the MCP23S17 with hardware address `hw_addr` (`0` to `7`)
+    ///
+    /// Hardware addressing is disabled after power-up, making every chip respond to address 0.
+    /// It is therefore enabled through address 0 first, which configures all chips sharing the
+    /// chip select the same way. All pins are reset to inputs without pull-ups and interrupts are
+    /// disabled.
+    pub fn new(mut bus: B, hw_addr: u8) -> ExpanderResult<Mcp23s17<B>> {
+        let tx = [opcode(0, false), IOCON, IOCON_HAEN | IOCON_MIRROR];
+        let mut rx = [0; 3];
+        bus.transfer(&tx, &mut rx)?;
+
+        Mcp23x17::with_registers(SpiRegisters { bus, hw_addr }, IOCON_HAEN)
+    }
+}
+
+/// Fake MCP23S17 for use with a `FakeSpiBus`
+///
+/// Models the register file and hardware addressing of the chip. Clones share the same
+/// registers.
+#[derive(Debug, Clone)]
+pub struct FakeMcp23s17 {
+    hw_addr: u8,
+    regs: FakeRegisters,
+}
+
+impl FakeMcp23s17 {
+    /// Create a chip with its address pins set to `hw_addr`
+    pub fn new(hw_addr: u8) -> FakeMcp23s17 {
+        FakeMcp23s17 {
+            hw_addr,
+            regs: FakeRegisters::new(REGISTER_COUNT),
+        }
+    }
+
+    /// The registers of the chip
+    #[inline]
+    pub fn registers(&self) -> &FakeRegisters {
+        &self.regs
+    }
+}
+
+impl FakeSpiDevice for FakeMcp23s17 {
+    fn transfer(&mut self, tx: &[u8], rx: &mut [u8]) {
+        if tx.len() < 2 || tx[0] & 0xF0 != OPCODE {
+            return;
+        }
+
+        // without HAEN, the chip only responds to address 0
+        let addr = if self.regs.get(IOCON) & IOCON_HAEN != 0 {
+            self.hw_addr
+        } else {
+            0
+        };
+        if (tx[0] >> 1) & 0x07 != addr {
+            return;
+        }
+
+        let start = tx[1] as usize;
+        for i in 2..tx.len() {
+            let reg = start + i - 2;
+            if reg >= REGISTER_COUNT {
+                break;
+            }
+            if tx[0] & OPCODE_READ != 0 {
+                rx[i] = self.regs.get(reg as u8);
+            } else {
+                self.regs.set(reg as u8, tx[i]);
+            }
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! Microchip MCP23x17 16-bit expander family
+//!
+//! The MCP23017 (I2C) and MCP23S17 (SPI) share the same register set, which is driven by
+//! `Mcp23x17`. The bus specific parts are provided by a `RegisterBus`, see the `mcp23017` and
+//! `mcp23s17` modules.
+//!
+//! Pins 0 to 7 map to port A (`GPA0`-`GPA7`), pins 8 to 15 to port B (`GPB0`-`GPB7`). The chip
+//! is used with the default register layout (`IOCON.BANK = 0`) and its two interrupt outputs
+//! mirrored, so either `INTA` or `INTB` can be wired to the host.
+//!
+//! Configuration and output registers are cached, writes that would not change a register are
+//! not sent to the chip at all.
+
+use std::{fmt, sync};
+use super::{check_pin, Expander, ExpanderEdge, ExpanderResult};
+use super::super::{GpioEdge, GpioValue};
+
+/// I/O direction register of port A, port B follows at the next address
+pub const IODIRA: u8 = 0x00;
+pub const IODIRB: u8 = 0x01;
+/// Input polarity register of port A
+pub const IPOLA: u8 = 0x02;
+pub const IPOLB: u8 = 0x03;
+/// Interrupt-on-change enable register of port A
+pub const GPINTENA: u8 = 0x04;
+pub const GPINTENB: u8 = 0x05;
+/// Default compare value register of port A
+pub const DEFVALA: u8 = 0x06;
+pub const DEFVALB: u8 = 0x07;
+/// Interrupt control register of port A
+pub const INTCONA: u8 = 0x08;
+pub const INTCONB: u8 = 0x09;
+/// Configuration register, shared by both ports
+pub const IOCON: u8 = 0x0A;
+/// Pull-up register of port A
+pub const GPPUA: u8 = 0x0C;
+pub const GPPUB: u8 = 0x0D;
+/// Interrupt flag register of port A
+pub const INTFA: u8 = 0x0E;
+pub const INTFB: u8 = 0x0F;
+/// Interrupt capture register of port A
+pub const INTCAPA: u8 = 0x10;
+pub const INTCAPB: u8 = 0x11;
+/// Port register of port A
+pub const GPIOA: u8 = 0x12;
+pub const GPIOB: u8 = 0x13;
+/// Output latch register of port A
+pub const OLATA: u8 = 0x14;
+pub const OLATB: u8 = 0x15;
+
+/// `IOCON` bit connecting both interrupt outputs
+pub const IOCON_MIRROR: u8 = 0x40;
+/// `IOCON` bit enabling the hardware address pins of the MCP23S17
+pub const IOCON_HAEN: u8 = 0x08;
+
+/// Number of registers in the default (`BANK = 0`) layout
+pub const REGISTER_COUNT: usize = 0x16;
+
+const PIN_COUNT: u8 = 16;
+
+/// Register access to a single chip
+///
+/// Writes and reads of multiple bytes access consecutive registers.
+pub trait RegisterBus {
+    /// Write `values` to the registers starting at `reg`
+    fn write_regs(&mut self, reg: u8, values: &[u8]) -> ExpanderResult<()>;
+
+    /// Read the registers starting at `reg` into `buf`
+    fn read_regs(&mut self, reg: u8, buf: &mut [u8]) -> ExpanderResult<()>;
+}
+
+struct Mcp23x17State<R> {
+    regs: R,
+    iodir: u16,
+    gppu: u16,
+    gpinten: u16,
+    olat: u16,
+    edges: [GpioEdge; PIN_COUNT as usize],
+}
+
+impl<R: RegisterBus> Mcp23x17State<R> {
+    /// Writes the bytes of a 16-bit register pair that differ from the cached value
+    fn update(&mut self, reg_a: u8, cached: u16, value: u16) -> ExpanderResult<()> {
+        let changed = cached ^ value;
+        let (lo, hi) = (value as u8, (value >> 8) as u8);
+
+        match (changed & 0x00FF != 0, changed & 0xFF00 != 0) {
+            (false, false) => Ok(()),
+            (true, false) => self.regs.write_regs(reg_a, &[lo]),
+            (false, true) => self.regs.write_regs(reg_a + 1, &[hi]),
+            (true, true) => self.regs.write_regs(reg_a, &[lo, hi]),
+        }
+    }
+
+    #[inline]
+    fn read16(&mut self, reg_a: u8) -> ExpanderResult<u16> {
+        let mut buf = [0; 2];
+        self.regs.read_regs(reg_a, &mut buf)?;
+        Ok(u16::from(buf[0]) | (u16::from(buf[1]) << 8))
+    }
+}
+
+#[inline]
+fn with_bit(reg: u16, pin: u8, set: bool) -> u16 {
+    if set {
+        reg | (1 << pin)
+    } else {
+        reg & !(1 << pin)
+    }
+}
+
+/// MCP23x17 chip handle
+///
+/// Clones refer to the same chip.
+pub struct Mcp23x17<R> {
+    state: sync::Arc<sync::Mutex<Mcp23x17State<R>>>,
+}
+
+impl<R> Clone for Mcp23x17<R> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Mcp23x17 {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<R> fmt::Debug for Mcp23x17<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Mcp23x17").finish()
+    }
+}
+
+impl<R: RegisterBus> Mcp23x17<R> {
+    /// Initialize a chip
+    ///
+    /// All pins are reset to inputs without pull-ups and interrupts are disabled. `iocon` is
+    /// written to the configuration register, `IOCON_MIRROR` is always added.
+    pub fn with_registers(mut regs: R, iocon: u8) -> ExpanderResult<Mcp23x17<R>> {
+        regs.write_regs(IOCON, &[iocon | IOCON_MIRROR])?;
+        regs.write_regs(IODIRA, &[0xFF, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0])?;
+        regs.write_regs(GPPUA, &[0, 0])?;
+        regs.write_regs(OLATA, &[0, 0])?;
+
+        Ok(Mcp23x17 {
+            state: sync::Arc::new(sync::Mutex::new(Mcp23x17State {
+                regs,
+                iodir: 0xFFFF,
+                gppu: 0,
+                gpinten: 0,
+                olat: 0,
+                edges: [GpioEdge::None; PIN_COUNT as usize],
+            })),
+        })
+    }
+
+    #[inline]
+    fn state(&self) -> sync::MutexGuard<'_, Mcp23x17State<R>> {
+        self.state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// Enable or disable the internal 100 kΩ pull-up of a pin
+    pub fn set_pull_up(&self, pin: u8, enabled: bool) -> ExpanderResult<()> {
+        check_pin(self, pin)?;
+        let mut state = self.state();
+        let gppu = with_bit(state.gppu, pin, enabled);
+        let cached = state.gppu;
+        state.update(GPPUA, cached, gppu)?;
+        state.gppu = gppu;
+        Ok(())
+    }
+
+    /// Read all 16 pins at once, pin 0 being the least significant bit
+    #[inline]
+    pub fn read_all(&self) -> ExpanderResult<u16> {
+        self.state().read16(GPIOA)
+    }
+
+    /// Set the output latches of all pins selected by `mask` at once
+    ///
+    /// Bits of `values` outside of `mask` are ignored. Only ports whose latch actually changes
+    /// are written, both ports are updated in a single transfer if necessary.
+    pub fn write_masked(&self, mask: u16, values: u16) -> ExpanderResult<()> {
+        let mut state = self.state();
+        let olat = (state.olat & !mask) | (values & mask);
+        let cached = state.olat;
+        state.update(OLATA, cached, olat)?;
+        state.olat = olat;
+        Ok(())
+    }
+}
+
+impl<R: RegisterBus> Expander for Mcp23x17<R> {
+    #[inline]
+    fn pin_count(&self) -> u8 {
+        PIN_COUNT
+    }
+
+    fn make_input(&self, pin: u8) -> ExpanderResult<()> {
+        check_pin(self, pin)?;
+        let mut state = self.state();
+        let iodir = with_bit(state.iodir, pin, true);
+        let cached = state.iodir;
+        state.update(IODIRA, cached, iodir)?;
+        state.iodir = iodir;
+        Ok(())
+    }
+
+    fn make_output(&self, pin: u8) -> ExpanderResult<()> {
+        check_pin(self, pin)?;
+        let mut state = self.state();
+        let iodir = with_bit(state.iodir, pin, false);
+        let cached = state.iodir;
+        state.update(IODIRA, cached, iodir)?;
+        state.iodir = iodir;
+        Ok(())
+    }
+
+    fn read_pin(&self, pin: u8) -> ExpanderResult<GpioValue> {
+        check_pin(self, pin)?;
+        let mut buf = [0; 1];
+        self.state()
+            .regs
+            .read_regs(if pin < 8 { GPIOA } else { GPIOB }, &mut buf)?;
+        Ok(GpioValue::from(buf[0] & (1 << (pin % 8))))
+    }
+
+    fn write_pin(&self, pin: u8, value: GpioValue) -> ExpanderResult<()> {
+        check_pin(self, pin)?;
+        self.write_masked(1 << pin, with_bit(0, pin, value.into()))
+    }
+
+    fn set_edge(&self, pin: u8, edge: GpioEdge) -> ExpanderResult<()> {
+        check_pin(self, pin)?;
+        let mut state = self.state();
+
+        // edges are filtered when reading the interrupt state, the chip only has to report changes
+        let gpinten = with_bit(state.gpinten, pin, edge != GpioEdge::None);
+        let cached = state.gpinten;
+        state.update(GPINTENA, cached, gpinten)?;
+        state.gpinten = gpinten;
+        state.edges[pin as usize] = edge;
+        Ok(())
+    }
+
+    fn read_interrupts(&self) -> ExpanderResult<Vec<ExpanderEdge>> {
+        let mut state = self.state();
+
+        // INTFA, INTFB, INTCAPA, INTCAPB are consecutive. reading INTCAP clears the interrupt
+        let mut buf = [0; 4];
+        state.regs.read_regs(INTFA, &mut buf)?;
+        let intf = u16::from(buf[0]) | (u16::from(buf[1]) << 8);
+        let intcap = u16::from(buf[2]) | (u16::from(buf[3]) << 8);
+
+        Ok((0..PIN_COUNT)
+            .filter(|&pin| intf & (1 << pin) != 0)
+            .map(|pin| ExpanderEdge {
+                pin,
+                value: GpioValue::from(intcap & (1 << pin) != 0),
+            })
+            .filter(|e| state.edges[e.pin as usize].matches(e.value))
+            .collect())
+    }
+}
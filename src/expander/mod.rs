@@ -0,0 +1,296 @@
+//! GPIO expander chips
+//!
+//! Port expanders such as the MCP23017, MCP23S17 or PCF8574 add GPIO pins through a serial bus.
+//! This module drives these chips and exposes each of their pins through the crate's
+//! `GpioIn`/`GpioOut` traits, making them interchangeable with native pins.
+//!
+//! Chips are accessed through small transport traits (`i2c::I2cBus` and `spi::SpiBus`), which
+//! are implemented for the Linux `/dev/i2c-N` and `/dev/spidevB.C` interfaces and for in-memory
+//! fakes used in tests.
+//!
+//! A chip handle (e.g. `Mcp23017`) is cheap to clone, all clones and pins share the same bus and
+//! register cache. Pins are created from the handle using `Expander::input` and
+//! `Expander::output`.
+//!
+//! Expanders signal input changes through an interrupt output, which is usually wired to a
+//! native GPIO. Every time that GPIO triggers, `Expander::read_interrupts` reads back which pins
+//! changed; `ExpanderEdgeIter` does this automatically for any edge iterator of the host GPIO.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use gpio::{GpioEdge, GpioIn, GpioOut, GpioValue};
+//! use gpio::expander::{Expander, ExpanderEdge, ExpanderEdgeIter};
+//! use gpio::expander::i2c::{FakeI2cBus, FakeRegisters};
+//! use gpio::expander::mcp23017::Mcp23017;
+//! use gpio::expander::mcp23x17::{self, REGISTER_COUNT};
+//!
+//! // a fake MCP23017 at address 0x20
+//! let bus = FakeI2cBus::new();
+//! let regs = FakeRegisters::new(REGISTER_COUNT);
+//! bus.attach(0x20, regs.clone());
+//!
+//! let chip = Mcp23017::new(bus, 0x20).unwrap();
+//!
+//! // pin 9 (GPB1) drives a LED
+//! let mut led = chip.output(9).unwrap();
+//! led.set_high().unwrap();
+//! assert_eq!(regs.get(mcp23x17::OLATB), 0b0000_0010);
+//!
+//! // pin 0 (GPA0) is a button, reporting falling edges
+//! let mut button = chip.input(0).unwrap();
+//! button.set_edge(GpioEdge::Falling).unwrap();
+//! regs.set(mcp23x17::GPIOA, 0b0000_0001);
+//! assert_eq!(button.read_value().unwrap(), GpioValue::High);
+//!
+//! // the button is pressed and the chip raises its interrupt line, which is normally read
+//! // through `SysFsGpioEdgeIter`
+//! regs.set(mcp23x17::INTFA, 0b0000_0001);
+//! regs.set(mcp23x17::INTCAPA, 0b0000_0000);
+//! let host_interrupts = vec![Ok::<(), ()>(())];
+//!
+//! let edges: Vec<_> = ExpanderEdgeIter::new(&chip, host_interrupts)
+//!     .map(|e| e.unwrap())
+//!     .collect();
+//! assert_eq!(edges, vec![ExpanderEdge { pin: 0, value: GpioValue::Low }]);
+//! ```
+
+use nix;
+use std::{collections, io};
+use super::{GpioEdge, GpioIn, GpioOut, GpioValue};
+use dummy;
+use sysfs;
+
+pub mod i2c;
+pub mod spi;
+pub mod mcp23x17;
+pub mod mcp23017;
+pub mod mcp23s17;
+pub mod pcf8574;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum ExpanderError {
+        Io(err: io::Error) {
+            from()
+            description("io error")
+            display("I/O error: {}", err)
+            cause(err)
+        }
+        InvalidPin(pin: u8) {
+            description("pin number out of range for expander")
+            display("pin number {} is out of range for expander", pin)
+        }
+        Sysfs(err: sysfs::GpioError) {
+            from()
+            description("interrupt gpio error")
+            display("Interrupt GPIO error: {}", err)
+            cause(err)
+        }
+        Dummy(err: dummy::DummyError) {
+            from()
+            description("interrupt dummy error")
+            display("Interrupt dummy error: {}", err)
+        }
+        InterruptSource {
+            from(())
+            description("interrupt source failed")
+            display("Interrupt source failed")
+        }
+    }
+}
+
+pub type ExpanderResult<T> = Result<T, ExpanderError>;
+
+/// An edge that occurred on an expander pin
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ExpanderEdge {
+    /// The pin number on the expander
+    pub pin: u8,
+    /// The value of the pin after the edge
+    pub value: GpioValue,
+}
+
+/// A GPIO expander chip
+///
+/// Implementations are handles to a chip that are cheap to clone, e.g. by wrapping their state in
+/// an `Arc`.
+pub trait Expander {
+    /// The number of pins on the chip
+    fn pin_count(&self) -> u8;
+
+    /// Configure a pin as an input
+    fn make_input(&self, pin: u8) -> ExpanderResult<()>;
+
+    /// Configure a pin as an output
+    fn make_output(&self, pin: u8) -> ExpanderResult<()>;
+
+    /// Read the current value of a pin
+    fn read_pin(&self, pin: u8) -> ExpanderResult<GpioValue>;
+
+    /// Set the output value of a pin
+    fn write_pin(&self, pin: u8, value: GpioValue) -> ExpanderResult<()>;
+
+    /// Configure which edges of a pin are reported by `read_interrupts`
+    fn set_edge(&self, pin: u8, edge: GpioEdge) -> ExpanderResult<()>;
+
+    /// Read and clear the chip's interrupt state
+    ///
+    /// Should be called whenever the interrupt output of the chip signals a change. Returns the
+    /// edges that occurred, filtered by the edge configuration of each pin.
+    fn read_interrupts(&self) -> ExpanderResult<Vec<ExpanderEdge>>;
+
+    /// Configure a pin as an input and return a handle to it
+    fn input(&self, pin: u8) -> ExpanderResult<ExpanderInput<Self>>
+    where
+        Self: Clone + Sized,
+    {
+        self.make_input(pin)?;
+        Ok(ExpanderInput {
+            chip: self.clone(),
+            pin,
+        })
+    }
+
+    /// Configure a pin as an output and return a handle to it
+    fn output(&self, pin: u8) -> ExpanderResult<ExpanderOutput<Self>>
+    where
+        Self: Clone + Sized,
+    {
+        self.make_output(pin)?;
+        Ok(ExpanderOutput {
+            chip: self.clone(),
+            pin,
+        })
+    }
+}
+
+/// The `io::Error` of a failed system call of a transport
+#[inline]
+pub(crate) fn nix_to_io(err: nix::Error) -> io::Error {
+    match err {
+        nix::Error::Sys(errno) => io::Error::from_raw_os_error(errno as i32),
+        err => io::Error::other(err),
+    }
+}
+
+#[inline]
+fn check_pin<E: Expander + ?Sized>(chip: &E, pin: u8) -> ExpanderResult<()> {
+    if pin < chip.pin_count() {
+        Ok(())
+    } else {
+        Err(ExpanderError::InvalidPin(pin))
+    }
+}
+
+/// Input pin of an expander chip
+#[derive(Debug, Clone)]
+pub struct ExpanderInput<E> {
+    chip: E,
+    pin: u8,
+}
+
+impl<E> ExpanderInput<E> {
+    /// The pin number on the expander
+    #[inline]
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+}
+
+impl<E: Expander> GpioIn for ExpanderInput<E> {
+    type Error = ExpanderError;
+
+    #[inline]
+    fn read_value(&self) -> ExpanderResult<GpioValue> {
+        self.chip.read_pin(self.pin)
+    }
+
+    #[inline]
+    fn set_edge(&mut self, edge: GpioEdge) -> ExpanderResult<()> {
+        self.chip.set_edge(self.pin, edge)
+    }
+}
+
+/// Output pin of an expander chip
+#[derive(Debug, Clone)]
+pub struct ExpanderOutput<E> {
+    chip: E,
+    pin: u8,
+}
+
+impl<E> ExpanderOutput<E> {
+    /// The pin number on the expander
+    #[inline]
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+}
+
+impl<E: Expander> GpioOut for ExpanderOutput<E> {
+    type Error = ExpanderError;
+
+    #[inline]
+    fn set_low(&mut self) -> ExpanderResult<()> {
+        self.chip.write_pin(self.pin, GpioValue::Low)
+    }
+
+    #[inline]
+    fn set_high(&mut self) -> ExpanderResult<()> {
+        self.chip.write_pin(self.pin, GpioValue::High)
+    }
+}
+
+/// Iterator over edges on expander pins
+///
+/// Wraps an iterator over interrupts of the GPIO connected to the chip's interrupt output, e.g.
+/// a `SysFsGpioEdgeIter`, and reads back the chip's interrupt state every time it fires.
+pub struct ExpanderEdgeIter<'a, E: 'a, I> {
+    chip: &'a E,
+    interrupts: I,
+    pending: collections::VecDeque<ExpanderEdge>,
+}
+
+impl<'a, E, I, T, Er> ExpanderEdgeIter<'a, E, I>
+where
+    E: Expander,
+    I: Iterator<Item = Result<T, Er>>,
+    Er: Into<ExpanderError>,
+{
+    pub fn new<It>(chip: &'a E, interrupts: It) -> ExpanderEdgeIter<'a, E, I>
+    where
+        It: IntoIterator<IntoIter = I, Item = Result<T, Er>>,
+    {
+        ExpanderEdgeIter {
+            chip,
+            interrupts: interrupts.into_iter(),
+            pending: collections::VecDeque::new(),
+        }
+    }
+}
+
+impl<'a, E, I, T, Er> Iterator for ExpanderEdgeIter<'a, E, I>
+where
+    E: Expander,
+    I: Iterator<Item = Result<T, Er>>,
+    Er: Into<ExpanderError>,
+{
+    type Item = ExpanderResult<ExpanderEdge>;
+
+    fn next(&mut self) -> Option<ExpanderResult<ExpanderEdge>> {
+        loop {
+            if let Some(edge) = self.pending.pop_front() {
+                return Some(Ok(edge));
+            }
+
+            // interrupts that only concern pins without a matching edge setting are skipped
+            match self.interrupts.next()? {
+                Ok(_) => match self.chip.read_interrupts() {
+                    Ok(edges) => self.pending.extend(edges),
+                    Err(e) => return Some(Err(e)),
+                },
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! NXP/TI PCF8574 8-bit I2C expander
+//!
+//! The PCF8574 has no direction or interrupt registers. Its pins are quasi-bidirectional: a pin
+//! written high is weakly pulled up and can be used as an input, a pin written low is actively
+//! driven. The interrupt output goes low whenever an input changes and is released on the next
+//! read, which is why changes are detected by comparing against the last read value.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use gpio::{GpioEdge, GpioIn, GpioOut, GpioValue};
+//! use gpio::expander::{Expander, ExpanderEdge};
+//! use gpio::expander::i2c::{FakeI2cBus, FakeLatch};
+//! use gpio::expander::pcf8574::Pcf8574;
+//!
+//! let bus = FakeI2cBus::new();
+//! let port = FakeLatch::new();
+//! bus.attach(0x38, port.clone());
+//!
+//! let chip = Pcf8574::new(bus, 0x38).unwrap();
+//! let mut relay = chip.output(7).unwrap();
+//! let mut sensor = chip.input(2).unwrap();
+//! sensor.set_edge(GpioEdge::Both).unwrap();
+//!
+//! relay.set_low().unwrap();
+//! assert_eq!(port.latch(), 0b0111_1111);
+//!
+//! // the sensor pulls its line low
+//! port.set_inputs(0b1111_1011);
+//! assert_eq!(sensor.read_value().unwrap(), GpioValue::Low);
+//! assert_eq!(
+//!     chip.read_interrupts().unwrap(),
+//!     vec![ExpanderEdge { pin: 2, value: GpioValue::Low }]
+//! );
+//! ```
+
+use std::sync;
+use super::{check_pin, Expander, ExpanderEdge, ExpanderResult};
+use super::i2c::I2cBus;
+use super::super::{GpioEdge, GpioValue};
+
+const PIN_COUNT: u8 = 8;
+
+struct Pcf8574State<B> {
+    bus: B,
+    addr: u16,
+    latch: u8,
+    outputs: u8,
+    last: u8,
+    edges: [GpioEdge; PIN_COUNT as usize],
+}
+
+impl<B: I2cBus> Pcf8574State<B> {
+    #[inline]
+    fn write_latch(&mut self) -> ExpanderResult<()> {
+        self.bus.write(self.addr, &[self.latch])?;
+        Ok(())
+    }
+
+    #[inline]
+    fn read_port(&mut self) -> ExpanderResult<u8> {
+        let mut buf = [0; 1];
+        self.bus.read(self.addr, &mut buf)?;
+        Ok(buf[0])
+    }
+}
+
+/// PCF8574 chip handle
+///
+/// Clones refer to the same chip.
+pub struct Pcf8574<B> {
+    state: sync::Arc<sync::Mutex<Pcf8574State<B>>>,
+}
+
+impl<B> Clone for Pcf8574<B> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Pcf8574 {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<B> ::std::fmt::Debug for Pcf8574<B> {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_struct("Pcf8574").finish()
+    }
+}
+
+impl<B: I2cBus> Pcf8574<B> {
+    /// Initialize the chip at address `addr`
+    ///
+    /// Valid addresses are `0x20` to `0x27` for the PCF8574 and `0x38` to `0x3F` for the
+    /// PCF8574A. All pins are released high, i.e. configured as inputs.
+    pub fn new(bus: B, addr: u16) -> ExpanderResult<Pcf8574<B>> {
+        let mut state = Pcf8574State {
+            bus,
+            addr,
+            latch: 0xFF,
+            outputs: 0,
+            last: 0,
+            edges: [GpioEdge::None; PIN_COUNT as usize],
+        };
+        state.write_latch()?;
+        state.last = state.read_port()?;
+
+        Ok(Pcf8574 {
+            state: sync::Arc::new(sync::Mutex::new(state)),
+        })
+    }
+
+    #[inline]
+    fn state(&self) -> sync::MutexGuard<'_, Pcf8574State<B>> {
+        self.state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// Read all 8 pins at once, pin 0 being the least significant bit
+    pub fn read_all(&self) -> ExpanderResult<u8> {
+        self.state().read_port()
+    }
+}
+
+impl<B: I2cBus> Expander for Pcf8574<B> {
+    #[inline]
+    fn pin_count(&self) -> u8 {
+        PIN_COUNT
+    }
+
+    fn make_input(&self, pin: u8) -> ExpanderResult<()> {
+        check_pin(self, pin)?;
+        let mut state = self.state();
+        state.outputs &= !(1 << pin);
+        state.latch |= 1 << pin;
+        state.write_latch()
+    }
+
+    fn make_output(&self, pin: u8) -> ExpanderResult<()> {
+        check_pin(self, pin)?;
+        self.state().outputs |= 1 << pin;
+        Ok(())
+    }
+
+    fn read_pin(&self, pin: u8) -> ExpanderResult<GpioValue> {
+        check_pin(self, pin)?;
+        Ok(GpioValue::from(self.state().read_port()? & (1 << pin)))
+    }
+
+    fn write_pin(&self, pin: u8, value: GpioValue) -> ExpanderResult<()> {
+        check_pin(self, pin)?;
+        let mut state = self.state();
+        match value {
+            GpioValue::High => state.latch |= 1 << pin,
+            GpioValue::Low => state.latch &= !(1 << pin),
+        }
+        state.write_latch()
+    }
+
+    fn set_edge(&self, pin: u8, edge: GpioEdge) -> ExpanderResult<()> {
+        check_pin(self, pin)?;
+        self.state().edges[pin as usize] = edge;
+        Ok(())
+    }
+
+    fn read_interrupts(&self) -> ExpanderResult<Vec<ExpanderEdge>> {
+        let mut state = self.state();
+        let current = state.read_port()?;
+        let changed = (current ^ state.last) & !state.outputs;
+        state.last = current;
+
+        Ok((0..PIN_COUNT)
+            .filter(|&pin| changed & (1 << pin) != 0)
+            .map(|pin| ExpanderEdge {
+                pin,
+                value: GpioValue::from(current & (1 << pin)),
+            })
+            .filter(|e| state.edges[e.pin as usize].matches(e.value))
+            .collect())
+    }
+}
@@ -0,0 +1,172 @@
+//! SPI transports for expander chips
+//!
+//! `LinuxSpiBus` talks to devices through the Linux
+//! [spidev](https://www.kernel.org/doc/Documentation/spi/spidev) interface, while `FakeSpiBus`
+//! routes transfers to in-memory device models for tests.
+
+use nix::libc;
+use std::{fs, io, path, sync};
+use std::os::unix::io::AsRawFd;
+use super::nix_to_io;
+
+/// `struct spi_ioc_transfer` from linux/spi/spidev.h
+#[repr(C)]
+#[derive(Debug, Default)]
+struct SpiIocTransfer {
+    tx_buf: u64,
+    rx_buf: u64,
+    len: u32,
+    speed_hz: u32,
+    delay_usecs: u16,
+    bits_per_word: u8,
+    cs_change: u8,
+    tx_nbits: u8,
+    rx_nbits: u8,
+    word_delay_usecs: u8,
+    pad: u8,
+}
+
+// SPI_IOC_MESSAGE(1), SPI_IOC_WR_MODE and SPI_IOC_WR_MAX_SPEED_HZ from linux/spi/spidev.h
+ioctl!(write_ptr spi_message_1 with b'k', 0; SpiIocTransfer);
+ioctl!(write_ptr spi_write_mode with b'k', 1; u8);
+ioctl!(write_ptr spi_write_max_speed_hz with b'k', 4; u32);
+
+/// A bus that exchanges bytes with SPI devices
+pub trait SpiBus {
+    /// Perform a full-duplex transfer while asserting chip select
+    ///
+    /// `tx` and `rx` must be of the same length.
+    fn transfer(&mut self, tx: &[u8], rx: &mut [u8]) -> io::Result<()>;
+}
+
+/// SPI bus accessed through a Linux `/dev/spidevB.C` device
+///
+/// A spidev device represents a single chip select line. Every transfer is submitted as a single
+/// message, so multiple handles to the same device can be used concurrently.
+#[derive(Debug)]
+pub struct LinuxSpiBus {
+    fp: fs::File,
+}
+
+impl LinuxSpiBus {
+    /// Open `/dev/spidev<bus>.<chip_select>`
+    #[inline]
+    pub fn open(bus: u8, chip_select: u8) -> io::Result<LinuxSpiBus> {
+        Self::open_path(format!("/dev/spidev{}.{}", bus, chip_select))
+    }
+
+    /// Open a spidev device by path
+    pub fn open_path<P: AsRef<path::Path>>(path: P) -> io::Result<LinuxSpiBus> {
+        Ok(LinuxSpiBus {
+            fp: fs::OpenOptions::new().read(true).write(true).open(path)?,
+        })
+    }
+
+    /// Set the SPI mode (0 to 3)
+    pub fn set_mode(&mut self, mode: u8) -> io::Result<()> {
+        unsafe { spi_write_mode(self.fp.as_raw_fd(), &mode) }.map_err(nix_to_io)?;
+        Ok(())
+    }
+
+    /// Set the maximum clock frequency
+    pub fn set_max_speed_hz(&mut self, speed_hz: u32) -> io::Result<()> {
+        unsafe { spi_write_max_speed_hz(self.fp.as_raw_fd(), &speed_hz) }.map_err(nix_to_io)?;
+        Ok(())
+    }
+}
+
+impl SpiBus for LinuxSpiBus {
+    fn transfer(&mut self, tx: &[u8], rx: &mut [u8]) -> io::Result<()> {
+        if tx.len() != rx.len() {
+            return Err(io::Error::from_raw_os_error(libc::EINVAL));
+        }
+
+        let xfer = SpiIocTransfer {
+            tx_buf: tx.as_ptr() as u64,
+            rx_buf: rx.as_mut_ptr() as u64,
+            len: tx.len() as u32,
+            ..Default::default()
+        };
+        unsafe { spi_message_1(self.fp.as_raw_fd(), &xfer) }.map_err(nix_to_io)?;
+        Ok(())
+    }
+}
+
+/// A device model attached to a `FakeSpiBus`
+pub trait FakeSpiDevice {
+    /// Take part in a transfer
+    ///
+    /// `rx` holds the bytes clocked back to the host. Devices that are not addressed by the
+    /// transfer must leave it untouched.
+    fn transfer(&mut self, tx: &[u8], rx: &mut [u8]);
+}
+
+struct FakeSpiState {
+    devices: Vec<Box<dyn FakeSpiDevice + Send>>,
+    transfers: usize,
+}
+
+/// In-memory SPI bus for tests
+///
+/// All attached devices share a single chip select and see every transfer. Clones share the same
+/// devices.
+#[derive(Clone)]
+pub struct FakeSpiBus {
+    state: sync::Arc<sync::Mutex<FakeSpiState>>,
+}
+
+impl FakeSpiBus {
+    /// Create a new bus without any devices attached
+    pub fn new() -> FakeSpiBus {
+        FakeSpiBus {
+            state: sync::Arc::new(sync::Mutex::new(FakeSpiState {
+                devices: Vec::new(),
+                transfers: 0,
+            })),
+        }
+    }
+
+    #[inline]
+    fn state(&self) -> sync::MutexGuard<'_, FakeSpiState> {
+        self.state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// Attach a device to the bus
+    pub fn attach<D>(&self, device: D)
+    where
+        D: FakeSpiDevice + Send + 'static,
+    {
+        self.state().devices.push(Box::new(device));
+    }
+
+    /// The number of transfers performed on the bus so far
+    #[inline]
+    pub fn transfer_count(&self) -> usize {
+        self.state().transfers
+    }
+}
+
+impl Default for FakeSpiBus {
+    #[inline]
+    fn default() -> FakeSpiBus {
+        FakeSpiBus::new()
+    }
+}
+
+impl SpiBus for FakeSpiBus {
+    fn transfer(&mut self, tx: &[u8], rx: &mut [u8]) -> io::Result<()> {
+        if tx.len() != rx.len() {
+            return Err(io::Error::from_raw_os_error(libc::EINVAL));
+        }
+
+        let mut state = self.state();
+        state.transfers += 1;
+        for b in rx.iter_mut() {
+            *b = 0;
+        }
+        for dev in &mut state.devices {
+            dev.transfer(tx, rx);
+        }
+        Ok(())
+    }
+}
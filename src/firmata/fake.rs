@@ -0,0 +1,247 @@
+//! A stand-in for a board running StandardFirmata
+//!
+//! `FakeBoard` answers version and firmware queries, sets pin modes, drives outputs and reports
+//! ports like StandardFirmata does, including switching inputs to `InputPullup` when a port
+//! message writes 1 to them. Inputs are driven from tests with `FakeBoard::set_input`.
+//! `FakeBoard::pty` runs the board at the master end of a pseudo-terminal, so the terminal
+//! handling of `Firmata::from_tty` is exercised as well.
+
+use std::{fmt, fs, io, ptr, sync, thread};
+use std::io::{Read, Write};
+use std::os::unix::io::FromRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use nix::libc;
+use super::super::GpioValue;
+use super::{encode_name, make_raw, Message, Mode, Parser, PORTS, REPORT_FIRMWARE};
+
+/// Protocol version reported by the board
+const VERSION: (u8, u8) = (2, 5);
+
+/// Firmware name reported by the board
+const FIRMWARE: &str = "StandardFirmata.ino";
+
+const PINS: usize = PORTS * 8;
+
+struct State {
+    modes: [Option<Mode>; PINS],
+    /// Levels applied to the pins from outside
+    external: [Option<bool>; PINS],
+    outputs: [u8; PORTS],
+    reporting: [bool; PORTS],
+    /// The values last reported for each port
+    reported: [Option<u8>; PORTS],
+    writer: Box<dyn Write + Send>,
+}
+
+impl State {
+    fn new(writer: Box<dyn Write + Send>) -> State {
+        State {
+            modes: [None; PINS],
+            external: [None; PINS],
+            outputs: [0; PORTS],
+            reporting: [false; PORTS],
+            reported: [None; PORTS],
+            writer,
+        }
+    }
+
+    fn send(&mut self, message: &Message) {
+        // a host that went away is no error for the board
+        let _ = self.writer.write_all(&message.encode());
+        let _ = self.writer.flush();
+    }
+
+    fn announce(&mut self) {
+        self.send(&Message::Version(VERSION.0, VERSION.1));
+        let mut firmware = vec![REPORT_FIRMWARE, VERSION.0, VERSION.1];
+        firmware.extend(encode_name(FIRMWARE));
+        self.send(&Message::Sysex(firmware));
+    }
+
+    /// The values of the inputs of `port`, other pins read as 0
+    fn inputs(&self, port: usize) -> u8 {
+        (0..8).fold(0, |values, bit| {
+            let pin = port * 8 + bit;
+            let level = match self.modes[pin] {
+                Some(Mode::Input) => self.external[pin].unwrap_or(false),
+                Some(Mode::InputPullup) => self.external[pin].unwrap_or(true),
+                _ => false,
+            };
+            values | (u8::from(level) << bit)
+        })
+    }
+
+    /// Report `port` if it is reported and its inputs changed or `force` is set
+    fn check(&mut self, port: usize, force: bool) {
+        let values = self.inputs(port);
+        if self.reporting[port] && (force || self.reported[port] != Some(values)) {
+            self.reported[port] = Some(values);
+            self.send(&Message::Digital {
+                port: port as u8,
+                values,
+            });
+        }
+    }
+
+    fn write(&mut self, pin: usize, value: bool) {
+        if self.modes[pin] == Some(Mode::Output) {
+            let mask = 1 << (pin % 8);
+            if value {
+                self.outputs[pin / 8] |= mask;
+            } else {
+                self.outputs[pin / 8] &= !mask;
+            }
+        }
+    }
+
+    fn handle(&mut self, message: Message) {
+        match message {
+            Message::QueryVersion => self.send(&Message::Version(VERSION.0, VERSION.1)),
+            Message::Sysex(ref data) if data[..] == [REPORT_FIRMWARE] => self.announce(),
+            Message::PinMode { pin, mode } => {
+                let pin = usize::from(pin);
+                self.modes[pin] = Mode::from_raw(mode);
+                self.check(pin / 8, false);
+            }
+            Message::ReportDigital { port, enable } => {
+                let port = usize::from(port);
+                self.reporting[port] = enable;
+                if enable {
+                    self.check(port, true);
+                }
+            }
+            Message::Digital { port, values } => {
+                let port = usize::from(port);
+                for bit in 0..8 {
+                    let pin = port * 8 + bit;
+                    let value = values & (1 << bit) != 0;
+                    // like StandardFirmata 2.5, which enables the pull-up of inputs written 1
+                    if value && self.modes[pin] == Some(Mode::Input) {
+                        self.modes[pin] = Some(Mode::InputPullup);
+                    }
+                    self.write(pin, value);
+                }
+                self.check(port, false);
+            }
+            Message::SetDigital { pin, value } => self.write(usize::from(pin), value),
+            Message::Reset => {
+                let writer = std::mem::replace(&mut self.writer, Box::new(io::sink()));
+                *self = State::new(writer);
+                self.announce();
+            }
+            _ => (),
+        }
+    }
+}
+
+#[inline]
+fn lock(state: &sync::Mutex<State>) -> sync::MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+}
+
+/// A fake board, answering the host until the stream ends
+///
+/// Like StandardFirmata, the board announces its version and firmware when started.
+pub struct FakeBoard {
+    state: sync::Arc<sync::Mutex<State>>,
+    closed: sync::Arc<AtomicBool>,
+}
+
+impl fmt::Debug for FakeBoard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FakeBoard").finish()
+    }
+}
+
+impl FakeBoard {
+    /// Start a board receiving from `reader` and sending to `writer`
+    pub fn new<R, W>(mut reader: R, writer: W) -> FakeBoard
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let mut state = State::new(Box::new(writer));
+        state.announce();
+        let board = FakeBoard {
+            state: sync::Arc::new(sync::Mutex::new(state)),
+            closed: sync::Arc::new(AtomicBool::new(false)),
+        };
+
+        let state = board.state.clone();
+        let closed = board.closed.clone();
+        thread::spawn(move || {
+            let mut parser = Parser::from_host();
+            let mut buffer = [0; 256];
+            while !closed.load(Ordering::SeqCst) {
+                let count = match reader.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(count) => count,
+                };
+                let mut state = lock(&state);
+                for &byte in &buffer[..count] {
+                    if let Some(message) = parser.push(byte) {
+                        state.handle(message);
+                    }
+                }
+            }
+        });
+        board
+    }
+
+    /// Start a board at the master end of a new pseudo-terminal, returning the board and the
+    /// terminal to pass to `Firmata::from_tty`
+    pub fn pty() -> io::Result<(FakeBoard, fs::File)> {
+        let (mut master, mut slave) = (0, 0);
+        let result = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                ptr::null_mut(),
+                ptr::null(),
+                ptr::null(),
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let master = unsafe { fs::File::from_raw_fd(master) };
+        let tty = unsafe { fs::File::from_raw_fd(slave) };
+        // a terminal echoing the announcement would make the board answer its own messages
+        make_raw(&tty)?;
+        Ok((FakeBoard::new(master.try_clone()?, master), tty))
+    }
+
+    /// Apply `value` to `pin` from outside, as a button or sensor would
+    ///
+    /// Inputs with the pull-up enabled read high until a value is applied.
+    ///
+    /// # Panics
+    ///
+    /// If `pin` is not 0-127.
+    pub fn set_input<V: Into<GpioValue>>(&self, pin: u8, value: V) {
+        let pin = usize::from(pin);
+        assert!(pin < PINS, "pin {} out of range", pin);
+        let mut state = lock(&self.state);
+        state.external[pin] = Some(value.into() == GpioValue::High);
+        state.check(pin / 8, false);
+    }
+
+    /// The value `pin` is driven with, low unless it is an output that was set high
+    pub fn level(&self, pin: u8) -> GpioValue {
+        let pin = usize::from(pin);
+        let state = lock(&self.state);
+        GpioValue::from(state.outputs[pin / 8] & (1 << (pin % 8)) != 0)
+    }
+
+    /// The mode of `pin`, unless it was not set or is not used for GPIO
+    pub fn mode(&self, pin: u8) -> Option<Mode> {
+        lock(&self.state).modes[usize::from(pin)]
+    }
+}
+
+impl Drop for FakeBoard {
+    fn drop(&mut self) {
+        // the board stops after its current read
+        self.closed.store(true, Ordering::SeqCst);
+    }
+}
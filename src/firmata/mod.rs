@@ -0,0 +1,811 @@
+//! GPIO of a microcontroller running Firmata
+//!
+//! [Firmata](https://github.com/firmata/protocol) turns a microcontroller, typically an Arduino
+//! running StandardFirmata, into a GPIO adapter controlled over a serial line. `Firmata` speaks
+//! the protocol over any byte stream and hands out `FirmataGpioIn` and `FirmataGpioOut` pins.
+//!
+//! The board reports the inputs of a port of 8 pins whenever one of them changes, once reporting
+//! of the port is enabled. Inputs are therefore read from the last report instead of asking the
+//! board, and changes are turned into edges, which are received through a `FirmataEdgeIter`.
+//!
+//! `fake::FakeBoard` answers like StandardFirmata does, optionally at the other end of a
+//! pseudo-terminal, so code using a board can be tested without one.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use gpio::{GpioEdge, GpioIn, GpioOut, GpioValue};
+//! use gpio::firmata::{Firmata, FirmataEdgeIter, Mode};
+//! use gpio::firmata::fake::FakeBoard;
+//! use std::time::Duration;
+//!
+//! // the fake board listens on a pseudo-terminal, like an Arduino on /dev/ttyACM0
+//! let (board, tty) = FakeBoard::pty().unwrap();
+//! let firmata = Firmata::from_tty(tty).unwrap();
+//! assert_eq!(firmata.wait_ready(Duration::from_secs(1)).unwrap(), (2, 5));
+//!
+//! let mut led = firmata.output(13).unwrap();
+//! led.set_high().unwrap();
+//!
+//! board.set_input(2, true);
+//! let mut button = firmata.input(2).unwrap();
+//! assert_eq!(button.read_value().unwrap(), GpioValue::High);
+//! assert_eq!(board.mode(13), Some(Mode::Output));
+//!
+//! button.set_edge(GpioEdge::Falling).unwrap();
+//! let mut edges = FirmataEdgeIter::new().unwrap();
+//! edges.timeout_ms(1000).add(&button).unwrap();
+//! board.set_input(2, false);
+//! assert_eq!(edges.next().unwrap().unwrap().pin(), 2);
+//! assert_eq!(board.level(13), GpioValue::High);
+//! ```
+
+use std::{fmt, fs, io, mem, path, sync, thread, time};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use nix::libc;
+use super::{GpioEdge, GpioIn, GpioOut, GpioValue, Pull};
+use clock::{Clock, Notifier, SystemClock};
+
+pub mod fake;
+
+const DIGITAL_MESSAGE: u8 = 0x90;
+const ANALOG_MESSAGE: u8 = 0xe0;
+const REPORT_ANALOG: u8 = 0xc0;
+const REPORT_DIGITAL: u8 = 0xd0;
+const START_SYSEX: u8 = 0xf0;
+const SET_PIN_MODE: u8 = 0xf4;
+const SET_DIGITAL_PIN_VALUE: u8 = 0xf5;
+const END_SYSEX: u8 = 0xf7;
+const REPORT_VERSION: u8 = 0xf9;
+const SYSTEM_RESET: u8 = 0xff;
+
+/// Sysex command reporting the firmware name and version
+const REPORT_FIRMWARE: u8 = 0x79;
+
+/// Pin numbers are 7 bits, making 16 ports of 8 pins
+const PORTS: usize = 16;
+
+/// How long the board may take to report a port after reporting was enabled
+const REPORT_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+/// Edges not taken by an edge iterator are dropped beyond this number, oldest first
+const MAX_QUEUED_EVENTS: usize = 1024;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum FirmataError {
+        Io(err: io::Error) {
+            from()
+            description("io error")
+            display("I/O error: {}", err)
+            cause(err)
+        }
+        /// Firmata addresses pins with 7 bits
+        InvalidPin(pin: u8) {
+            description("pin number out of range for Firmata")
+            display("pin number {} is out of range for Firmata", pin)
+        }
+        /// The board cannot do what was asked for
+        Unsupported(what: &'static str) {
+            description("not supported by Firmata")
+            display("{} is not supported by Firmata", what)
+        }
+        /// The board did not answer in time
+        Timeout {
+            description("timed out waiting for the board")
+        }
+        /// The stream to the board ended
+        Disconnected {
+            description("disconnected from the board")
+        }
+    }
+}
+
+pub type FirmataResult<T> = Result<T, FirmataError>;
+
+/// The mode of a pin, as far as it is used for GPIO
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Mode {
+    Input,
+    Output,
+    /// An input with the internal pull-up resistor enabled
+    InputPullup,
+}
+
+impl Mode {
+    fn to_raw(self) -> u8 {
+        match self {
+            Mode::Input => 0x00,
+            Mode::Output => 0x01,
+            Mode::InputPullup => 0x0b,
+        }
+    }
+
+    fn from_raw(raw: u8) -> Option<Mode> {
+        match raw {
+            0x00 => Some(Mode::Input),
+            0x01 => Some(Mode::Output),
+            0x0b => Some(Mode::InputPullup),
+            _ => None,
+        }
+    }
+}
+
+/// A message in either direction
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Message {
+    Digital { port: u8, values: u8 },
+    ReportDigital { port: u8, enable: bool },
+    PinMode { pin: u8, mode: u8 },
+    SetDigital { pin: u8, value: bool },
+    Version(u8, u8),
+    QueryVersion,
+    Sysex(Vec<u8>),
+    Reset,
+    /// Analog messages and other commands that are not used for GPIO
+    Other,
+}
+
+impl Message {
+    fn encode(&self) -> Vec<u8> {
+        match *self {
+            Message::Digital { port, values } => {
+                vec![DIGITAL_MESSAGE | port, values & 0x7f, values >> 7]
+            }
+            Message::ReportDigital { port, enable } => vec![REPORT_DIGITAL | port, enable as u8],
+            Message::PinMode { pin, mode } => vec![SET_PIN_MODE, pin, mode],
+            Message::SetDigital { pin, value } => vec![SET_DIGITAL_PIN_VALUE, pin, value as u8],
+            Message::Version(major, minor) => vec![REPORT_VERSION, major, minor],
+            Message::QueryVersion => vec![REPORT_VERSION],
+            Message::Sysex(ref data) => {
+                let mut bytes = vec![START_SYSEX];
+                bytes.extend_from_slice(data);
+                bytes.push(END_SYSEX);
+                bytes
+            }
+            Message::Reset => vec![SYSTEM_RESET],
+            Message::Other => Vec::new(),
+        }
+    }
+}
+
+/// Splits a byte stream into messages
+///
+/// Commands have the high bit set, data bytes do not. A command interrupting an incomplete
+/// message discards it, as happens when connecting in the middle of a message.
+#[derive(Debug, Default)]
+struct Parser {
+    /// Whether the messages are sent by the host, which queries the version without data
+    from_host: bool,
+    command: Option<u8>,
+    data: Vec<u8>,
+}
+
+impl Parser {
+    /// A parser for the messages a host sends to a board
+    fn from_host() -> Parser {
+        Parser {
+            from_host: true,
+            ..Default::default()
+        }
+    }
+
+    fn push(&mut self, byte: u8) -> Option<Message> {
+        if byte & 0x80 != 0 {
+            if byte == END_SYSEX && self.command == Some(START_SYSEX) {
+                self.command = None;
+                return Some(Message::Sysex(mem::take(&mut self.data)));
+            }
+            self.command = Some(byte);
+            self.data.clear();
+        } else if self.command.is_some() {
+            self.data.push(byte);
+        } else {
+            return None;
+        }
+
+        let command = self.command?;
+        let length = match command {
+            START_SYSEX => return None,
+            REPORT_VERSION if self.from_host => 0,
+            SET_PIN_MODE | SET_DIGITAL_PIN_VALUE | REPORT_VERSION => 2,
+            _ if command < 0xf0 => match command & 0xf0 {
+                DIGITAL_MESSAGE | ANALOG_MESSAGE => 2,
+                REPORT_ANALOG | REPORT_DIGITAL => 1,
+                _ => 0,
+            },
+            _ => 0,
+        };
+        if self.data.len() < length {
+            return None;
+        }
+
+        self.command = None;
+        let data = mem::take(&mut self.data);
+        Some(match command {
+            SYSTEM_RESET => Message::Reset,
+            SET_PIN_MODE => Message::PinMode {
+                pin: data[0],
+                mode: data[1],
+            },
+            SET_DIGITAL_PIN_VALUE => Message::SetDigital {
+                pin: data[0],
+                value: data[1] != 0,
+            },
+            REPORT_VERSION if self.from_host => Message::QueryVersion,
+            REPORT_VERSION => Message::Version(data[0], data[1]),
+            _ if command & 0xf0 == DIGITAL_MESSAGE => Message::Digital {
+                port: command & 0x0f,
+                values: data[0] | (data[1] << 7),
+            },
+            _ if command & 0xf0 == REPORT_DIGITAL => Message::ReportDigital {
+                port: command & 0x0f,
+                enable: data[0] != 0,
+            },
+            _ => Message::Other,
+        })
+    }
+}
+
+/// Decode the name of a firmware report, sent as pairs of 7-bit bytes
+fn decode_name(data: &[u8]) -> String {
+    data.chunks(2)
+        .map(|pair| (pair[0] | pair.get(1).map_or(0, |msb| msb << 7)) as char)
+        .collect()
+}
+
+fn encode_name(name: &str) -> Vec<u8> {
+    name.bytes().flat_map(|byte| vec![byte & 0x7f, byte >> 7]).collect()
+}
+
+/// What is known about the board
+#[derive(Debug, Default)]
+struct Board {
+    version: Option<(u8, u8)>,
+    firmware: Option<(u8, u8, String)>,
+    modes: Vec<Option<Mode>>,
+    /// The last reported values of each port's inputs
+    inputs: [u8; PORTS],
+    /// Inputs whose value was reported, changes of other pins are no edges
+    known: [u8; PORTS],
+    /// Values last written to each port's outputs
+    outputs: [u8; PORTS],
+    /// Number of reports received for each port
+    reports: [u64; PORTS],
+    events: VecDeque<(u8, GpioValue)>,
+    disconnected: bool,
+}
+
+impl Board {
+    fn mode(&self, pin: u8) -> Option<Mode> {
+        self.modes.get(usize::from(pin)).cloned().unwrap_or(None)
+    }
+
+    fn is_input(&self, pin: u8) -> bool {
+        matches!(self.mode(pin), Some(Mode::Input) | Some(Mode::InputPullup))
+    }
+
+    fn handle(&mut self, message: Message) {
+        match message {
+            Message::Digital { port, values } => {
+                let index = usize::from(port);
+                for bit in 0..8 {
+                    let pin = port * 8 + bit;
+                    let mask = 1 << bit;
+                    if !self.is_input(pin) {
+                        continue;
+                    }
+                    let changed = (self.inputs[index] ^ values) & mask != 0;
+                    if changed && self.known[index] & mask != 0 {
+                        if self.events.len() == MAX_QUEUED_EVENTS {
+                            self.events.pop_front();
+                        }
+                        self.events
+                            .push_back((pin, GpioValue::from(values & mask != 0)));
+                    }
+                    self.known[index] |= mask;
+                }
+                self.inputs[index] = values;
+                self.reports[index] += 1;
+            }
+            Message::Version(major, minor) => self.version = Some((major, minor)),
+            Message::Sysex(ref data) if data.len() >= 3 && data[0] == REPORT_FIRMWARE => {
+                self.firmware = Some((data[1], data[2], decode_name(&data[3..])));
+            }
+            _ => (),
+        }
+    }
+}
+
+/// State shared with the reading thread
+#[derive(Default)]
+struct Shared {
+    board: sync::Mutex<Board>,
+    notifier: Notifier,
+    closed: AtomicBool,
+}
+
+impl Shared {
+    #[inline]
+    fn board(&self) -> sync::MutexGuard<'_, Board> {
+        self.board.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// Block until `done` returns a result or `timeout` passed
+    fn wait<T, F>(&self, timeout: Option<time::Duration>, mut done: F) -> FirmataResult<T>
+    where
+        F: FnMut(&mut Board) -> Option<T>,
+    {
+        let start = time::Instant::now();
+        loop {
+            let generation = self.notifier.generation();
+            {
+                let mut board = self.board();
+                if let Some(result) = done(&mut board) {
+                    return Ok(result);
+                }
+                if board.disconnected {
+                    return Err(FirmataError::Disconnected);
+                }
+            }
+            let wait = match timeout {
+                Some(timeout) => match timeout.checked_sub(start.elapsed()) {
+                    Some(remaining) if remaining > time::Duration::from_secs(0) => Some(remaining),
+                    _ => return Err(FirmataError::Timeout),
+                },
+                None => None,
+            };
+            SystemClock.wait(&self.notifier, generation, wait);
+        }
+    }
+}
+
+/// Parse messages from `reader` until it ends
+///
+/// With `eof_is_timeout`, reading nothing only means that nothing arrived for a while, as on
+/// terminals configured by `Firmata::from_tty`.
+fn read<R: Read>(mut reader: R, shared: &Shared, eof_is_timeout: bool) {
+    let mut parser = Parser::default();
+    let mut buffer = [0; 256];
+    while !shared.closed.load(Ordering::SeqCst) {
+        let count = match reader.read(&mut buffer) {
+            Ok(0) if eof_is_timeout => continue,
+            Ok(0) => break,
+            Ok(count) => count,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+        let mut board = shared.board();
+        for &byte in &buffer[..count] {
+            if let Some(message) = parser.push(byte) {
+                board.handle(message);
+            }
+        }
+        drop(board);
+        shared.notifier.notify();
+    }
+    shared.board().disconnected = true;
+    shared.notifier.notify();
+}
+
+/// Configure a terminal for Firmata: raw bytes at 57600 baud, reads time out after 100 ms
+fn make_raw(tty: &fs::File) -> io::Result<()> {
+    let fd = tty.as_raw_fd();
+    unsafe {
+        let mut termios: libc::termios = mem::zeroed();
+        if libc::tcgetattr(fd, &mut termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        libc::cfmakeraw(&mut termios);
+        libc::cfsetspeed(&mut termios, libc::B57600);
+        termios.c_cc[libc::VMIN] = 0;
+        termios.c_cc[libc::VTIME] = 1;
+        if libc::tcsetattr(fd, libc::TCSANOW, &termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+struct Connection {
+    writer: sync::Mutex<Box<dyn Write + Send>>,
+    shared: sync::Arc<Shared>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // the reading thread stops after its current read
+        self.shared.closed.store(true, Ordering::SeqCst);
+    }
+}
+
+#[inline]
+fn port_of(pin: u8) -> FirmataResult<(u8, u8)> {
+    if pin < 0x80 {
+        Ok((pin / 8, 1 << (pin % 8)))
+    } else {
+        Err(FirmataError::InvalidPin(pin))
+    }
+}
+
+/// A board running Firmata
+///
+/// Clones share the connection, which is closed when the last clone and all pins obtained from
+/// it are dropped.
+#[derive(Clone)]
+pub struct Firmata {
+    conn: sync::Arc<Connection>,
+}
+
+impl fmt::Debug for Firmata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Firmata")
+            .field("version", &self.version())
+            .finish()
+    }
+}
+
+impl Firmata {
+    /// Talk to a board receiving from `reader` and sending to `writer`
+    ///
+    /// These are usually two handles of the same stream. A thread reads messages from `reader`
+    /// until it ends. The board is asked for its version right away, see `wait_ready`.
+    pub fn new<R, W>(reader: R, writer: W) -> FirmataResult<Firmata>
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        Firmata::start(reader, writer, false)
+    }
+
+    /// Talk to a board attached to the serial terminal `tty`, configuring it for Firmata
+    pub fn from_tty(tty: fs::File) -> FirmataResult<Firmata> {
+        make_raw(&tty)?;
+        Firmata::start(tty.try_clone()?, tty, true)
+    }
+
+    /// Open the serial terminal at `path`, e.g. `/dev/ttyACM0`, see `from_tty`
+    pub fn open<P: AsRef<path::Path>>(path: P) -> FirmataResult<Firmata> {
+        let tty = fs::OpenOptions::new().read(true).write(true).open(path)?;
+        Firmata::from_tty(tty)
+    }
+
+    fn start<R, W>(reader: R, writer: W, eof_is_timeout: bool) -> FirmataResult<Firmata>
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let shared = sync::Arc::new(Shared::default());
+        {
+            let shared = shared.clone();
+            thread::spawn(move || read(reader, &shared, eof_is_timeout));
+        }
+        let firmata = Firmata {
+            conn: sync::Arc::new(Connection {
+                writer: sync::Mutex::new(Box::new(writer)),
+                shared,
+            }),
+        };
+        firmata.send(&Message::QueryVersion.encode())?;
+        firmata.send(&Message::Sysex(vec![REPORT_FIRMWARE]).encode())?;
+        Ok(firmata)
+    }
+
+    fn send(&self, data: &[u8]) -> FirmataResult<()> {
+        let mut writer = self
+            .conn
+            .writer
+            .lock()
+            .unwrap_or_else(sync::PoisonError::into_inner);
+        writer.write_all(data)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    #[inline]
+    fn shared(&self) -> &Shared {
+        &self.conn.shared
+    }
+
+    /// Wait until the board reported its protocol version, returning it
+    ///
+    /// Boards like the Arduino Uno restart when the serial port is opened and only answer
+    /// after their boot loader finished, which can take two seconds.
+    pub fn wait_ready(&self, timeout: time::Duration) -> FirmataResult<(u8, u8)> {
+        self.shared().wait(Some(timeout), |board| board.version)
+    }
+
+    /// The protocol version reported by the board
+    #[inline]
+    pub fn version(&self) -> Option<(u8, u8)> {
+        self.shared().board().version
+    }
+
+    /// The name and version of the firmware reported by the board
+    #[inline]
+    pub fn firmware(&self) -> Option<(u8, u8, String)> {
+        self.shared().board().firmware.clone()
+    }
+
+    /// Reset the board to its initial state, which stops reporting all ports
+    pub fn reset(&self) -> FirmataResult<()> {
+        self.send(&Message::Reset.encode())?;
+        let mut board = self.shared().board();
+        let version = board.version;
+        let firmware = board.firmware.take();
+        *board = Board {
+            version,
+            firmware,
+            ..Board::default()
+        };
+        Ok(())
+    }
+
+    /// Set the mode of `pin`
+    ///
+    /// Inputs are reported from now on and their value is known once this returns. The value
+    /// last written to a former output is forgotten, since writing its port would otherwise
+    /// write a 1 to the input, which enables its pull-up with StandardFirmata 2.5 and later:
+    ///
+    /// ```rust
+    /// use gpio::GpioOut;
+    /// use gpio::firmata::{Firmata, Mode};
+    /// use gpio::firmata::fake::FakeBoard;
+    /// use std::time::Duration;
+    ///
+    /// let (board, tty) = FakeBoard::pty().unwrap();
+    /// let firmata = Firmata::from_tty(tty).unwrap();
+    /// firmata.wait_ready(Duration::from_secs(1)).unwrap();
+    ///
+    /// firmata.output(12).unwrap().set_high().unwrap();
+    /// let _sensor = firmata.input(12).unwrap();
+    /// firmata.output(13).unwrap().set_high().unwrap();
+    /// // the board handles messages in order
+    /// while board.level(13) != gpio::GpioValue::High {
+    ///     std::thread::sleep(Duration::from_millis(1));
+    /// }
+    /// assert_eq!(board.mode(12), Some(Mode::Input));
+    /// ```
+    pub fn set_mode(&self, pin: u8, mode: Mode) -> FirmataResult<()> {
+        let (port, mask) = port_of(pin)?;
+        {
+            // updated first, so a report caused by the new mode is no edge
+            let mut board = self.shared().board();
+            let index = usize::from(pin);
+            if board.modes.len() <= index {
+                board.modes.resize(index + 1, None);
+            }
+            board.modes[index] = Some(mode);
+            board.known[usize::from(port)] &= !mask;
+            if mode != Mode::Output {
+                board.outputs[usize::from(port)] &= !mask;
+            }
+        }
+        self.send(&Message::PinMode { pin, mode: mode.to_raw() }.encode())?;
+        if mode != Mode::Output {
+            self.refresh(port)?;
+        }
+        Ok(())
+    }
+
+    /// Enable reporting of `port` and wait for the report that follows
+    fn refresh(&self, port: u8) -> FirmataResult<()> {
+        let reports = self.shared().board().reports[usize::from(port)];
+        self.send(&Message::ReportDigital { port, enable: true }.encode())?;
+        self.shared().wait(Some(REPORT_TIMEOUT), |board| {
+            if board.reports[usize::from(port)] > reports {
+                Some(())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The mode `pin` was set to
+    #[inline]
+    pub fn mode(&self, pin: u8) -> Option<Mode> {
+        self.shared().board().mode(pin)
+    }
+
+    /// The last reported value of an input or the value last written to an output
+    pub fn read(&self, pin: u8) -> FirmataResult<GpioValue> {
+        let (port, mask) = port_of(pin)?;
+        let board = self.shared().board();
+        let values = if board.is_input(pin) {
+            board.inputs[usize::from(port)]
+        } else {
+            board.outputs[usize::from(port)]
+        };
+        Ok(GpioValue::from(values & mask != 0))
+    }
+
+    /// Drive `pin` with `value`
+    ///
+    /// The whole port is written, so this works with versions of Firmata predating the command
+    /// for setting a single pin.
+    pub fn write(&self, pin: u8, value: GpioValue) -> FirmataResult<()> {
+        let (port, mask) = port_of(pin)?;
+        let values = {
+            let mut board = self.shared().board();
+            let values = &mut board.outputs[usize::from(port)];
+            match value {
+                GpioValue::Low => *values &= !mask,
+                GpioValue::High => *values |= mask,
+            }
+            *values
+        };
+        self.send(&Message::Digital { port, values }.encode())
+    }
+
+    /// Configure `pin` as an input and return a handle to it
+    pub fn input(&self, pin: u8) -> FirmataResult<FirmataGpioIn> {
+        self.set_mode(pin, Mode::Input)?;
+        Ok(FirmataGpioIn {
+            firmata: self.clone(),
+            pin,
+            edge: GpioEdge::None,
+        })
+    }
+
+    /// Configure `pin` as an output and return a handle to it
+    pub fn output(&self, pin: u8) -> FirmataResult<FirmataGpioOut> {
+        self.set_mode(pin, Mode::Output)?;
+        Ok(FirmataGpioOut {
+            firmata: self.clone(),
+            pin,
+        })
+    }
+}
+
+/// Input pin of a Firmata board
+///
+/// The edge set with `set_edge` is only used by `FirmataEdgeIter`, the board always reports
+/// changes.
+#[derive(Debug, Clone)]
+pub struct FirmataGpioIn {
+    firmata: Firmata,
+    pin: u8,
+    edge: GpioEdge,
+}
+
+impl FirmataGpioIn {
+    /// The number of the pin on the board
+    #[inline]
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    /// Enable or disable the pull-up resistor of the pin
+    ///
+    /// Firmata has no pull-down resistors.
+    pub fn set_pull(&self, pull: Pull) -> FirmataResult<()> {
+        match pull {
+            Pull::None => self.firmata.set_mode(self.pin, Mode::Input),
+            Pull::Up => self.firmata.set_mode(self.pin, Mode::InputPullup),
+            Pull::Down => Err(FirmataError::Unsupported("pull-down")),
+        }
+    }
+}
+
+impl GpioIn for FirmataGpioIn {
+    type Error = FirmataError;
+
+    #[inline]
+    fn read_value(&self) -> FirmataResult<GpioValue> {
+        self.firmata.read(self.pin)
+    }
+
+    #[inline]
+    fn set_edge(&mut self, edge: GpioEdge) -> FirmataResult<()> {
+        self.edge = edge;
+        Ok(())
+    }
+}
+
+/// Output pin of a Firmata board
+#[derive(Debug, Clone)]
+pub struct FirmataGpioOut {
+    firmata: Firmata,
+    pin: u8,
+}
+
+impl FirmataGpioOut {
+    /// The number of the pin on the board
+    #[inline]
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+}
+
+impl GpioOut for FirmataGpioOut {
+    type Error = FirmataError;
+
+    #[inline]
+    fn set_low(&mut self) -> FirmataResult<()> {
+        self.firmata.write(self.pin, GpioValue::Low)
+    }
+
+    #[inline]
+    fn set_high(&mut self) -> FirmataResult<()> {
+        self.firmata.write(self.pin, GpioValue::High)
+    }
+}
+
+/// Iterator over edges of Firmata inputs
+///
+/// All pins must belong to the same board. Edges of pins not added to any iterator are kept
+/// until the board has reported `MAX_QUEUED_EVENTS` newer ones.
+pub struct FirmataEdgeIter<'a> {
+    shared: sync::Arc<Shared>,
+    devs: Vec<&'a FirmataGpioIn>,
+    timeout: Option<time::Duration>,
+}
+
+impl<'a> fmt::Debug for FirmataEdgeIter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FirmataEdgeIter")
+            .field("devs", &self.devs)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl<'a> FirmataEdgeIter<'a> {
+    /// Create a new iterator without any pins
+    #[inline]
+    pub fn new() -> FirmataResult<FirmataEdgeIter<'a>> {
+        Ok(FirmataEdgeIter {
+            shared: Default::default(),
+            devs: Vec::new(),
+            timeout: None,
+        })
+    }
+
+    /// Wait at most `timeout_ms` for an edge on each call of `next`
+    #[inline]
+    pub fn timeout_ms(&mut self, timeout_ms: u64) -> &mut Self {
+        self.timeout = Some(time::Duration::from_millis(timeout_ms));
+        self
+    }
+
+    /// Add a pin to the iterator
+    pub fn add(&mut self, dev: &'a FirmataGpioIn) -> FirmataResult<&mut Self> {
+        let shared = &dev.firmata.conn.shared;
+        if self.devs.is_empty() {
+            self.shared = shared.clone();
+        } else if !sync::Arc::ptr_eq(&self.shared, shared) {
+            return Err(FirmataError::Unsupported("watching pins of several boards at once"));
+        }
+        self.devs.push(dev);
+        Ok(self)
+    }
+
+    /// Take the oldest queued edge of one of the pins, dropping those of unwanted edges
+    fn pop(&self, board: &mut Board) -> Option<&'a FirmataGpioIn> {
+        let mut index = 0;
+        while index < board.events.len() {
+            let (pin, value) = board.events[index];
+            if let Some(&dev) = self.devs.iter().find(|dev| dev.pin == pin) {
+                board.events.remove(index);
+                if dev.edge.matches(value) {
+                    return Some(dev);
+                }
+            } else {
+                index += 1;
+            }
+        }
+        None
+    }
+}
+
+impl<'a> Iterator for FirmataEdgeIter<'a> {
+    type Item = Result<&'a FirmataGpioIn, FirmataError>;
+
+    fn next(&mut self) -> Option<Result<&'a FirmataGpioIn, FirmataError>> {
+        let shared = self.shared.clone();
+        Some(shared.wait(self.timeout, |board| self.pop(board)))
+    }
+}
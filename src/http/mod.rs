@@ -0,0 +1,555 @@
+//! A REST gateway for pins
+//!
+//! `Gateway` serves pins over HTTP, so they can be read and set from a browser or dashboard. It
+//! is only built with the `http` feature. Every pin is a resource:
+//!
+//! * `GET /pins` lists all pins, e.g. `[{"pin":17,"direction":"in","access":"read-only",
+//!   "value":1}]`
+//! * `GET /pins/PIN` returns a single pin in the same form, outputs that were not written yet
+//!   have a `value` of `null`
+//! * `PUT /pins/PIN` sets an output to the `value` of the body, e.g. `{"value":1}`, a plain `1`
+//!   or `high` is accepted as well
+//! * `POST /pins/PIN/pulse` drives an output with `value` (default 1) for `width_ms` (default
+//!   100), then with the inverse value, responding once done
+//! * `GET /events` streams edges of the inputs as
+//!   [Server-Sent Events](https://html.spec.whatwg.org/multipage/server-sent-events.html), e.g.
+//!   `event: edge` and `data: {"pin":17,"edge":"rising","value":1}`, limited to some pins with
+//!   `?pins=17,18`
+//!
+//! Outputs are either read-only or read-write, inputs are always read-only. Writing to a
+//! read-only pin is answered with `403 Forbidden`, other errors with a `4xx` or `5xx` status and
+//! a body like `{"error":"pin 5 is not served"}`. Edges are detected by polling the inputs.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use std::io::{Read, Write};
+//! use std::net::TcpStream;
+//! use gpio::GpioValue;
+//! use gpio::dummy::{DummyGpioIn, DummyGpioOut};
+//! use gpio::http::{Access, Gateway};
+//!
+//! let button = DummyGpioIn::with_value(false);
+//! let gateway = Gateway::new()
+//!     .input(17, button.clone())
+//!     .output(18, DummyGpioOut::new(|_| ()), Access::ReadWrite)
+//!     .listen("127.0.0.1:0")
+//!     .unwrap();
+//!
+//! let request = |request: &str| {
+//!     let mut stream = TcpStream::connect(gateway.local_addr()).unwrap();
+//!     stream.write_all(request.as_bytes()).unwrap();
+//!     let mut response = String::new();
+//!     stream.read_to_string(&mut response).unwrap();
+//!     response
+//! };
+//!
+//! let response = request("PUT /pins/18 HTTP/1.1\r\nContent-Length: 11\r\n\r\n{\"value\":1}");
+//! assert!(response.starts_with("HTTP/1.1 200 OK"));
+//! assert!(request("GET /pins/18 HTTP/1.1\r\n\r\n").ends_with("\"value\":1}"));
+//! assert!(request("PUT /pins/17 HTTP/1.1\r\nContent-Length: 1\r\n\r\n1").contains("403"));
+//!
+//! // edges of the inputs as server-sent events
+//! let mut events = TcpStream::connect(gateway.local_addr()).unwrap();
+//! events.write_all(b"GET /events HTTP/1.1\r\n\r\n").unwrap();
+//! let mut head = [0; 256];
+//! let length = events.read(&mut head).unwrap();
+//! assert!(String::from_utf8_lossy(&head[..length]).contains("text/event-stream"));
+//! button.set(true);
+//! let mut event = [0; 256];
+//! let length = events.read(&mut event).unwrap();
+//! assert_eq!(
+//!     String::from_utf8_lossy(&event[..length]),
+//!     "event: edge\ndata: {\"pin\":17,\"edge\":\"rising\",\"value\":1}\n\n"
+//! );
+//! ```
+
+use std::{collections, fmt, io, net, sync, thread, time};
+use std::io::Write;
+use super::{GpioIn, GpioOut, GpioValue};
+use served::{self, Gpio, Service};
+
+mod request;
+
+use self::request::{Request, Value};
+
+/// How often inputs are read to detect edges, unless set with `Gateway::poll_interval`
+const POLL_INTERVAL: time::Duration = time::Duration::from_millis(1);
+
+/// Event streams receive a comment this often, which detects clients that went away
+const KEEP_ALIVE: time::Duration = time::Duration::from_secs(15);
+
+/// Clients not sending a complete request within this time are disconnected
+const REQUEST_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+
+/// Event streams that cannot be written to within this time are closed
+const WRITE_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+/// Pulses are limited to this width, as the request is answered only afterwards
+const MAX_PULSE: time::Duration = time::Duration::from_secs(10);
+
+/// Which requests are allowed for a pin
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Access {
+    /// The pin can only be read
+    ReadOnly,
+    /// The pin can be read and set
+    ReadWrite,
+}
+
+struct Pin {
+    pin: served::Pin,
+    access: Access,
+}
+
+/// A client receiving edges
+struct EventStream {
+    stream: net::TcpStream,
+    /// The pins the client is interested in, all if `None`
+    pins: Option<Vec<u16>>,
+}
+
+#[derive(Default)]
+struct State {
+    pins: collections::BTreeMap<u16, Pin>,
+    streams: Vec<EventStream>,
+}
+
+/// An error response
+struct Error(u16, String);
+
+type Response = Result<String, Error>;
+
+fn value_json(value: Option<GpioValue>) -> &'static str {
+    match value {
+        Some(GpioValue::Low) => "0",
+        Some(GpioValue::High) => "1",
+        None => "null",
+    }
+}
+
+/// A value of a request body, as JSON value or the plain words the CLI accepts
+fn parse_value(value: &Value) -> Option<GpioValue> {
+    match *value {
+        Value::Number(0.0) => Some(GpioValue::Low),
+        Value::Number(1.0) => Some(GpioValue::High),
+        Value::Bool(b) => Some(GpioValue::from(b)),
+        Value::String(ref s) => match s.as_str() {
+            "0" | "low" => Some(GpioValue::Low),
+            "1" | "high" => Some(GpioValue::High),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn invert(value: GpioValue) -> GpioValue {
+    match value {
+        GpioValue::Low => GpioValue::High,
+        GpioValue::High => GpioValue::Low,
+    }
+}
+
+fn bad_request(message: &str) -> Error {
+    Error(400, message.to_owned())
+}
+
+/// A gateway serving pins over HTTP
+///
+/// Pins are added with `input` and `output`, then `listen` starts serving them.
+pub struct Gateway {
+    pins: collections::BTreeMap<u16, Pin>,
+    poll_interval: time::Duration,
+}
+
+impl fmt::Debug for Gateway {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Gateway")
+            .field("pins", &self.pins.keys().collect::<Vec<_>>())
+            .field("poll_interval", &self.poll_interval)
+            .finish()
+    }
+}
+
+impl Default for Gateway {
+    fn default() -> Gateway {
+        Gateway {
+            pins: collections::BTreeMap::new(),
+            poll_interval: POLL_INTERVAL,
+        }
+    }
+}
+
+impl Gateway {
+    /// Create a gateway without any pins
+    #[inline]
+    pub fn new() -> Gateway {
+        Default::default()
+    }
+
+    /// Serve `gpio` as read-only input `pin`, replacing any pin of the same number
+    pub fn input<P>(mut self, pin: u16, gpio: P) -> Self
+    where
+        P: GpioIn + Send + 'static,
+        P::Error: fmt::Debug,
+    {
+        self.pins.insert(
+            pin,
+            Pin {
+                pin: served::Pin::input(gpio),
+                access: Access::ReadOnly,
+            },
+        );
+        self
+    }
+
+    /// Serve `gpio` as output `pin` with `access`, replacing any pin of the same number
+    ///
+    /// Outputs cannot be read back, their `value` is the one last written through the gateway.
+    /// Read-only outputs therefore only show the direction of the pin.
+    pub fn output<P>(mut self, pin: u16, gpio: P, access: Access) -> Self
+    where
+        P: GpioOut + Send + 'static,
+        P::Error: fmt::Debug,
+    {
+        self.pins.insert(
+            pin,
+            Pin {
+                pin: served::Pin::output(gpio),
+                access,
+            },
+        );
+        self
+    }
+
+    /// Read the inputs every `interval` while clients receive events
+    ///
+    /// Pulses shorter than the interval may be missed.
+    pub fn poll_interval(mut self, interval: time::Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Start serving the pins on `addr`, e.g. `0.0.0.0:8080`
+    pub fn listen<A: net::ToSocketAddrs>(self, addr: A) -> io::Result<GatewayHandle> {
+        let listener = net::TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let shared = sync::Arc::new(Shared {
+            state: sync::Mutex::new(State {
+                pins: self.pins,
+                ..Default::default()
+            }),
+        });
+
+        let service = {
+            let (serve, poll) = (shared.clone(), shared.clone());
+            let mut keep_alive = time::Instant::now();
+            Service::spawn(
+                listener,
+                move || drop(net::TcpStream::connect(local_addr)),
+                move |stream| drop(serve.serve(stream)),
+                move || {
+                    poll.poll();
+                    if keep_alive.elapsed() >= KEEP_ALIVE {
+                        poll.broadcast(None, ":\n\n");
+                        keep_alive = time::Instant::now();
+                    }
+                },
+                self.poll_interval,
+            )
+        };
+
+        Ok(GatewayHandle {
+            local_addr,
+            shared,
+            service,
+        })
+    }
+}
+
+/// State shared by the threads of a running gateway
+struct Shared {
+    state: sync::Mutex<State>,
+}
+
+impl Shared {
+    #[inline]
+    fn state(&self) -> sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// Send `message` to the event streams interested in `pin`, closing those that fail
+    fn broadcast(&self, pin: Option<u16>, message: &str) {
+        self.state().streams.retain_mut(|client| {
+            let interested = match (pin, &client.pins) {
+                (Some(pin), Some(pins)) => pins.contains(&pin),
+                _ => true,
+            };
+            !interested || client.stream.write_all(message.as_bytes()).is_ok()
+        });
+    }
+
+    /// Read the inputs and send their edges to the event streams
+    fn poll(&self) {
+        let mut edges = Vec::new();
+        {
+            let mut state = self.state();
+            if state.streams.is_empty() {
+                return;
+            }
+            for (&pin, entry) in &mut state.pins {
+                if let Some(value) = entry.pin.poll() {
+                    edges.push((pin, value));
+                }
+            }
+        }
+        for (pin, value) in edges {
+            let edge = match value {
+                GpioValue::Low => "falling",
+                GpioValue::High => "rising",
+            };
+            let message = format!(
+                "event: edge\ndata: {{\"pin\":{},\"edge\":\"{}\",\"value\":{}}}\n\n",
+                pin,
+                edge,
+                value_json(Some(value))
+            );
+            self.broadcast(Some(pin), &message);
+        }
+    }
+
+    fn pin_json(pin: u16, entry: &Pin, value: Option<GpioValue>) -> String {
+        let (direction, access) = match (&entry.pin.gpio, entry.access) {
+            (&Gpio::Input(_), _) => ("in", "read-only"),
+            (&Gpio::Output(_), Access::ReadOnly) => ("out", "read-only"),
+            (&Gpio::Output(_), Access::ReadWrite) => ("out", "read-write"),
+        };
+        format!(
+            "{{\"pin\":{},\"direction\":\"{}\",\"access\":\"{}\",\"value\":{}}}",
+            pin,
+            direction,
+            access,
+            value_json(value)
+        )
+    }
+
+    fn read(pin: u16, entry: &Pin) -> Response {
+        let value = match entry.pin.gpio {
+            Gpio::Input(ref gpio) => Some(gpio.read().map_err(|err| Error(500, err))?),
+            Gpio::Output(_) => entry.pin.value,
+        };
+        Ok(Shared::pin_json(pin, entry, value))
+    }
+
+    /// Write `value` to a read-write output
+    fn write(&self, pin: u16, value: GpioValue) -> Response {
+        let mut state = self.state();
+        let entry = state.pins.get_mut(&pin).ok_or_else(|| unknown(pin))?;
+        match (&mut entry.pin.gpio, entry.access) {
+            (&mut Gpio::Output(ref mut gpio), Access::ReadWrite) => {
+                gpio.write(value).map_err(|err| Error(500, err))?
+            }
+            _ => return Err(Error(403, format!("pin {} is read-only", pin))),
+        }
+        entry.pin.value = Some(value);
+        Ok(Shared::pin_json(pin, entry, entry.pin.value))
+    }
+
+    fn pin_of(segment: &str) -> Result<u16, Error> {
+        segment
+            .parse()
+            .map_err(|_| Error(404, format!("invalid pin {}", segment)))
+    }
+
+    /// Answer a request other than one for the event stream
+    fn respond(&self, request: &Request) -> Response {
+        let body = String::from_utf8_lossy(&request.body);
+        let object = || {
+            if body.trim().is_empty() {
+                return Ok(collections::HashMap::new());
+            }
+            request::parse_object(&body).ok_or_else(|| bad_request("invalid JSON object"))
+        };
+
+        match (request.method.as_str(), &request.segments()[..]) {
+            ("GET", ["pins"]) => {
+                let state = self.state();
+                let pins = state
+                    .pins
+                    .iter()
+                    .map(|(&pin, entry)| Shared::read(pin, entry))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(format!("[{}]", pins.join(",")))
+            }
+            ("GET", ["pins", pin]) => {
+                let pin = Shared::pin_of(pin)?;
+                let state = self.state();
+                Shared::read(pin, state.pins.get(&pin).ok_or_else(|| unknown(pin))?)
+            }
+            ("PUT", ["pins", pin]) => {
+                let pin = Shared::pin_of(pin)?;
+                let plain = Value::String(body.trim().to_owned());
+                let value = if body.trim_start().starts_with('{') {
+                    object()?.get("value").and_then(parse_value)
+                } else {
+                    parse_value(&plain)
+                };
+                self.write(pin, value.ok_or_else(|| bad_request("missing or invalid value"))?)
+            }
+            ("POST", ["pins", pin, "pulse"]) => {
+                let pin = Shared::pin_of(pin)?;
+                let object = object()?;
+                let value = match object.get("value") {
+                    Some(value) => parse_value(value).ok_or_else(|| bad_request("invalid value"))?,
+                    None => GpioValue::High,
+                };
+                let width = match object.get("width_ms") {
+                    Some(&Value::Number(ms)) if ms >= 0.0 => {
+                        time::Duration::try_from_secs_f64(ms / 1e3)
+                            .map_err(|_| bad_request("invalid width"))?
+                    }
+                    Some(_) => return Err(bad_request("invalid width")),
+                    None => time::Duration::from_millis(100),
+                };
+                if width > MAX_PULSE {
+                    return Err(bad_request("pulse too long"));
+                }
+                self.write(pin, value)?;
+                thread::sleep(width);
+                self.write(pin, invert(value))
+            }
+            (method, segments) => match allowed(segments) {
+                Some(_) => Err(Error(405, format!("{} is not supported here", method))),
+                None => Err(Error(404, format!("no resource at {}", request.path))),
+            },
+        }
+    }
+
+    /// Serve a single request, closing the connection afterwards unless it receives events
+    fn serve(&self, stream: net::TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let mut reader = io::BufReader::new(stream.try_clone()?);
+        let mut stream = stream;
+        let request = match request::read(&mut reader) {
+            Ok(Some(request)) => request,
+            Ok(None) => return Ok(()),
+            Err(err) => {
+                let body = format!("{{\"error\":{}}}", request::quote(&err.to_string()));
+                return write_response(&mut stream, 400, None, &body);
+            }
+        };
+
+        if request.method == "GET" && request.segments() == ["events"] {
+            let pins = match request.query("pins") {
+                Some(pins) => match pins.split(',').map(str::parse).collect() {
+                    Ok(pins) => Some(pins),
+                    Err(_) => {
+                        let body = "{\"error\":\"invalid pins\"}";
+                        return write_response(&mut stream, 400, None, body);
+                    }
+                },
+                None => None,
+            };
+            let mut state = self.state();
+            // edges are relative to the values before the client learns it is subscribed
+            for entry in state.pins.values_mut() {
+                if let Gpio::Input(ref gpio) = entry.pin.gpio {
+                    entry.pin.value = gpio.read().ok();
+                }
+            }
+            stream.write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                  Cache-Control: no-cache\r\n\r\n",
+            )?;
+            state.streams.push(EventStream { stream, pins });
+            return Ok(());
+        }
+
+        match self.respond(&request) {
+            Ok(body) => write_response(&mut stream, 200, None, &body),
+            Err(Error(status, message)) => {
+                let body = format!("{{\"error\":{}}}", request::quote(&message));
+                let allow = if status == 405 {
+                    allowed(&request.segments())
+                } else {
+                    None
+                };
+                write_response(&mut stream, status, allow, &body)
+            }
+        }
+    }
+}
+
+fn unknown(pin: u16) -> Error {
+    Error(404, format!("pin {} is not served", pin))
+}
+
+/// The methods supported by the resource at `segments`, `None` if there is none
+fn allowed(segments: &[&str]) -> Option<&'static str> {
+    match *segments {
+        ["pins"] | ["events"] => Some("GET"),
+        ["pins", _] => Some("GET, PUT"),
+        ["pins", _, "pulse"] => Some("POST"),
+        _ => None,
+    }
+}
+
+/// Write a JSON response, with an `Allow` header for the methods `allow` if set
+fn write_response(
+    stream: &mut net::TcpStream,
+    status: u16,
+    allow: Option<&str>,
+    body: &str,
+) -> io::Result<()> {
+    write!(stream, "HTTP/1.1 {} {}\r\n", status, request::reason(status))?;
+    if let Some(allow) = allow {
+        write!(stream, "Allow: {}\r\n", allow)?;
+    }
+    write!(
+        stream,
+        "Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// A running gateway, shut down when dropped
+pub struct GatewayHandle {
+    local_addr: net::SocketAddr,
+    shared: sync::Arc<Shared>,
+    service: Service,
+}
+
+impl fmt::Debug for GatewayHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GatewayHandle")
+            .field("local_addr", &self.local_addr)
+            .finish()
+    }
+}
+
+impl GatewayHandle {
+    /// The address the gateway listens on, useful when listening on port 0
+    #[inline]
+    pub fn local_addr(&self) -> net::SocketAddr {
+        self.local_addr
+    }
+
+    /// Close all event streams and stop serving
+    ///
+    /// The pins are dropped once requests being answered are done.
+    pub fn shutdown(self) {}
+}
+
+impl Drop for GatewayHandle {
+    fn drop(&mut self) {
+        let shared = &self.shared;
+        self.service.stop(|| {
+            for client in shared.state().streams.drain(..) {
+                let _ = client.stream.shutdown(net::Shutdown::Both);
+            }
+        });
+    }
+}
@@ -0,0 +1,209 @@
+//! Just enough HTTP and JSON for the gateway
+
+use std::{collections, io};
+use std::io::{BufRead, Read};
+
+/// Requests with longer heads or bodies are rejected
+const MAX_HEAD: usize = 8192;
+const MAX_BODY: usize = 4096;
+
+/// An HTTP request
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// The path split at slashes, without empty segments
+    pub fn segments(&self) -> Vec<&str> {
+        self.path.split('/').filter(|s| !s.is_empty()).collect()
+    }
+
+    /// The value of `name` in the query string
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query.as_ref()?.split('&').find_map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) if key == name => Some(value),
+                _ => None,
+            }
+        })
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Read a request, `None` if the connection was closed before one started
+pub fn read<R: BufRead>(reader: &mut R) -> io::Result<Option<Request>> {
+    let mut head = reader.take(MAX_HEAD as u64);
+    let mut line = String::new();
+    if head.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) if version.starts_with("HTTP/1.") => {
+            (method.to_owned(), target.to_owned())
+        }
+        _ => return Err(invalid("malformed request line")),
+    };
+
+    let mut length = 0;
+    loop {
+        line.clear();
+        if head.read_line(&mut line)? == 0 {
+            return Err(invalid("request head too long or incomplete"));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let mut header = line.splitn(2, ':');
+        let name = header.next().unwrap_or("").trim();
+        let value = header.next().unwrap_or("").trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            length = value.parse().map_err(|_| invalid("invalid content length"))?;
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            return Err(invalid("chunked requests are not supported"));
+        }
+    }
+    if length > MAX_BODY {
+        return Err(invalid("request body too large"));
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    let mut target = target.splitn(2, '?');
+    Ok(Some(Request {
+        method,
+        path: target.next().unwrap_or("").to_owned(),
+        query: target.next().map(str::to_owned),
+        body,
+    }))
+}
+
+/// The reason phrase of the status codes used by the gateway
+pub fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        500 => "Internal Server Error",
+        _ => "Unknown",
+    }
+}
+
+/// A JSON value as found in request bodies, nested values are not supported
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+/// Parse a JSON object whose values are all scalars
+pub fn parse_object(text: &str) -> Option<collections::HashMap<String, Value>> {
+    let mut chars = text.trim().chars().peekable();
+    let mut object = collections::HashMap::new();
+    let skip = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+    };
+    let string = |chars: &mut std::iter::Peekable<std::str::Chars>| -> Option<String> {
+        let mut string = String::new();
+        loop {
+            match chars.next()? {
+                '"' => return Some(string),
+                '\\' => string.push(match chars.next()? {
+                    'n' => '\n',
+                    't' => '\t',
+                    c @ ('"' | '\\' | '/') => c,
+                    _ => return None,
+                }),
+                c => string.push(c),
+            }
+        }
+    };
+
+    if chars.next()? != '{' {
+        return None;
+    }
+    skip(&mut chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+        return if chars.next().is_none() { Some(object) } else { None };
+    }
+    loop {
+        skip(&mut chars);
+        if chars.next()? != '"' {
+            return None;
+        }
+        let key = string(&mut chars)?;
+        skip(&mut chars);
+        if chars.next()? != ':' {
+            return None;
+        }
+        skip(&mut chars);
+        let value = match *chars.peek()? {
+            '"' => {
+                chars.next();
+                Value::String(string(&mut chars)?)
+            }
+            _ => {
+                let mut word = String::new();
+                while chars
+                    .peek()
+                    .is_some_and(|&c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+                {
+                    word.push(chars.next()?);
+                }
+                match word.as_str() {
+                    "null" => Value::Null,
+                    "true" => Value::Bool(true),
+                    "false" => Value::Bool(false),
+                    _ => Value::Number(word.parse().ok()?),
+                }
+            }
+        };
+        object.insert(key, value);
+        skip(&mut chars);
+        match chars.next()? {
+            ',' => continue,
+            '}' => break,
+            _ => return None,
+        }
+    }
+    skip(&mut chars);
+    if chars.next().is_none() {
+        Some(object)
+    } else {
+        None
+    }
+}
+
+/// Quote `text` as a JSON string
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
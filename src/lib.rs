@@ -73,12 +73,28 @@
 //! * `/dev/mem` interface: Higher frequency port usage
 //!
 
+#[macro_use]
 extern crate nix;
 #[macro_use]
 extern crate quick_error;
 
+pub mod clock;
 pub mod sysfs;
 pub mod dummy;
+pub mod expander;
+pub mod encoder;
+pub mod button;
+pub mod pulse;
+pub mod record;
+pub mod sim;
+pub mod remote;
+pub mod pigpio;
+pub mod firmata;
+pub mod config;
+pub mod boards;
+mod served;
+#[cfg(feature = "http")]
+pub mod http;
 
 /// A value read from or written to a GPIO port
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -89,6 +105,17 @@ pub enum GpioValue {
     High,
 }
 
+/// A pull resistor, or the value of a line that is not driven
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Pull {
+    /// No pull resistor, the line floats
+    None,
+    /// The line is pulled high
+    Up,
+    /// The line is pulled low
+    Down,
+}
+
 /// A setting for signaling an interrupt.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum GpioEdge {
@@ -102,6 +129,21 @@ pub enum GpioEdge {
     Both,
 }
 
+impl GpioEdge {
+    /// Whether a change to `value` is an edge that should be reported
+    #[inline]
+    pub(crate) fn matches(self, value: GpioValue) -> bool {
+        match (self, value) {
+            (GpioEdge::Both, _) |
+            (GpioEdge::Rising, GpioValue::High) |
+            (GpioEdge::Falling, GpioValue::Low) => true,
+            (GpioEdge::None, _) |
+            (GpioEdge::Rising, GpioValue::Low) |
+            (GpioEdge::Falling, GpioValue::High) => false,
+        }
+    }
+}
+
 impl From<bool> for GpioValue {
     #[inline]
     fn from(val: bool) -> GpioValue {
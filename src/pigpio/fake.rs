@@ -0,0 +1,283 @@
+//! A stand-in for `pigpiod`
+//!
+//! `FakePigpiod` listens on a local TCP port and implements the commands used by `Pigpio`:
+//! setting and reading modes, pull resistors and levels, reading bank 1 and notifications. Other
+//! commands are refused with `PI_NOT_PERMITTED`. Levels of inputs are set with
+//! `FakePigpiod::set_level`, as if driven by external hardware, and sent to notification handles
+//! like the real daemon does.
+
+use std::{collections, fmt, io, net, sync, thread, time};
+use std::io::{Read, Write};
+use super::super::{GpioValue, Pull};
+use super::{decode, encode, Mode, CMD_BR1, CMD_MODEG, CMD_MODES, CMD_NB, CMD_NC, CMD_NOIB,
+            CMD_NP, CMD_PUD, CMD_READ, CMD_WRITE, PI_BAD_GPIO, PI_BAD_HANDLE, PI_BAD_LEVEL,
+            PI_BAD_MODE, PI_BAD_PUD, PI_NOT_PERMITTED};
+
+/// Number of GPIOs of the BCM2835
+const GPIOS: usize = 54;
+
+struct Notification {
+    stream: net::TcpStream,
+    bits: u32,
+    seq: u16,
+}
+
+struct State {
+    levels: u64,
+    /// GPIOs whose level was set through `set_level`, pull resistors no longer change them
+    driven: u64,
+    modes: [u32; GPIOS],
+    pulls: [Pull; GPIOS],
+    notifications: collections::BTreeMap<u32, Notification>,
+    next_handle: u32,
+    connections: Vec<net::TcpStream>,
+    start: time::Instant,
+}
+
+impl State {
+    fn level(&self, gpio: usize) -> bool {
+        self.levels & (1 << gpio) != 0
+    }
+
+    /// Change the level of `gpio`, reporting it to the notification handles watching it
+    fn set_level(&mut self, gpio: usize, level: bool) {
+        if self.level(gpio) == level {
+            return;
+        }
+        self.levels ^= 1 << gpio;
+        if gpio >= 32 {
+            return;
+        }
+
+        let tick = self.start.elapsed().as_micros() as u32;
+        let levels = self.levels as u32;
+        for notification in self.notifications.values_mut() {
+            if notification.bits & (1 << gpio) == 0 {
+                continue;
+            }
+            let mut report = [0; 12];
+            report[..2].copy_from_slice(&notification.seq.to_le_bytes());
+            report[4..8].copy_from_slice(&tick.to_le_bytes());
+            report[8..].copy_from_slice(&levels.to_le_bytes());
+            notification.seq = notification.seq.wrapping_add(1);
+            // handles of clients that went away are closed when their connection ends
+            let _ = notification.stream.write_all(&report);
+        }
+    }
+
+    /// Execute a command, returning its result or error code
+    fn command(&mut self, cmd: u32, p1: u32, p2: u32) -> i32 {
+        let gpio = p1 as usize;
+        let valid_gpio = gpio < GPIOS;
+        match cmd {
+            CMD_MODES | CMD_MODEG | CMD_PUD | CMD_READ | CMD_WRITE if !valid_gpio => PI_BAD_GPIO,
+            CMD_MODES => match Mode::from_raw(p2) {
+                Some(_) => {
+                    self.modes[gpio] = p2;
+                    0
+                }
+                None => PI_BAD_MODE,
+            },
+            CMD_MODEG => self.modes[gpio] as i32,
+            CMD_PUD => {
+                let pull = match p2 {
+                    0 => Pull::None,
+                    1 => Pull::Down,
+                    2 => Pull::Up,
+                    _ => return PI_BAD_PUD,
+                };
+                self.pulls[gpio] = pull;
+                let undriven = self.modes[gpio] == 0 && self.driven & (1 << gpio) == 0;
+                match pull {
+                    Pull::Up if undriven => self.set_level(gpio, true),
+                    Pull::Down if undriven => self.set_level(gpio, false),
+                    _ => (),
+                }
+                0
+            }
+            CMD_READ => i32::from(self.level(gpio)),
+            CMD_WRITE => {
+                if p2 > 1 {
+                    return PI_BAD_LEVEL;
+                }
+                self.modes[gpio] = 1;
+                self.set_level(gpio, p2 == 1);
+                0
+            }
+            CMD_BR1 => self.levels as u32 as i32,
+            CMD_NB | CMD_NP => match self.notifications.get_mut(&p1) {
+                Some(notification) => {
+                    notification.bits = if cmd == CMD_NB { p2 } else { 0 };
+                    0
+                }
+                None => PI_BAD_HANDLE,
+            },
+            CMD_NC => match self.notifications.remove(&p1) {
+                Some(notification) => {
+                    let _ = notification.stream.shutdown(net::Shutdown::Both);
+                    0
+                }
+                None => PI_BAD_HANDLE,
+            },
+            _ => PI_NOT_PERMITTED,
+        }
+    }
+}
+
+#[inline]
+fn lock(state: &sync::Mutex<State>) -> sync::MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+}
+
+/// Answer the commands of a client until it disconnects
+fn serve(state: &sync::Mutex<State>, mut stream: net::TcpStream) -> io::Result<()> {
+    loop {
+        let mut request = [0; 16];
+        match stream.read_exact(&mut request) {
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            result => result?,
+        }
+        let [cmd, p1, p2, p3] = decode(&request);
+        // extensions belong to commands that are not implemented
+        io::copy(&mut (&mut stream).take(u64::from(p3)), &mut io::sink())?;
+
+        if cmd == CMD_NOIB {
+            let handle = {
+                let mut state = lock(state);
+                let handle = state.next_handle;
+                state.next_handle += 1;
+                state.notifications.insert(
+                    handle,
+                    Notification {
+                        stream: stream.try_clone()?,
+                        bits: 0,
+                        seq: 0,
+                    },
+                );
+                handle
+            };
+            stream.write_all(&encode([cmd, p1, p2, handle]))?;
+            // the connection only carries reports from now on, the handle is closed with it
+            let result = io::copy(&mut stream, &mut io::sink());
+            lock(state).notifications.remove(&handle);
+            return result.map(|_| ());
+        }
+
+        let result = lock(state).command(cmd, p1, p2);
+        stream.write_all(&encode([cmd, p1, p2, result as u32]))?;
+    }
+}
+
+/// A local stand-in for `pigpiod`, stopped when dropped
+///
+/// All GPIOs start as low inputs without pull resistors.
+pub struct FakePigpiod {
+    addr: net::SocketAddr,
+    state: sync::Arc<sync::Mutex<State>>,
+    stop: sync::Arc<sync::atomic::AtomicBool>,
+    accept: Option<thread::JoinHandle<()>>,
+}
+
+impl fmt::Debug for FakePigpiod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FakePigpiod")
+            .field("addr", &self.addr)
+            .finish()
+    }
+}
+
+impl FakePigpiod {
+    /// Start listening on a free port of the loopback interface
+    pub fn new() -> io::Result<FakePigpiod> {
+        let listener = net::TcpListener::bind((net::Ipv4Addr::LOCALHOST, 0))?;
+        let addr = listener.local_addr()?;
+        let state = sync::Arc::new(sync::Mutex::new(State {
+            levels: 0,
+            driven: 0,
+            modes: [0; GPIOS],
+            pulls: [Pull::None; GPIOS],
+            notifications: collections::BTreeMap::new(),
+            next_handle: 0,
+            connections: Vec::new(),
+            start: time::Instant::now(),
+        }));
+        let stop = sync::Arc::new(sync::atomic::AtomicBool::new(false));
+
+        let accept = {
+            let state = state.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stop.load(sync::atomic::Ordering::SeqCst) {
+                        break;
+                    }
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(_) => continue,
+                    };
+                    if let Ok(clone) = stream.try_clone() {
+                        lock(&state).connections.push(clone);
+                    }
+                    let state = state.clone();
+                    thread::spawn(move || serve(&state, stream));
+                }
+            })
+        };
+
+        Ok(FakePigpiod {
+            addr,
+            state,
+            stop,
+            accept: Some(accept),
+        })
+    }
+
+    /// The address to connect to
+    #[inline]
+    pub fn addr(&self) -> net::SocketAddr {
+        self.addr
+    }
+
+    /// Drive `gpio` with `value` from outside, regardless of its mode
+    ///
+    /// # Panics
+    ///
+    /// If `gpio` is not 0-53.
+    pub fn set_level<V: Into<GpioValue>>(&self, gpio: u8, value: V) {
+        let gpio = usize::from(gpio);
+        assert!(gpio < GPIOS, "GPIO {} out of range", gpio);
+        let mut state = lock(&self.state);
+        state.driven |= 1 << gpio;
+        state.set_level(gpio, value.into() == GpioValue::High);
+    }
+
+    /// The level of `gpio`
+    pub fn level(&self, gpio: u8) -> GpioValue {
+        GpioValue::from(lock(&self.state).level(usize::from(gpio)))
+    }
+
+    /// The function of `gpio`
+    pub fn mode(&self, gpio: u8) -> Mode {
+        let raw = lock(&self.state).modes[usize::from(gpio)];
+        Mode::from_raw(raw).expect("only valid modes are stored")
+    }
+
+    /// The pull resistor of `gpio`
+    pub fn pull(&self, gpio: u8) -> Pull {
+        lock(&self.state).pulls[usize::from(gpio)]
+    }
+}
+
+impl Drop for FakePigpiod {
+    fn drop(&mut self) {
+        self.stop.store(true, sync::atomic::Ordering::SeqCst);
+        // wake up the accept thread, which checks for the stop flag on every connection
+        let _ = net::TcpStream::connect(self.addr);
+        for stream in &lock(&self.state).connections {
+            let _ = stream.shutdown(net::Shutdown::Both);
+        }
+        if let Some(accept) = self.accept.take() {
+            let _ = accept.join();
+        }
+    }
+}
@@ -0,0 +1,538 @@
+//! GPIO access through the pigpio daemon
+//!
+//! `pigpiod` owns the GPIOs of a Raspberry Pi and accepts commands over TCP, port 8888 by
+//! default. `Pigpio` is a connection to it, handing out `PigpioGpioIn` and `PigpioGpioOut` pins.
+//! Edges are reported by the daemon through notifications, which `PigpioEdgeIter` receives on a
+//! connection of its own.
+//!
+//! Every command is sent as four little-endian 32-bit words: the command, two parameters and
+//! the length of an extension, which none of the commands used here have. The daemon answers
+//! with the command, the parameters and a result, which is an error code if negative, except for
+//! bitmask results like the levels read by BR1.
+//!
+//! `fake::FakePigpiod` implements the commands used by this module, so code using the daemon
+//! can be tested without a Raspberry Pi.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use gpio::{GpioEdge, GpioIn, GpioOut, GpioValue, Pull};
+//! use gpio::pigpio::{Mode, Pigpio, PigpioEdgeIter};
+//! use gpio::pigpio::fake::FakePigpiod;
+//!
+//! let daemon = FakePigpiod::new().unwrap();
+//! let pi = Pigpio::connect(daemon.addr()).unwrap();
+//!
+//! let mut led = pi.output(18).unwrap();
+//! led.set_high().unwrap();
+//! assert_eq!(daemon.mode(18), Mode::Output);
+//! assert_eq!(daemon.level(18), GpioValue::High);
+//!
+//! let mut button = pi.input(17).unwrap();
+//! button.set_pull(Pull::Up).unwrap();
+//! assert_eq!(button.read_value().unwrap(), GpioValue::High);
+//!
+//! button.set_edge(GpioEdge::Falling).unwrap();
+//! let mut edges = PigpioEdgeIter::new(&pi).unwrap();
+//! edges.timeout_ms(1000).add(&button).unwrap();
+//! daemon.set_level(17, GpioValue::Low);
+//! assert_eq!(edges.next().unwrap().unwrap().gpio(), 17);
+//! ```
+
+use std::{collections, env, fmt, io, net, sync, time};
+use std::io::{Read, Write};
+use super::{GpioEdge, GpioIn, GpioOut, GpioValue, Pull};
+
+pub mod fake;
+
+/// Port `pigpiod` listens on unless started with `-p`
+pub const DEFAULT_PORT: u16 = 8888;
+
+const CMD_MODES: u32 = 0;
+const CMD_MODEG: u32 = 1;
+const CMD_PUD: u32 = 2;
+const CMD_READ: u32 = 3;
+const CMD_WRITE: u32 = 4;
+const CMD_BR1: u32 = 10;
+const CMD_NB: u32 = 19;
+const CMD_NP: u32 = 20;
+const CMD_NC: u32 = 21;
+const CMD_NOIB: u32 = 99;
+
+const PI_BAD_GPIO: i32 = -3;
+const PI_BAD_MODE: i32 = -4;
+const PI_BAD_LEVEL: i32 = -5;
+const PI_BAD_PUD: i32 = -6;
+const PI_NO_HANDLE: i32 = -24;
+const PI_BAD_HANDLE: i32 = -25;
+const PI_NOT_PERMITTED: i32 = -41;
+
+/// Flags of notification reports that are not level changes
+const NTFY_FLAGS_WDOG: u16 = 1 << 5;
+const NTFY_FLAGS_ALIVE: u16 = 1 << 6;
+const NTFY_FLAGS_EVENT: u16 = 1 << 7;
+
+/// Size of a notification report: sequence number, flags, tick and levels
+const REPORT_SIZE: usize = 12;
+
+/// GPIOs that can be notified about, those of bank 1
+const NOTIFY_GPIOS: u8 = 32;
+
+/// The message of a pigpio error code
+fn error_message(code: i32) -> Option<&'static str> {
+    Some(match code {
+        PI_BAD_GPIO => "GPIO not 0-53",
+        PI_BAD_MODE => "mode not 0-7",
+        PI_BAD_LEVEL => "level not 0-1",
+        PI_BAD_PUD => "pud not 0-2",
+        PI_NO_HANDLE => "no handle available",
+        PI_BAD_HANDLE => "unknown handle",
+        PI_NOT_PERMITTED => "GPIO operation not permitted",
+        _ => return None,
+    })
+}
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum PigpioError {
+        Io(err: io::Error) {
+            from()
+            description("io error")
+            display("I/O error: {}", err)
+            cause(err)
+        }
+        /// The daemon answered with an error code
+        Daemon(code: i32) {
+            description("pigpio error")
+            display("pigpio error {}: {}", code, error_message(*code).unwrap_or("unknown error"))
+        }
+        /// The daemon answered a different command than was sent
+        Protocol(sent: u32, answered: u32) {
+            description("unexpected response from pigpio")
+            display("pigpio answered command {} instead of {}", answered, sent)
+        }
+        /// Only GPIOs 0-31 can be watched for edges
+        NotNotifiable(gpio: u8) {
+            description("GPIO cannot be watched for edges")
+            display("GPIO {} cannot be watched for edges, only 0-31 can", gpio)
+        }
+        /// Pins of different connections were added to the same edge iterator
+        ForeignPin(gpio: u8) {
+            description("pin belongs to a different connection")
+            display("GPIO {} belongs to a different connection than the edge iterator", gpio)
+        }
+        /// No edge occurred before the timeout of an edge iterator
+        Timeout {
+            description("timed out waiting for an edge")
+        }
+    }
+}
+
+pub type PigpioResult<T> = Result<T, PigpioError>;
+
+/// The function of a GPIO
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Mode {
+    Input,
+    Output,
+    /// Alternative function 0-5
+    Alt(u8),
+}
+
+/// Numbers of the alternative functions in pigpio's mode encoding
+const ALT_MODES: [u32; 6] = [4, 5, 6, 7, 3, 2];
+
+impl Mode {
+    fn to_raw(self) -> PigpioResult<u32> {
+        match self {
+            Mode::Input => Ok(0),
+            Mode::Output => Ok(1),
+            Mode::Alt(alt) => ALT_MODES
+                .get(alt as usize)
+                .cloned()
+                .ok_or(PigpioError::Daemon(PI_BAD_MODE)),
+        }
+    }
+
+    fn from_raw(raw: u32) -> Option<Mode> {
+        match raw {
+            0 => Some(Mode::Input),
+            1 => Some(Mode::Output),
+            _ => ALT_MODES
+                .iter()
+                .position(|&mode| mode == raw)
+                .map(|alt| Mode::Alt(alt as u8)),
+        }
+    }
+}
+
+fn pull_to_raw(pull: Pull) -> u32 {
+    match pull {
+        Pull::None => 0,
+        Pull::Down => 1,
+        Pull::Up => 2,
+    }
+}
+
+fn encode(words: [u32; 4]) -> [u8; 16] {
+    let mut data = [0; 16];
+    for (chunk, word) in data.chunks_mut(4).zip(&words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    data
+}
+
+fn decode(data: &[u8; 16]) -> [u32; 4] {
+    let mut words = [0; 4];
+    for (word, chunk) in words.iter_mut().zip(data.chunks(4)) {
+        *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    words
+}
+
+/// Send a command on `stream` and return its raw result
+///
+/// Bitmask commands like BR1 use all 32 bits of the result and never fail.
+fn exchange(stream: &mut net::TcpStream, cmd: u32, p1: u32, p2: u32) -> PigpioResult<u32> {
+    stream.write_all(&encode([cmd, p1, p2, 0]))?;
+    let mut response = [0; 16];
+    stream.read_exact(&mut response)?;
+    let response = decode(&response);
+    if response[0] != cmd {
+        return Err(PigpioError::Protocol(cmd, response[0]));
+    }
+    Ok(response[3])
+}
+
+/// Send a command on `stream` and return its non-negative result
+fn command(stream: &mut net::TcpStream, cmd: u32, p1: u32, p2: u32) -> PigpioResult<u32> {
+    let result = exchange(stream, cmd, p1, p2)? as i32;
+    if result < 0 {
+        return Err(PigpioError::Daemon(result));
+    }
+    Ok(result as u32)
+}
+
+struct Connection {
+    addr: net::SocketAddr,
+    stream: sync::Mutex<net::TcpStream>,
+}
+
+/// A connection to `pigpiod`
+///
+/// Clones share the connection, commands of different threads are sent one after another.
+#[derive(Clone)]
+pub struct Pigpio {
+    conn: sync::Arc<Connection>,
+}
+
+impl fmt::Debug for Pigpio {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pigpio")
+            .field("addr", &self.conn.addr)
+            .finish()
+    }
+}
+
+impl Pigpio {
+    /// Connect to the daemon listening on `addr`
+    pub fn connect<A: net::ToSocketAddrs>(addr: A) -> PigpioResult<Pigpio> {
+        let stream = net::TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Pigpio {
+            conn: sync::Arc::new(Connection {
+                addr: stream.peer_addr()?,
+                stream: sync::Mutex::new(stream),
+            }),
+        })
+    }
+
+    /// Connect to the daemon at `$PIGPIO_ADDR` and `$PIGPIO_PORT`, like pigpio's own clients
+    ///
+    /// Defaults to port 8888 on localhost.
+    pub fn connect_default() -> PigpioResult<Pigpio> {
+        let host = env::var("PIGPIO_ADDR").unwrap_or_else(|_| "localhost".to_owned());
+        let port = env::var("PIGPIO_PORT")
+            .ok()
+            .and_then(|port| port.parse().ok())
+            .unwrap_or(DEFAULT_PORT);
+        Pigpio::connect((host.as_str(), port))
+    }
+
+    fn command(&self, cmd: u32, p1: u32, p2: u32) -> PigpioResult<u32> {
+        command(&mut self.stream(), cmd, p1, p2)
+    }
+
+    fn stream(&self) -> sync::MutexGuard<'_, net::TcpStream> {
+        self.conn
+            .stream
+            .lock()
+            .unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// Set the function of `gpio`
+    pub fn set_mode(&self, gpio: u8, mode: Mode) -> PigpioResult<()> {
+        self.command(CMD_MODES, u32::from(gpio), mode.to_raw()?)
+            .map(|_| ())
+    }
+
+    /// The function of `gpio`
+    pub fn mode(&self, gpio: u8) -> PigpioResult<Mode> {
+        let raw = self.command(CMD_MODEG, u32::from(gpio), 0)?;
+        Mode::from_raw(raw).ok_or(PigpioError::Daemon(PI_BAD_MODE))
+    }
+
+    /// Set the pull resistor of `gpio`, `Pull::None` disables it
+    pub fn set_pull(&self, gpio: u8, pull: Pull) -> PigpioResult<()> {
+        self.command(CMD_PUD, u32::from(gpio), pull_to_raw(pull))
+            .map(|_| ())
+    }
+
+    /// Read the level of `gpio`
+    pub fn read(&self, gpio: u8) -> PigpioResult<GpioValue> {
+        Ok(GpioValue::from(self.command(CMD_READ, u32::from(gpio), 0)? != 0))
+    }
+
+    /// Drive `gpio` with `value`, making it an output if necessary
+    pub fn write(&self, gpio: u8, value: GpioValue) -> PigpioResult<()> {
+        self.command(CMD_WRITE, u32::from(gpio), u32::from(u8::from(value)))
+            .map(|_| ())
+    }
+
+    /// Read the levels of GPIOs 0-31, one bit each
+    pub fn read_bank(&self) -> PigpioResult<u32> {
+        exchange(&mut self.stream(), CMD_BR1, 0, 0)
+    }
+
+    /// Configure `gpio` as an input and return a handle to it
+    pub fn input(&self, gpio: u8) -> PigpioResult<PigpioGpioIn> {
+        self.set_mode(gpio, Mode::Input)?;
+        Ok(PigpioGpioIn {
+            pigpio: self.clone(),
+            gpio,
+            edge: GpioEdge::None,
+        })
+    }
+
+    /// Configure `gpio` as an output and return a handle to it
+    ///
+    /// The output keeps its previous level until written.
+    pub fn output(&self, gpio: u8) -> PigpioResult<PigpioGpioOut> {
+        self.set_mode(gpio, Mode::Output)?;
+        Ok(PigpioGpioOut {
+            pigpio: self.clone(),
+            gpio,
+        })
+    }
+}
+
+/// Input GPIO of a pigpio daemon
+///
+/// The edge set with `set_edge` is only used by `PigpioEdgeIter`, the daemon always reports both
+/// edges.
+#[derive(Debug, Clone)]
+pub struct PigpioGpioIn {
+    pigpio: Pigpio,
+    gpio: u8,
+    edge: GpioEdge,
+}
+
+impl PigpioGpioIn {
+    /// The Broadcom number of the GPIO
+    #[inline]
+    pub fn gpio(&self) -> u8 {
+        self.gpio
+    }
+
+    /// Set the pull resistor of the GPIO
+    #[inline]
+    pub fn set_pull(&self, pull: Pull) -> PigpioResult<()> {
+        self.pigpio.set_pull(self.gpio, pull)
+    }
+}
+
+impl GpioIn for PigpioGpioIn {
+    type Error = PigpioError;
+
+    #[inline]
+    fn read_value(&self) -> PigpioResult<GpioValue> {
+        self.pigpio.read(self.gpio)
+    }
+
+    #[inline]
+    fn set_edge(&mut self, edge: GpioEdge) -> PigpioResult<()> {
+        self.edge = edge;
+        Ok(())
+    }
+}
+
+/// Output GPIO of a pigpio daemon
+#[derive(Debug, Clone)]
+pub struct PigpioGpioOut {
+    pigpio: Pigpio,
+    gpio: u8,
+}
+
+impl PigpioGpioOut {
+    /// The Broadcom number of the GPIO
+    #[inline]
+    pub fn gpio(&self) -> u8 {
+        self.gpio
+    }
+}
+
+impl GpioOut for PigpioGpioOut {
+    type Error = PigpioError;
+
+    #[inline]
+    fn set_low(&mut self) -> PigpioResult<()> {
+        self.pigpio.write(self.gpio, GpioValue::Low)
+    }
+
+    #[inline]
+    fn set_high(&mut self) -> PigpioResult<()> {
+        self.pigpio.write(self.gpio, GpioValue::High)
+    }
+}
+
+/// Iterator over edges of pigpio inputs
+///
+/// Opens a notification handle on a connection of its own, which is closed when the iterator is
+/// dropped. Only GPIOs 0-31 can be added. Edges shorter than the daemon's sampling interval
+/// (5 µs by default) may be missed.
+pub struct PigpioEdgeIter<'a> {
+    pigpio: Pigpio,
+    notifications: net::TcpStream,
+    handle: u32,
+    devs: Vec<&'a PigpioGpioIn>,
+    bits: u32,
+    levels: u32,
+    buffer: Vec<u8>,
+    pending: collections::VecDeque<&'a PigpioGpioIn>,
+    timeout: Option<time::Duration>,
+}
+
+impl<'a> fmt::Debug for PigpioEdgeIter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PigpioEdgeIter")
+            .field("handle", &self.handle)
+            .field("devs", &self.devs)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl<'a> PigpioEdgeIter<'a> {
+    /// Open a notification handle on the daemon `pigpio` is connected to
+    pub fn new(pigpio: &Pigpio) -> PigpioResult<PigpioEdgeIter<'a>> {
+        let mut notifications = net::TcpStream::connect(pigpio.conn.addr)?;
+        let handle = command(&mut notifications, CMD_NOIB, 0, 0)?;
+        Ok(PigpioEdgeIter {
+            pigpio: pigpio.clone(),
+            notifications,
+            handle,
+            devs: Vec::new(),
+            bits: 0,
+            levels: 0,
+            buffer: Vec::new(),
+            pending: collections::VecDeque::new(),
+            timeout: None,
+        })
+    }
+
+    /// Wait at most `timeout_ms` for an edge on each call of `next`
+    #[inline]
+    pub fn timeout_ms(&mut self, timeout_ms: u64) -> &mut Self {
+        self.timeout = Some(time::Duration::from_millis(timeout_ms));
+        self
+    }
+
+    /// Add a pin to the iterator
+    pub fn add(&mut self, dev: &'a PigpioGpioIn) -> PigpioResult<&mut Self> {
+        if dev.gpio >= NOTIFY_GPIOS {
+            return Err(PigpioError::NotNotifiable(dev.gpio));
+        }
+        if !sync::Arc::ptr_eq(&dev.pigpio.conn, &self.pigpio.conn) {
+            return Err(PigpioError::ForeignPin(dev.gpio));
+        }
+        let bit = 1 << dev.gpio;
+        if self.bits & bit == 0 {
+            self.bits |= bit;
+            self.pigpio.command(CMD_NB, self.handle, self.bits)?;
+            // edges are detected relative to the levels once notifications started
+            let levels = self.pigpio.read_bank()?;
+            self.levels = (self.levels & !bit) | (levels & bit);
+        }
+        self.devs.push(dev);
+        Ok(self)
+    }
+
+    /// Queue the pins whose edge configuration matches a change to `levels`
+    fn report(&mut self, flags: u16, levels: u32) {
+        if flags & (NTFY_FLAGS_WDOG | NTFY_FLAGS_ALIVE | NTFY_FLAGS_EVENT) != 0 {
+            return;
+        }
+        let changed = (levels ^ self.levels) & self.bits;
+        self.levels = levels;
+        for &dev in &self.devs {
+            let bit = 1 << dev.gpio;
+            if changed & bit != 0 && dev.edge.matches(GpioValue::from(levels & bit != 0)) {
+                self.pending.push_back(dev);
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for PigpioEdgeIter<'a> {
+    type Item = Result<&'a PigpioGpioIn, PigpioError>;
+
+    fn next(&mut self) -> Option<Result<&'a PigpioGpioIn, PigpioError>> {
+        let start = time::Instant::now();
+        loop {
+            if let Some(dev) = self.pending.pop_front() {
+                return Some(Ok(dev));
+            }
+
+            let wait = match self.timeout {
+                Some(timeout) => match timeout.checked_sub(start.elapsed()) {
+                    Some(remaining) if remaining > time::Duration::from_secs(0) => Some(remaining),
+                    _ => return Some(Err(PigpioError::Timeout)),
+                },
+                None => None,
+            };
+            if let Err(err) = self.notifications.set_read_timeout(wait) {
+                return Some(Err(err.into()));
+            }
+
+            // reports may arrive in pieces, a partial one is kept until the rest was read
+            let mut data = [0; REPORT_SIZE * 16];
+            let count = match self.notifications.read(&mut data) {
+                Ok(0) => return Some(Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())),
+                Ok(count) => count,
+                Err(ref err)
+                    if err.kind() == io::ErrorKind::WouldBlock
+                        || err.kind() == io::ErrorKind::TimedOut =>
+                {
+                    continue
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Some(Err(err.into())),
+            };
+            self.buffer.extend_from_slice(&data[..count]);
+
+            let complete = self.buffer.len() / REPORT_SIZE * REPORT_SIZE;
+            let reports: Vec<u8> = self.buffer.drain(..complete).collect();
+            for report in reports.chunks(REPORT_SIZE) {
+                let flags = u16::from_le_bytes([report[2], report[3]]);
+                let levels = u32::from_le_bytes([report[8], report[9], report[10], report[11]]);
+                self.report(flags, levels);
+            }
+        }
+    }
+}
+
+impl<'a> Drop for PigpioEdgeIter<'a> {
+    fn drop(&mut self) {
+        let _ = self.pigpio.command(CMD_NC, self.handle, 0);
+    }
+}
@@ -0,0 +1,244 @@
+//! Pulse-width and frequency measurement
+//!
+//! `PulseMeter` records the time of every edge of a signal, e.g. a fan tachometer or a flow
+//! meter, and derives period, frequency, duty cycle and the widths of high and low pulses from
+//! the edges within a sliding window.
+//!
+//! Like `Button`, the meter is passed the time of each edge instead of measuring it, so it can
+//! be driven by any edge iterator and tested on a made-up timeline. Edges should be timestamped
+//! as soon as the iterator returns them. For the pulse widths, the input needs to report both
+//! edges; with only rising or falling edges, the period is still measured.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use std::time::{Duration, Instant};
+//! use gpio::GpioValue;
+//! use gpio::pulse::PulseMeter;
+//!
+//! let mut meter = PulseMeter::new(Duration::from_secs(1), Duration::from_millis(100));
+//!
+//! // a 50 Hz signal, high for 5 ms of each 20 ms period
+//! let t0 = Instant::now();
+//! for i in 0..10 {
+//!     meter.record(GpioValue::High, t0 + Duration::from_millis(i * 20));
+//!     meter.record(GpioValue::Low, t0 + Duration::from_millis(i * 20 + 5));
+//! }
+//!
+//! let now = t0 + Duration::from_millis(200);
+//! let m = meter.measure(now).unwrap();
+//! assert_eq!(m.period.average, Duration::from_millis(20));
+//! assert_eq!(m.high.unwrap().max, Duration::from_millis(5));
+//! assert!((m.frequency() - 50.0).abs() < 1e-9);
+//! assert!((m.duty_cycle().unwrap() - 0.25).abs() < 1e-9);
+//!
+//! // the signal stops
+//! assert!(meter.signal_lost(now + Duration::from_millis(150)));
+//! assert!(meter.measure(now + Duration::from_millis(150)).is_none());
+//! ```
+
+use std::{collections, time};
+use super::{GpioIn, GpioValue};
+
+/// Statistics over a set of durations
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PulseStats {
+    /// Shortest duration
+    pub min: time::Duration,
+    /// Longest duration
+    pub max: time::Duration,
+    /// Mean duration
+    pub average: time::Duration,
+    /// Number of durations the statistics are based on
+    pub count: usize,
+}
+
+impl PulseStats {
+    fn from_durations<I>(durations: I) -> Option<PulseStats>
+    where
+        I: Iterator<Item = time::Duration>,
+    {
+        let mut stats: Option<PulseStats> = None;
+        let mut total = time::Duration::from_secs(0);
+
+        for d in durations {
+            total += d;
+            stats = Some(match stats {
+                None => PulseStats {
+                    min: d,
+                    max: d,
+                    average: d,
+                    count: 1,
+                },
+                Some(s) => PulseStats {
+                    min: s.min.min(d),
+                    max: s.max.max(d),
+                    average: s.average,
+                    count: s.count + 1,
+                },
+            });
+        }
+
+        stats.map(|s| PulseStats {
+            average: total / s.count as u32,
+            ..s
+        })
+    }
+}
+
+/// A measurement of a periodic signal
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PulseMeasurement {
+    /// Time between consecutive edges of the same direction
+    pub period: PulseStats,
+    /// Widths of high pulses, if both edges are reported
+    pub high: Option<PulseStats>,
+    /// Widths of low pulses, if both edges are reported
+    pub low: Option<PulseStats>,
+}
+
+impl PulseMeasurement {
+    /// Frequency in Hz, based on the average period
+    #[inline]
+    pub fn frequency(&self) -> f64 {
+        1.0 / self.period.average.as_secs_f64()
+    }
+
+    /// Fraction of time the signal is high, from 0.0 to 1.0
+    pub fn duty_cycle(&self) -> Option<f64> {
+        let high = self.high?.average.as_secs_f64();
+        let low = self.low?.average.as_secs_f64();
+        Some(high / (high + low))
+    }
+}
+
+/// Measures pulses from timestamped edges
+#[derive(Debug, Clone)]
+pub struct PulseMeter {
+    window: time::Duration,
+    timeout: time::Duration,
+    max_edges: usize,
+    /// Edges within the window, as the value after the edge and its time
+    edges: collections::VecDeque<(GpioValue, time::Instant)>,
+}
+
+impl PulseMeter {
+    /// Create a new meter
+    ///
+    /// Measurements are based on the edges of the last `window`. If no edge arrives for
+    /// `timeout`, the signal is considered lost.
+    pub fn new(window: time::Duration, timeout: time::Duration) -> PulseMeter {
+        PulseMeter {
+            window,
+            timeout,
+            max_edges: 4096,
+            edges: collections::VecDeque::new(),
+        }
+    }
+
+    /// Limit the number of edges kept in the window (default: 4096)
+    ///
+    /// Bounds memory usage and computation time for high frequency signals. Once the limit is
+    /// reached, the oldest edges are dropped, shortening the effective window.
+    pub fn max_edges(mut self, max_edges: usize) -> Self {
+        self.max_edges = max_edges.max(2);
+        self
+    }
+
+    /// Record an edge at time `at`, after which the signal had `value`
+    ///
+    /// Edges must be recorded in chronological order.
+    pub fn record(&mut self, value: GpioValue, at: time::Instant) {
+        self.edges.push_back((value, at));
+        while self.edges.len() > self.max_edges {
+            self.edges.pop_front();
+        }
+        self.prune(at);
+    }
+
+    /// Record an edge on `pin` at time `at`, reading its current value
+    ///
+    /// Very short pulses may already be over when the value is read; such edges are detected
+    /// by their value not alternating and are excluded from the periods and pulse widths.
+    pub fn handle_edge<P: GpioIn>(&mut self, pin: &P, at: time::Instant) -> Result<(), P::Error> {
+        let value = pin.read_value()?;
+        self.record(value, at);
+        Ok(())
+    }
+
+    /// Forget all edges recorded so far
+    #[inline]
+    pub fn reset(&mut self) {
+        self.edges.clear();
+    }
+
+    /// Time of the most recent edge
+    #[inline]
+    pub fn last_edge(&self) -> Option<time::Instant> {
+        self.edges.back().map(|&(_, at)| at)
+    }
+
+    /// Whether no edge was seen for longer than the timeout at time `now`
+    ///
+    /// Also true if no edge has been recorded at all.
+    pub fn signal_lost(&self, now: time::Instant) -> bool {
+        self.last_edge()
+            .is_none_or(|last| now.duration_since(last) > self.timeout)
+    }
+
+    /// Measure the signal at time `now`
+    ///
+    /// Returns `None` if the signal is lost or too few edges are within the window.
+    pub fn measure(&self, now: time::Instant) -> Option<PulseMeasurement> {
+        if self.signal_lost(now) {
+            return None;
+        }
+
+        let start = now.checked_sub(self.window);
+        let edges: Vec<_> = self.edges
+            .iter()
+            .filter(|&&(_, at)| start.is_none_or(|s| at >= s))
+            .collect();
+
+        // period between edges of the same kind: every edge if only one kind is reported,
+        // otherwise every other edge, skipping edges that do not alternate
+        let both = edges.windows(2).any(|w| w[0].0 != w[1].0);
+        let period = if both {
+            PulseStats::from_durations(edges.windows(3).filter_map(|w| {
+                let (&(v0, t0), &(v1, _), &(v2, t2)) = (w[0], w[1], w[2]);
+                if v0 == v2 && v0 != v1 {
+                    Some(t2.duration_since(t0))
+                } else {
+                    None
+                }
+            }))
+        } else {
+            PulseStats::from_durations(edges.windows(2).map(|w| w[1].1.duration_since(w[0].1)))
+        }?;
+
+        let widths = |level: GpioValue| {
+            PulseStats::from_durations(edges.windows(2).filter_map(|w| {
+                let (&(v0, t0), &(v1, t1)) = (w[0], w[1]);
+                if v0 == level && v1 != level {
+                    Some(t1.duration_since(t0))
+                } else {
+                    None
+                }
+            }))
+        };
+
+        Some(PulseMeasurement {
+            period,
+            high: widths(GpioValue::High),
+            low: widths(GpioValue::Low),
+        })
+    }
+
+    fn prune(&mut self, now: time::Instant) {
+        if let Some(start) = now.checked_sub(self.window) {
+            while self.edges.front().is_some_and(|&(_, at)| at < start) {
+                self.edges.pop_front();
+            }
+        }
+    }
+}
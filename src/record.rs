@@ -0,0 +1,410 @@
+//! Recording pin accesses
+//!
+//! `Recording` wraps any input or output and logs every read, write and edge configuration,
+//! including its result, to an `EventLog`. The log is a text file with one event per line:
+//!
+//! ```text
+//! 0.000012000 button read high
+//! 0.000015000 led write low ok
+//! 0.000020000 button edge falling error Io(Os { code: 16, kind: ResourceBusy, .. })
+//! ```
+//!
+//! A log can be fed back through `dummy::Replay`, which returns the recorded results to an
+//! application making the same calls, e.g. to reproduce a problem of a field unit on a
+//! development machine.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use gpio::{GpioIn, GpioOut, GpioValue};
+//! use gpio::dummy::{DummyError, DummyGpioIn, DummyGpioOut, Faults, Replay};
+//! use gpio::record::{EventLog, Recording};
+//!
+//! let path = std::env::temp_dir().join(format!("gpio-record-{}.log", std::process::id()));
+//!
+//! // in the field, the real pins are wrapped
+//! let log = EventLog::create(&path).unwrap();
+//! let sensor = DummyGpioIn::with_value(true).faults(Faults::new().fail_on(2));
+//! let sensor = Recording::new(sensor, "sensor", &log);
+//! let mut led = Recording::new(DummyGpioOut::new(|_| ()), "led", &log);
+//!
+//! assert_eq!(sensor.read_value(), Ok(GpioValue::High));
+//! assert!(sensor.read_value().is_err());
+//! led.set_high().unwrap();
+//! log.flush().unwrap();
+//!
+//! // on a development machine, the log is replayed
+//! let replay = Replay::load(&path).unwrap();
+//! let sensor = replay.input("sensor");
+//! let mut led = replay.output("led");
+//!
+//! assert_eq!(sensor.read_value(), Ok(GpioValue::High));
+//! assert_eq!(
+//!     sensor.read_value(),
+//!     Err(DummyError::Replayed("FailedCall(2)".to_owned()))
+//! );
+//! // the application must make the same calls as recorded
+//! assert!(led.set_low().is_err());
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+
+use std::{fmt, fs, io, path, sync, time};
+use std::io::{BufRead, Write};
+use super::{GpioEdge, GpioIn, GpioOut, GpioValue};
+use clock::{Clock, SystemClock};
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum LogError {
+        Io(err: io::Error) {
+            from()
+            description("io error")
+            display("I/O error: {}", err)
+            cause(err)
+        }
+        Parse(line: usize, reason: String) {
+            description("invalid event log")
+            display("invalid event log in line {}: {}", line, reason)
+        }
+    }
+}
+
+pub type LogResult<T> = Result<T, LogError>;
+
+/// An access of a pin and its result, errors are stored as their debug representation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A value was read
+    Read(Result<GpioValue, String>),
+    /// A value was written
+    Write(GpioValue, Result<(), String>),
+    /// The edge was configured
+    Edge(GpioEdge, Result<(), String>),
+}
+
+/// An event of a single pin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    /// Time since the log was created
+    pub time: time::Duration,
+    /// Name of the pin
+    pub pin: String,
+    /// What happened
+    pub event: Event,
+}
+
+fn value_name(value: GpioValue) -> &'static str {
+    match value {
+        GpioValue::Low => "low",
+        GpioValue::High => "high",
+    }
+}
+
+fn edge_name(edge: GpioEdge) -> &'static str {
+    match edge {
+        GpioEdge::None => "none",
+        GpioEdge::Rising => "rising",
+        GpioEdge::Falling => "falling",
+        GpioEdge::Both => "both",
+    }
+}
+
+fn parse_value(value: &str) -> Option<GpioValue> {
+    match value {
+        "low" => Some(GpioValue::Low),
+        "high" => Some(GpioValue::High),
+        _ => None,
+    }
+}
+
+fn parse_edge(edge: &str) -> Option<GpioEdge> {
+    match edge {
+        "none" => Some(GpioEdge::None),
+        "rising" => Some(GpioEdge::Rising),
+        "falling" => Some(GpioEdge::Falling),
+        "both" => Some(GpioEdge::Both),
+        _ => None,
+    }
+}
+
+/// Parse the result at the end of a write or edge event
+fn parse_result(result: &str) -> Option<Result<(), String>> {
+    match result {
+        "ok" => Some(Ok(())),
+        _ if result.starts_with("error ") => Some(Err(result["error ".len()..].to_owned())),
+        _ => None,
+    }
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}.{:09} {} ",
+            self.time.as_secs(),
+            self.time.subsec_nanos(),
+            self.pin
+        )?;
+        let result = |f: &mut fmt::Formatter, result: &Result<(), String>| match *result {
+            Ok(()) => write!(f, "ok"),
+            Err(ref err) => write!(f, "error {}", err),
+        };
+        match self.event {
+            Event::Read(Ok(value)) => write!(f, "read {}", value_name(value)),
+            Event::Read(Err(ref err)) => write!(f, "read error {}", err),
+            Event::Write(value, ref res) => {
+                write!(f, "write {} ", value_name(value))?;
+                result(f, res)
+            }
+            Event::Edge(edge, ref res) => {
+                write!(f, "edge {} ", edge_name(edge))?;
+                result(f, res)
+            }
+        }
+    }
+}
+
+impl LogEntry {
+    /// Parse a line of an event log
+    pub fn parse(line: &str) -> Option<LogEntry> {
+        let mut fields = line.splitn(4, ' ');
+        let (time, pin) = (fields.next()?, fields.next()?);
+        let (op, rest) = (fields.next()?, fields.next()?);
+
+        let mut time = time.splitn(2, '.');
+        let secs = time.next()?.parse().ok()?;
+        let nanos = time.next()?;
+        if nanos.len() != 9 {
+            return None;
+        }
+        let time = time::Duration::new(secs, nanos.parse().ok()?);
+
+        let event = match op {
+            "read" if rest.starts_with("error ") => {
+                Event::Read(Err(rest["error ".len()..].to_owned()))
+            }
+            "read" => Event::Read(Ok(parse_value(rest)?)),
+            "write" => {
+                let mut rest = rest.splitn(2, ' ');
+                Event::Write(parse_value(rest.next()?)?, parse_result(rest.next()?)?)
+            }
+            "edge" => {
+                let mut rest = rest.splitn(2, ' ');
+                Event::Edge(parse_edge(rest.next()?)?, parse_result(rest.next()?)?)
+            }
+            _ => return None,
+        };
+
+        Some(LogEntry {
+            time,
+            pin: pin.to_owned(),
+            event,
+        })
+    }
+}
+
+/// Read all entries of an event log, empty lines are skipped
+pub fn read_log<R: BufRead>(reader: R) -> LogResult<Vec<LogEntry>> {
+    let mut entries = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(
+            LogEntry::parse(&line)
+                .ok_or_else(|| LogError::Parse(index + 1, format!("invalid event: {}", line)))?,
+        );
+    }
+    Ok(entries)
+}
+
+struct LogState {
+    out: Box<dyn Write + Send>,
+    clock: Box<dyn Clock>,
+    start: time::Instant,
+    error: Option<io::Error>,
+}
+
+/// Destination of the events of recorded pins
+///
+/// Clones write to the same log. Errors writing the log do not affect the recorded pins, the
+/// first one is returned by `flush` instead.
+#[derive(Clone)]
+pub struct EventLog {
+    state: sync::Arc<sync::Mutex<LogState>>,
+}
+
+impl fmt::Debug for EventLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventLog").finish()
+    }
+}
+
+impl EventLog {
+    /// Create a log writing to `out`, its timeline starts now
+    #[inline]
+    pub fn new<W: Write + Send + 'static>(out: W) -> EventLog {
+        Self::with_clock(out, SystemClock)
+    }
+
+    /// Create a log writing to `out` that timestamps events using `clock`
+    pub fn with_clock<W, C>(out: W, clock: C) -> EventLog
+    where
+        W: Write + Send + 'static,
+        C: Clock + 'static,
+    {
+        EventLog {
+            state: sync::Arc::new(sync::Mutex::new(LogState {
+                out: Box::new(out),
+                start: clock.now(),
+                clock: Box::new(clock),
+                error: None,
+            })),
+        }
+    }
+
+    /// Create a log file, every event is written as soon as it happens
+    pub fn create<P: AsRef<path::Path>>(path: P) -> io::Result<EventLog> {
+        Ok(Self::new(io::LineWriter::new(fs::File::create(path)?)))
+    }
+
+    #[inline]
+    fn state(&self) -> sync::MutexGuard<'_, LogState> {
+        self.state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    fn log(&self, pin: &str, event: Event) {
+        let mut state = self.state();
+        if state.error.is_some() {
+            return;
+        }
+        let entry = LogEntry {
+            time: state.clock.now().duration_since(state.start),
+            pin: pin.to_owned(),
+            event,
+        };
+        if let Err(err) = writeln!(state.out, "{}", entry) {
+            state.error = Some(err);
+        }
+    }
+
+    /// Flush the log, returning the first error that occurred while writing it
+    pub fn flush(&self) -> io::Result<()> {
+        let mut state = self.state();
+        match state.error.take() {
+            Some(err) => Err(err),
+            None => state.out.flush(),
+        }
+    }
+}
+
+/// Error of a wrapped pin as stored in the log
+fn describe<E: fmt::Debug>(err: &E) -> String {
+    format!("{:?}", err).replace(['\r', '\n'], " ")
+}
+
+/// A pin whose accesses are logged
+#[derive(Debug)]
+pub struct Recording<P> {
+    inner: P,
+    name: String,
+    log: EventLog,
+}
+
+impl<P> Recording<P> {
+    /// Wrap `inner`, logging its events to `log` under `name`
+    ///
+    /// Whitespace in `name` is replaced by underscores.
+    pub fn new(inner: P, name: &str, log: &EventLog) -> Recording<P> {
+        Recording {
+            inner,
+            name: name.replace(char::is_whitespace, "_"),
+            log: log.clone(),
+        }
+    }
+
+    /// The name events are logged under
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The wrapped pin, e.g. to add it to an edge iterator
+    #[inline]
+    pub fn get_ref(&self) -> &P {
+        &self.inner
+    }
+
+    /// The wrapped pin, accesses through it are not logged
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut P {
+        &mut self.inner
+    }
+
+    /// Unwrap the pin
+    #[inline]
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+}
+
+impl<P> Recording<P>
+where
+    P: GpioOut,
+    P::Error: fmt::Debug,
+{
+    fn write(&mut self, value: GpioValue) -> Result<(), P::Error> {
+        let result = match value {
+            GpioValue::Low => self.inner.set_low(),
+            GpioValue::High => self.inner.set_high(),
+        };
+        self.log.log(
+            &self.name,
+            Event::Write(value, result.as_ref().map(|_| ()).map_err(describe)),
+        );
+        result
+    }
+}
+
+impl<P> GpioIn for Recording<P>
+where
+    P: GpioIn,
+    P::Error: fmt::Debug,
+{
+    type Error = P::Error;
+
+    fn read_value(&self) -> Result<GpioValue, Self::Error> {
+        let result = self.inner.read_value();
+        self.log
+            .log(&self.name, Event::Read(result.as_ref().copied().map_err(describe)));
+        result
+    }
+
+    fn set_edge(&mut self, edge: GpioEdge) -> Result<(), Self::Error> {
+        let result = self.inner.set_edge(edge);
+        self.log.log(
+            &self.name,
+            Event::Edge(edge, result.as_ref().map(|_| ()).map_err(describe)),
+        );
+        result
+    }
+}
+
+impl<P> GpioOut for Recording<P>
+where
+    P: GpioOut,
+    P::Error: fmt::Debug,
+{
+    type Error = P::Error;
+
+    #[inline]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.write(GpioValue::Low)
+    }
+
+    #[inline]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.write(GpioValue::High)
+    }
+}
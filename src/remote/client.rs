@@ -0,0 +1,358 @@
+//! The client side of the protocol
+
+use std::{fmt, io, path, sync, thread, time};
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use super::super::{GpioEdge, GpioIn, GpioOut, GpioValue};
+use super::super::clock::{Clock, Notifier, SystemClock};
+use super::{edge_name, parse_mode, parse_value, value_name, PinMode, RemoteError, RemoteResult,
+            PROTOCOL_VERSION};
+
+/// Events not taken by an edge iterator are dropped beyond this number, oldest first
+const MAX_QUEUED_EVENTS: usize = 1024;
+
+#[inline]
+fn lock<T>(mutex: &sync::Mutex<T>) -> sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(sync::PoisonError::into_inner)
+}
+
+/// Edges received from the server, shared with the reading thread
+#[derive(Default)]
+struct Events {
+    queue: sync::Mutex<VecDeque<(u16, GpioValue)>>,
+    notifier: Notifier,
+    disconnected: AtomicBool,
+}
+
+struct Connection {
+    /// The socket requests are written to and the responses read by the reading thread
+    requests: sync::Mutex<(UnixStream, mpsc::Receiver<RemoteResult<String>>)>,
+    events: sync::Arc<Events>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // ends the reading thread
+        let _ = lock(&self.requests).0.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+/// Sort the lines sent by the server into responses and events
+fn read(stream: UnixStream, responses: &mpsc::Sender<RemoteResult<String>>, events: &Events) {
+    for line in io::BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let response = if line == "OK" {
+            Ok(String::new())
+        } else if let Some(result) = line.strip_prefix("OK ") {
+            Ok(result.to_owned())
+        } else if let Some(message) = line.strip_prefix("ERR ") {
+            Err(RemoteError::Server(message.to_owned()))
+        } else if let Some(event) = line.strip_prefix("EVENT ") {
+            let mut fields = event.split(' ');
+            match (
+                fields.next().and_then(|pin| pin.parse().ok()),
+                fields.next().and_then(parse_value),
+                fields.next(),
+            ) {
+                (Some(pin), Some(value), None) => {
+                    let mut queue = lock(&events.queue);
+                    if queue.len() == MAX_QUEUED_EVENTS {
+                        queue.pop_front();
+                    }
+                    queue.push_back((pin, value));
+                    drop(queue);
+                    events.notifier.notify();
+                    continue;
+                }
+                _ => Err(RemoteError::Protocol(line)),
+            }
+        } else {
+            Err(RemoteError::Protocol(line))
+        };
+        if responses.send(response).is_err() {
+            break;
+        }
+    }
+    events.disconnected.store(true, Ordering::SeqCst);
+    events.notifier.notify();
+}
+
+/// A connection to a `Server`
+///
+/// Clones share the connection, which is closed when the last clone and all pins obtained from
+/// it are dropped.
+#[derive(Clone)]
+pub struct Client {
+    conn: sync::Arc<Connection>,
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("disconnected", &self.conn.events.disconnected.load(Ordering::SeqCst))
+            .finish()
+    }
+}
+
+impl Client {
+    /// Connect to the server listening on `path`
+    pub fn connect<P: AsRef<path::Path>>(path: P) -> RemoteResult<Client> {
+        let stream = UnixStream::connect(path)?;
+        let reader = stream.try_clone()?;
+        let events = sync::Arc::new(Events::default());
+        let (sender, receiver) = mpsc::channel();
+        {
+            let events = events.clone();
+            thread::spawn(move || read(reader, &sender, &events));
+        }
+
+        let client = Client {
+            conn: sync::Arc::new(Connection {
+                requests: sync::Mutex::new((stream, receiver)),
+                events,
+            }),
+        };
+        let version = client.request(&format!("HELLO {}", PROTOCOL_VERSION))?;
+        if version != PROTOCOL_VERSION.to_string() {
+            return Err(RemoteError::Protocol(format!("OK {}", version)));
+        }
+        Ok(client)
+    }
+
+    /// Send a request and wait for its response
+    fn request(&self, request: &str) -> RemoteResult<String> {
+        let requests = lock(&self.conn.requests);
+        let (ref stream, ref responses) = *requests;
+        let mut stream = stream;
+        if let Err(err) = writeln!(stream, "{}", request) {
+            return Err(match err.kind() {
+                io::ErrorKind::BrokenPipe => RemoteError::Disconnected,
+                _ => err.into(),
+            });
+        }
+        responses.recv().map_err(|_| RemoteError::Disconnected)?
+    }
+
+    /// The pins served and whether they are inputs or outputs
+    pub fn pins(&self) -> RemoteResult<Vec<(u16, PinMode)>> {
+        let list = self.request("LIST")?;
+        list.split_whitespace()
+            .map(|pin| {
+                let mut fields = pin.splitn(2, ':');
+                match (
+                    fields.next().and_then(|pin| pin.parse().ok()),
+                    fields.next().and_then(parse_mode),
+                ) {
+                    (Some(pin), Some(mode)) => Ok((pin, mode)),
+                    _ => Err(RemoteError::Protocol(format!("OK {}", list))),
+                }
+            })
+            .collect()
+    }
+
+    /// Read an input or the value last written to an output
+    pub fn read(&self, pin: u16) -> RemoteResult<GpioValue> {
+        let value = self.request(&format!("READ {}", pin))?;
+        parse_value(&value).ok_or_else(|| RemoteError::Protocol(format!("OK {}", value)))
+    }
+
+    /// Write `value` to an output
+    pub fn write(&self, pin: u16, value: GpioValue) -> RemoteResult<()> {
+        self.request(&format!("WRITE {} {}", pin, value_name(value)))
+            .map(|_| ())
+    }
+
+    /// Receive `edge`s of an input, `GpioEdge::None` ends the subscription
+    pub fn subscribe(&self, pin: u16, edge: GpioEdge) -> RemoteResult<()> {
+        self.request(&format!("EDGE {} {}", pin, edge_name(edge)))
+            .map(|_| ())
+    }
+
+    /// Access input `pin` of the server
+    ///
+    /// Whether the server has such an input is only checked once the pin is used.
+    pub fn input(&self, pin: u16) -> RemoteGpioIn {
+        RemoteGpioIn {
+            client: self.clone(),
+            pin,
+            last_event: Default::default(),
+        }
+    }
+
+    /// Access output `pin` of the server
+    ///
+    /// Whether the server has such an output is only checked once the pin is used.
+    pub fn output(&self, pin: u16) -> RemoteGpioOut {
+        RemoteGpioOut {
+            client: self.clone(),
+            pin,
+        }
+    }
+}
+
+/// An input pin of a server
+#[derive(Debug, Clone)]
+pub struct RemoteGpioIn {
+    client: Client,
+    pin: u16,
+    /// Shared with clones, like the connection
+    last_event: sync::Arc<sync::Mutex<Option<GpioValue>>>,
+}
+
+impl RemoteGpioIn {
+    /// The number of the pin on the server
+    #[inline]
+    pub fn pin(&self) -> u16 {
+        self.pin
+    }
+
+    /// The value sent with the edge last returned for this pin by a `RemoteEdgeIter`
+    ///
+    /// Unlike `read_value` this needs no request, and it is the value right after the edge even
+    /// if the pin has changed again since.
+    #[inline]
+    pub fn last_event(&self) -> Option<GpioValue> {
+        *lock(&self.last_event)
+    }
+}
+
+impl GpioIn for RemoteGpioIn {
+    type Error = RemoteError;
+
+    #[inline]
+    fn read_value(&self) -> Result<GpioValue, Self::Error> {
+        self.client.read(self.pin)
+    }
+
+    /// Subscribe to edges of the pin, which are received through a `RemoteEdgeIter`
+    #[inline]
+    fn set_edge(&mut self, edge: GpioEdge) -> Result<(), Self::Error> {
+        self.client.subscribe(self.pin, edge)
+    }
+}
+
+/// An output pin of a server
+#[derive(Debug, Clone)]
+pub struct RemoteGpioOut {
+    client: Client,
+    pin: u16,
+}
+
+impl RemoteGpioOut {
+    /// The number of the pin on the server
+    #[inline]
+    pub fn pin(&self) -> u16 {
+        self.pin
+    }
+}
+
+impl GpioOut for RemoteGpioOut {
+    type Error = RemoteError;
+
+    #[inline]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.client.write(self.pin, GpioValue::Low)
+    }
+
+    #[inline]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.client.write(self.pin, GpioValue::High)
+    }
+}
+
+/// Iterator over edges of remote pins
+///
+/// Edges are only sent for pins subscribed to with `set_edge`. All pins must belong to the same
+/// connection. Events of pins not added to any iterator are kept until the connection has
+/// received `MAX_QUEUED_EVENTS` newer ones.
+pub struct RemoteEdgeIter<'a> {
+    events: sync::Arc<Events>,
+    devs: Vec<&'a RemoteGpioIn>,
+    timeout: Option<time::Duration>,
+}
+
+impl<'a> fmt::Debug for RemoteEdgeIter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RemoteEdgeIter")
+            .field("devs", &self.devs)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl<'a> RemoteEdgeIter<'a> {
+    /// Create a new iterator without any pins
+    #[inline]
+    pub fn new() -> RemoteResult<RemoteEdgeIter<'a>> {
+        Ok(RemoteEdgeIter {
+            events: Default::default(),
+            devs: Vec::new(),
+            timeout: None,
+        })
+    }
+
+    /// Wait at most `timeout_ms` for an edge on each call of `next`
+    #[inline]
+    pub fn timeout_ms(&mut self, timeout_ms: u64) -> &mut Self {
+        self.timeout = Some(time::Duration::from_millis(timeout_ms));
+        self
+    }
+
+    /// Add a pin to the iterator
+    pub fn add(&mut self, dev: &'a RemoteGpioIn) -> RemoteResult<&mut Self> {
+        let events = &dev.client.conn.events;
+        if self.devs.is_empty() {
+            self.events = events.clone();
+        } else if !sync::Arc::ptr_eq(&self.events, events) {
+            return Err(RemoteError::MixedConnections);
+        }
+        self.devs.push(dev);
+        Ok(self)
+    }
+
+    /// Take the oldest queued event of one of the pins, keeping its value in the pin
+    fn pop(&self, events: &Events) -> Option<&'a RemoteGpioIn> {
+        let mut queue = lock(&events.queue);
+        let (index, dev, value) = queue.iter().enumerate().find_map(|(index, &(pin, value))| {
+            self.devs
+                .iter()
+                .find(|dev| dev.pin == pin)
+                .map(|&dev| (index, dev, value))
+        })?;
+        queue.remove(index);
+        *lock(&dev.last_event) = Some(value);
+        Some(dev)
+    }
+}
+
+impl<'a> Iterator for RemoteEdgeIter<'a> {
+    type Item = Result<&'a RemoteGpioIn, RemoteError>;
+
+    fn next(&mut self) -> Option<Result<&'a RemoteGpioIn, RemoteError>> {
+        let events = self.events.clone();
+        let start = time::Instant::now();
+        loop {
+            let generation = events.notifier.generation();
+            if let Some(dev) = self.pop(&events) {
+                return Some(Ok(dev));
+            }
+            if events.disconnected.load(Ordering::SeqCst) {
+                return Some(Err(RemoteError::Disconnected));
+            }
+            let wait = match self.timeout {
+                Some(timeout) => match timeout.checked_sub(start.elapsed()) {
+                    Some(remaining) if remaining > time::Duration::from_secs(0) => Some(remaining),
+                    _ => return Some(Err(RemoteError::Timeout)),
+                },
+                None => None,
+            };
+            SystemClock.wait(&events.notifier, generation, wait);
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Sharing pins between processes through a daemon
+//!
+//! Only one process can sensibly own a sysfs pin, as dropping it unexports the pin. A `Server`
+//! owns a set of pins and serves them to any number of clients over a Unix domain socket. A
+//! `Client` connects to it and hands out `RemoteGpioIn` and `RemoteGpioOut` pins, which
+//! implement the usual traits, and edges are received through a `RemoteEdgeIter`.
+//!
+//! ## Protocol
+//!
+//! The protocol is line based text. A connection starts with the client announcing the protocol
+//! version it speaks, which the server confirms:
+//!
+//! ```text
+//! > HELLO 1
+//! < OK 1
+//! ```
+//!
+//! Every following request is answered by `OK`, possibly followed by a result, or `ERR` and a
+//! message:
+//!
+//! * `READ PIN` reads an input or the value last written to an output, e.g. `OK 1`
+//! * `WRITE PIN VALUE` writes `0` or `1` to an output
+//! * `EDGE PIN EDGE` subscribes to `rising`, `falling` or `both` edges of an input, `none` ends
+//!   the subscription
+//! * `LIST` lists the pins and their modes, e.g. `OK 17:in 18:out`
+//!
+//! Edges of subscribed pins are sent as `EVENT PIN VALUE` at any time, also between a request
+//! and its response. The server detects edges by polling its inputs.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use gpio::{GpioEdge, GpioIn, GpioOut, GpioValue};
+//! use gpio::dummy::{DummyGpioIn, DummyGpioOut};
+//! use gpio::remote::{Client, RemoteEdgeIter, Server};
+//!
+//! let path = std::env::temp_dir().join(format!("gpio-remote-{}.sock", std::process::id()));
+//! let button = DummyGpioIn::with_value(false);
+//! let server = Server::new()
+//!     .input(17, button.clone())
+//!     .output(18, DummyGpioOut::new(|_| ()))
+//!     .listen(&path)
+//!     .unwrap();
+//!
+//! let client = Client::connect(&path).unwrap();
+//! let mut input = client.input(17);
+//! let mut output = client.output(18);
+//! assert_eq!(input.read_value().unwrap(), GpioValue::Low);
+//! output.set_high().unwrap();
+//! assert_eq!(client.read(18).unwrap(), GpioValue::High);
+//!
+//! input.set_edge(GpioEdge::Rising).unwrap();
+//! let mut edges = RemoteEdgeIter::new().unwrap();
+//! edges.timeout_ms(1000).add(&input).unwrap();
+//! button.set(true);
+//! assert_eq!(edges.next().unwrap().unwrap().last_event(), Some(GpioValue::High));
+//!
+//! server.shutdown();
+//! ```
+
+use std::io;
+use super::{GpioEdge, GpioValue};
+
+mod client;
+mod server;
+
+pub use self::client::{Client, RemoteEdgeIter, RemoteGpioIn, RemoteGpioOut};
+pub use self::server::{Server, ServerHandle};
+
+/// The version of the protocol implemented by `Server` and `Client`
+pub const PROTOCOL_VERSION: u32 = 1;
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum RemoteError {
+        Io(err: io::Error) {
+            from()
+            description("io error")
+            display("I/O error: {}", err)
+            cause(err)
+        }
+        /// The server answered a request with an error
+        Server(message: String) {
+            description("server error")
+            display("server error: {}", message)
+        }
+        /// The server sent something that is not part of the protocol
+        Protocol(line: String) {
+            description("protocol violation")
+            display("unexpected message from server: {}", line)
+        }
+        /// The connection to the server was closed
+        Disconnected {
+            description("disconnected from server")
+        }
+        /// Pins of different connections were added to the same edge iterator
+        MixedConnections {
+            description("edge iterator pins belong to different connections")
+        }
+        /// No edge occurred before the timeout of an edge iterator
+        Timeout {
+            description("timed out waiting for an edge")
+        }
+    }
+}
+
+pub type RemoteResult<T> = Result<T, RemoteError>;
+
+/// Whether a served pin is an input or an output
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PinMode {
+    Input,
+    Output,
+}
+
+fn value_name(value: GpioValue) -> &'static str {
+    match value {
+        GpioValue::Low => "0",
+        GpioValue::High => "1",
+    }
+}
+
+fn parse_value(value: &str) -> Option<GpioValue> {
+    match value {
+        "0" => Some(GpioValue::Low),
+        "1" => Some(GpioValue::High),
+        _ => None,
+    }
+}
+
+fn edge_name(edge: GpioEdge) -> &'static str {
+    match edge {
+        GpioEdge::None => "none",
+        GpioEdge::Rising => "rising",
+        GpioEdge::Falling => "falling",
+        GpioEdge::Both => "both",
+    }
+}
+
+fn parse_edge(edge: &str) -> Option<GpioEdge> {
+    match edge {
+        "none" => Some(GpioEdge::None),
+        "rising" => Some(GpioEdge::Rising),
+        "falling" => Some(GpioEdge::Falling),
+        "both" => Some(GpioEdge::Both),
+        _ => None,
+    }
+}
+
+fn mode_name(mode: PinMode) -> &'static str {
+    match mode {
+        PinMode::Input => "in",
+        PinMode::Output => "out",
+    }
+}
+
+fn parse_mode(mode: &str) -> Option<PinMode> {
+    match mode {
+        "in" => Some(PinMode::Input),
+        "out" => Some(PinMode::Output),
+        _ => None,
+    }
+}
@@ -0,0 +1,368 @@
+//! The daemon side of the protocol
+
+use std::{collections, fmt, fs, io, path, sync, time};
+use std::io::{BufRead, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use super::super::{GpioEdge, GpioIn, GpioOut};
+use super::{mode_name, parse_edge, parse_value, value_name, PinMode, PROTOCOL_VERSION};
+use served::{Gpio, Pin, Service};
+
+/// How often inputs are read to detect edges, unless set with `Server::poll_interval`
+const POLL_INTERVAL: time::Duration = time::Duration::from_millis(1);
+
+/// Events that cannot be sent to a client within this time end its subscriptions
+const WRITE_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+struct Subscription {
+    conn: usize,
+    pin: u16,
+    edge: GpioEdge,
+}
+
+type Writer = sync::Arc<sync::Mutex<UnixStream>>;
+
+#[derive(Default)]
+struct State {
+    pins: collections::BTreeMap<u16, Pin>,
+    subscriptions: Vec<Subscription>,
+    connections: collections::HashMap<usize, Writer>,
+    next_conn: usize,
+}
+
+#[inline]
+fn lock<T>(mutex: &sync::Mutex<T>) -> sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(sync::PoisonError::into_inner)
+}
+
+fn send(writer: &Writer, line: &str) -> io::Result<()> {
+    let mut stream = lock(writer);
+    stream.write_all(line.as_bytes())?;
+    stream.write_all(b"\n")
+}
+
+/// A daemon serving pins to clients connecting through a Unix socket
+///
+/// Pins are added with `input` and `output`, then `listen` starts serving them. Requests for
+/// pins that were not added are answered with an error.
+pub struct Server {
+    pins: collections::BTreeMap<u16, Pin>,
+    poll_interval: time::Duration,
+}
+
+impl fmt::Debug for Server {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("pins", &self.pins.keys().collect::<Vec<_>>())
+            .field("poll_interval", &self.poll_interval)
+            .finish()
+    }
+}
+
+impl Default for Server {
+    fn default() -> Server {
+        Server {
+            pins: collections::BTreeMap::new(),
+            poll_interval: POLL_INTERVAL,
+        }
+    }
+}
+
+impl Server {
+    /// Create a server without any pins
+    #[inline]
+    pub fn new() -> Server {
+        Default::default()
+    }
+
+    /// Serve `gpio` as input `pin`, replacing any pin of the same number
+    pub fn input<P>(mut self, pin: u16, gpio: P) -> Self
+    where
+        P: GpioIn + Send + 'static,
+        P::Error: fmt::Debug,
+    {
+        self.pins.insert(pin, Pin::input(gpio));
+        self
+    }
+
+    /// Serve `gpio` as output `pin`, replacing any pin of the same number
+    ///
+    /// Reading the pin returns the value last written to it. Until then reading fails.
+    pub fn output<P>(mut self, pin: u16, gpio: P) -> Self
+    where
+        P: GpioOut + Send + 'static,
+        P::Error: fmt::Debug,
+    {
+        self.pins.insert(pin, Pin::output(gpio));
+        self
+    }
+
+    /// Read subscribed inputs every `interval` to detect edges
+    ///
+    /// Pulses shorter than the interval may be missed.
+    pub fn poll_interval(mut self, interval: time::Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Start serving the pins on a socket at `path`
+    ///
+    /// A socket left behind by a server that is no longer running is replaced. The socket is
+    /// removed again when the returned handle is dropped.
+    pub fn listen<P: AsRef<path::Path>>(self, path: P) -> io::Result<ServerHandle> {
+        let path = path.as_ref().to_owned();
+        let listener = match UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            Err(ref err) if err.kind() == io::ErrorKind::AddrInUse => {
+                if UnixStream::connect(&path).is_ok() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AddrInUse,
+                        format!("a server is already listening on {}", path.display()),
+                    ));
+                }
+                fs::remove_file(&path)?;
+                UnixListener::bind(&path)?
+            }
+            Err(err) => return Err(err),
+        };
+
+        let shared = sync::Arc::new(Shared {
+            state: sync::Mutex::new(State {
+                pins: self.pins,
+                ..Default::default()
+            }),
+        });
+
+        let service = {
+            let (wake, serve, poll) = (path.clone(), shared.clone(), shared.clone());
+            Service::spawn(
+                listener,
+                move || drop(UnixStream::connect(&wake)),
+                move |stream| drop(serve.serve(stream)),
+                move || poll.poll(),
+                self.poll_interval,
+            )
+        };
+
+        Ok(ServerHandle {
+            path,
+            shared,
+            service,
+        })
+    }
+}
+
+/// State shared by the threads of a running server
+struct Shared {
+    state: sync::Mutex<State>,
+}
+
+impl Shared {
+    /// Read all subscribed inputs and send events for their edges
+    ///
+    /// The events are sent after releasing the state, so a slow client does not hold up the
+    /// requests of the others.
+    fn poll(&self) {
+        let mut events = Vec::new();
+        {
+            let mut state = lock(&self.state);
+            let state = &mut *state;
+            for (&pin, entry) in &mut state.pins {
+                if !state.subscriptions.iter().any(|sub| sub.pin == pin) {
+                    continue;
+                }
+                let value = match entry.poll() {
+                    Some(value) => value,
+                    None => continue,
+                };
+
+                let line = format!("EVENT {} {}", pin, value_name(value));
+                for sub in &state.subscriptions {
+                    if sub.pin != pin || !sub.edge.matches(value) {
+                        continue;
+                    }
+                    if let Some(writer) = state.connections.get(&sub.conn) {
+                        events.push((sub.conn, writer.clone(), line.clone()));
+                    }
+                }
+            }
+        }
+
+        // clients not keeping up with events are disconnected, their threads clean up
+        let mut failed = Vec::new();
+        for (conn, writer, line) in events {
+            if failed.contains(&conn) {
+                continue;
+            }
+            if send(&writer, &line).is_err() {
+                let _ = lock(&writer).shutdown(std::net::Shutdown::Both);
+                failed.push(conn);
+            }
+        }
+    }
+
+    /// Answer a single request with a line starting with `OK` or `ERR`
+    fn request(&self, conn: usize, line: &str) -> Result<String, String> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let pin = |index: usize| -> Result<u16, String> {
+            let field = fields.get(index).ok_or("missing pin")?;
+            field.parse().map_err(|_| format!("invalid pin {}", field))
+        };
+        let mut state = lock(&self.state);
+
+        match fields.first().cloned() {
+            Some("READ") if fields.len() == 2 => {
+                let pin = pin(1)?;
+                let entry = state.pins.get(&pin).ok_or_else(|| unknown(pin))?;
+                let value = match entry.gpio {
+                    Gpio::Input(ref gpio) => gpio.read()?,
+                    Gpio::Output(_) => entry
+                        .value
+                        .ok_or_else(|| format!("pin {} was not written yet", pin))?,
+                };
+                Ok(format!("OK {}", value_name(value)))
+            }
+            Some("WRITE") if fields.len() == 3 => {
+                let pin = pin(1)?;
+                let value = parse_value(fields[2])
+                    .ok_or_else(|| format!("invalid value {}", fields[2]))?;
+                let entry = state.pins.get_mut(&pin).ok_or_else(|| unknown(pin))?;
+                match entry.gpio {
+                    Gpio::Output(ref mut gpio) => gpio.write(value)?,
+                    Gpio::Input(_) => return Err(format!("pin {} is an input", pin)),
+                }
+                entry.value = Some(value);
+                Ok("OK".to_owned())
+            }
+            Some("EDGE") if fields.len() == 3 => {
+                let pin = pin(1)?;
+                let edge =
+                    parse_edge(fields[2]).ok_or_else(|| format!("invalid edge {}", fields[2]))?;
+                let state = &mut *state;
+                let entry = state.pins.get_mut(&pin).ok_or_else(|| unknown(pin))?;
+                let value = match entry.gpio {
+                    Gpio::Input(ref gpio) => gpio.read()?,
+                    Gpio::Output(_) => return Err(format!("pin {} is an output", pin)),
+                };
+                state
+                    .subscriptions
+                    .retain(|sub| sub.conn != conn || sub.pin != pin);
+                if edge != GpioEdge::None {
+                    // edges are detected relative to the value at the time of subscribing
+                    if !state.subscriptions.iter().any(|sub| sub.pin == pin) {
+                        entry.value = Some(value);
+                    }
+                    state.subscriptions.push(Subscription { conn, pin, edge });
+                }
+                Ok("OK".to_owned())
+            }
+            Some("LIST") if fields.len() == 1 => {
+                let pins: Vec<String> = state
+                    .pins
+                    .iter()
+                    .map(|(pin, entry)| {
+                        let mode = match entry.gpio {
+                            Gpio::Input(_) => PinMode::Input,
+                            Gpio::Output(_) => PinMode::Output,
+                        };
+                        format!(" {}:{}", pin, mode_name(mode))
+                    })
+                    .collect();
+                Ok(format!("OK{}", pins.concat()))
+            }
+            Some(command) => Err(format!("invalid request {}", command)),
+            None => Err("empty request".to_owned()),
+        }
+    }
+
+    /// Serve a connection until the client disconnects or the server is shut down
+    fn serve(&self, stream: UnixStream) -> io::Result<()> {
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let writer = sync::Arc::new(sync::Mutex::new(stream.try_clone()?));
+        let mut lines = io::BufReader::new(stream).lines();
+
+        match lines.next().transpose()? {
+            Some(ref line) if line.trim() == format!("HELLO {}", PROTOCOL_VERSION) => {
+                send(&writer, &format!("OK {}", PROTOCOL_VERSION))?
+            }
+            Some(line) => {
+                let version = line.trim().strip_prefix("HELLO ").unwrap_or("");
+                return send(
+                    &writer,
+                    &format!(
+                        "ERR unsupported protocol version {}, supported: {}",
+                        version, PROTOCOL_VERSION
+                    ),
+                );
+            }
+            None => return Ok(()),
+        }
+
+        let conn = {
+            let mut state = lock(&self.state);
+            let conn = state.next_conn;
+            state.next_conn += 1;
+            state.connections.insert(conn, writer.clone());
+            conn
+        };
+        let result = (|| {
+            for line in lines {
+                let response = match self.request(conn, &line?) {
+                    Ok(response) => response,
+                    Err(message) => format!("ERR {}", message),
+                };
+                send(&writer, &response)?;
+            }
+            Ok(())
+        })();
+
+        let mut state = lock(&self.state);
+        state.connections.remove(&conn);
+        state.subscriptions.retain(|sub| sub.conn != conn);
+        result
+    }
+}
+
+fn unknown(pin: u16) -> String {
+    format!("pin {} is not served", pin)
+}
+
+/// A running server, shut down when dropped
+pub struct ServerHandle {
+    path: path::PathBuf,
+    shared: sync::Arc<Shared>,
+    service: Service,
+}
+
+impl fmt::Debug for ServerHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ServerHandle")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl ServerHandle {
+    /// The path of the socket
+    #[inline]
+    pub fn path(&self) -> &path::Path {
+        &self.path
+    }
+
+    /// Disconnect all clients and stop serving
+    ///
+    /// The pins are dropped once all connections are closed.
+    pub fn shutdown(self) {}
+
+}
+
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        let shared = &self.shared;
+        self.service.stop(|| {
+            for writer in lock(&shared.state).connections.values() {
+                let _ = lock(writer).shutdown(std::net::Shutdown::Both);
+            }
+        });
+        let _ = fs::remove_file(&self.path);
+    }
+}
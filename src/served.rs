@@ -0,0 +1,179 @@
+//! Pins served to clients, shared by the remote server and the HTTP gateway
+
+use std::{fmt, io, net, sync, thread, time};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use super::{GpioIn, GpioOut, GpioValue};
+
+/// An input pin of any backend
+pub(crate) trait ServedIn: Send {
+    fn read(&self) -> Result<GpioValue, String>;
+}
+
+impl<P> ServedIn for P
+where
+    P: GpioIn + Send,
+    P::Error: fmt::Debug,
+{
+    fn read(&self) -> Result<GpioValue, String> {
+        self.read_value().map_err(|err| format!("{:?}", err))
+    }
+}
+
+/// An output pin of any backend
+pub(crate) trait ServedOut: Send {
+    fn write(&mut self, value: GpioValue) -> Result<(), String>;
+}
+
+impl<P> ServedOut for P
+where
+    P: GpioOut + Send,
+    P::Error: fmt::Debug,
+{
+    fn write(&mut self, value: GpioValue) -> Result<(), String> {
+        self.set_value(value).map_err(|err| format!("{:?}", err))
+    }
+}
+
+pub(crate) enum Gpio {
+    Input(Box<dyn ServedIn>),
+    Output(Box<dyn ServedOut>),
+}
+
+pub(crate) struct Pin {
+    pub gpio: Gpio,
+    /// The value last polled from an input or written to an output
+    pub value: Option<GpioValue>,
+}
+
+impl Pin {
+    pub fn input<P>(gpio: P) -> Pin
+    where
+        P: GpioIn + Send + 'static,
+        P::Error: fmt::Debug,
+    {
+        Pin {
+            gpio: Gpio::Input(Box::new(gpio)),
+            value: None,
+        }
+    }
+
+    pub fn output<P>(gpio: P) -> Pin
+    where
+        P: GpioOut + Send + 'static,
+        P::Error: fmt::Debug,
+    {
+        Pin {
+            gpio: Gpio::Output(Box::new(gpio)),
+            value: None,
+        }
+    }
+
+    /// Read an input, returning its value if it changed since the last poll
+    pub fn poll(&mut self) -> Option<GpioValue> {
+        let value = match self.gpio {
+            Gpio::Input(ref gpio) => gpio.read().ok()?,
+            Gpio::Output(_) => return None,
+        };
+        match self.value.replace(value) {
+            Some(previous) if previous != value => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// A listening socket of any kind
+pub(crate) trait Listener: Send + 'static {
+    type Stream: Send + 'static;
+
+    fn accept(&self) -> io::Result<Self::Stream>;
+}
+
+impl Listener for UnixListener {
+    type Stream = UnixStream;
+
+    #[inline]
+    fn accept(&self) -> io::Result<Self::Stream> {
+        UnixListener::accept(self).map(|(stream, _)| stream)
+    }
+}
+
+impl Listener for net::TcpListener {
+    type Stream = net::TcpStream;
+
+    #[inline]
+    fn accept(&self) -> io::Result<Self::Stream> {
+        net::TcpListener::accept(self).map(|(stream, _)| stream)
+    }
+}
+
+/// The threads of a running server: one accepting connections and one polling the inputs
+pub(crate) struct Service {
+    stop: sync::Arc<AtomicBool>,
+    wake: Box<dyn Fn() + Send + Sync>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl Service {
+    /// Serve every connection of `listener` on a thread of its own and call `poll` every
+    /// `interval`
+    ///
+    /// `wake` connects to the listener, so the accept thread notices when the service stops.
+    pub fn spawn<L, W, S, P>(
+        listener: L,
+        wake: W,
+        serve: S,
+        mut poll: P,
+        interval: time::Duration,
+    ) -> Service
+    where
+        L: Listener,
+        W: Fn() + Send + Sync + 'static,
+        S: Fn(L::Stream) + Send + Sync + 'static,
+        P: FnMut() + Send + 'static,
+    {
+        let stop = sync::Arc::new(AtomicBool::new(false));
+
+        let accept = {
+            let stop = stop.clone();
+            let serve = sync::Arc::new(serve);
+            thread::spawn(move || loop {
+                let stream = listener.accept();
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let serve = serve.clone();
+                    // a failing connection only affects its own client
+                    thread::spawn(move || serve(stream));
+                }
+            })
+        };
+        let poll = {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    poll();
+                    thread::sleep(interval);
+                }
+            })
+        };
+
+        Service {
+            stop,
+            wake: Box::new(wake),
+            threads: vec![accept, poll],
+        }
+    }
+
+    /// Stop the threads, calling `disconnect` to close the connections of the clients
+    pub fn stop<D: FnOnce()>(&mut self, disconnect: D) {
+        self.stop.store(true, Ordering::SeqCst);
+        // wake up the accept thread, which checks for the stop flag on every connection
+        (self.wake)();
+        disconnect();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
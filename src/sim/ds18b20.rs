@@ -0,0 +1,422 @@
+//! DS18B20 1-Wire temperature sensor
+//!
+//! ## Example
+//!
+//! A bit-banged 1-Wire master reading the temperature:
+//!
+//! ```rust
+//! use gpio::{GpioIn, GpioOut, Pull};
+//! use gpio::clock::{Clock, VirtualClock};
+//! use gpio::sim::{Circuit, Ds18b20, SimGpioOut};
+//! use std::time::Duration;
+//!
+//! struct OneWire {
+//!     dq: SimGpioOut,
+//!     clock: VirtualClock,
+//! }
+//!
+//! impl OneWire {
+//!     fn us(&self, us: u64) {
+//!         self.clock.sleep(Duration::from_micros(us));
+//!     }
+//!
+//!     /// Reset the bus, returns whether a device answered with a presence pulse
+//!     fn reset(&mut self) -> bool {
+//!         self.dq.set_low().unwrap();
+//!         self.us(480);
+//!         self.dq.set_high().unwrap();
+//!         self.us(70);
+//!         let present = self.dq.read_value().unwrap() == gpio::GpioValue::Low;
+//!         self.us(410);
+//!         present
+//!     }
+//!
+//!     fn bit(&mut self, bit: bool) -> bool {
+//!         self.dq.set_low().unwrap();
+//!         self.us(if bit { 6 } else { 60 });
+//!         self.dq.set_high().unwrap();
+//!         self.us(if bit { 9 } else { 10 });
+//!         let read = self.dq.read_value().unwrap().into();
+//!         self.us(if bit { 55 } else { 0 });
+//!         read
+//!     }
+//!
+//!     fn write(&mut self, byte: u8) {
+//!         (0..8).for_each(|i| { self.bit(byte >> i & 1 == 1); });
+//!     }
+//!
+//!     fn read(&mut self) -> u8 {
+//!         (0..8).fold(0, |byte, i| byte | (self.bit(true) as u8) << i)
+//!     }
+//! }
+//!
+//! let circuit = Circuit::new();
+//! let dq = circuit.wire("dq", Pull::Up);
+//! let sensor = Ds18b20::new(dq, 0x0000_0123_4567);
+//! circuit.attach(sensor.clone());
+//! sensor.set_temperature(21.5);
+//!
+//! let mut bus = OneWire { dq: circuit.open_drain(dq), clock: circuit.clock() };
+//!
+//! assert!(bus.reset());
+//! bus.write(0x33);
+//! let rom: Vec<u8> = (0..8).map(|_| bus.read()).collect();
+//! assert_eq!(rom, sensor.rom());
+//!
+//! // convert, the sensor sends zeros until the conversion is done
+//! assert!(bus.reset());
+//! bus.write(0xcc);
+//! bus.write(0x44);
+//! assert!(!bus.bit(true));
+//! bus.us(750_000);
+//! assert!(bus.bit(true));
+//!
+//! assert!(bus.reset());
+//! bus.write(0xcc);
+//! bus.write(0xbe);
+//! let scratchpad: Vec<u8> = (0..9).map(|_| bus.read()).collect();
+//! let raw = i16::from(scratchpad[0]) | i16::from(scratchpad[1]) << 8;
+//! assert_eq!(f32::from(raw) / 16.0, 21.5);
+//! ```
+
+use std::{collections, sync, time};
+use super::super::GpioValue;
+use super::{Bus, Device, Wire};
+
+/// A low pulse of at least this length is a reset, the presence window has the same length
+const RESET: time::Duration = time::Duration::from_micros(480);
+/// Delay between the end of the reset and the presence pulse
+const PRESENCE_WAIT: time::Duration = time::Duration::from_micros(30);
+const PRESENCE: time::Duration = time::Duration::from_micros(120);
+/// Time after the start of a slot the line is sampled, or held low to send a zero
+const SAMPLE: time::Duration = time::Duration::from_micros(30);
+
+/// Timers
+const PRESENCE_START: u32 = 0;
+const RELEASE: u32 = 1;
+const SAMPLE_BIT: u32 = 2;
+const CONVERSION_DONE: u32 = 3;
+
+const FAMILY: u8 = 0x28;
+
+/// Dallas/Maxim CRC-8 as used for the ROM code and the scratchpad
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold((crc, byte), |(crc, byte), _| {
+            let mix = (crc ^ byte) & 1;
+            let crc = crc >> 1;
+            (if mix != 0 { crc ^ 0x8c } else { crc }, byte >> 1)
+        }).0
+    })
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Mode {
+    /// Waiting for a reset, after sending the queued bits
+    Idle,
+    /// Receiving a ROM command
+    Rom,
+    /// Receiving the ROM code to match
+    MatchRom(Vec<u8>),
+    /// Searching, sending bit `bit` of the ROM code, its complement, then receiving the
+    /// direction
+    Search { bit: usize, step: u8 },
+    /// Selected, receiving a function command
+    Function,
+    /// Receiving TH, TL and the configuration
+    WriteScratchpad(Vec<u8>),
+    /// Converting, read slots return whether the conversion is done
+    Convert,
+}
+
+#[derive(Debug)]
+struct State {
+    dq: Wire,
+    rom: [u8; 8],
+    scratchpad: [u8; 9],
+    eeprom: [u8; 3],
+    temperature: f32,
+    mode: Mode,
+    /// Bits to send in the next read slots
+    send: collections::VecDeque<bool>,
+    bits: u8,
+    byte: u8,
+    converting: bool,
+    /// Start of the current low pulse, unless the device pulled the line low itself
+    fall: Option<time::Instant>,
+    /// End of the presence window after a reset, presence pulses of other devices are no slots
+    quiet: Option<time::Instant>,
+    driving: bool,
+    low: bool,
+}
+
+impl State {
+    fn drive(&mut self, bus: &mut Bus, low: bool) {
+        self.driving = low;
+        bus.drive(self.dq, if low { Some(GpioValue::Low) } else { None });
+    }
+
+    /// Queue bytes to send, least significant bit first
+    fn queue(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.send.extend((0..8).map(|i| byte >> i & 1 == 1));
+        }
+    }
+
+    fn rom_bit(&self, bit: usize) -> bool {
+        self.rom[bit / 8] >> (bit % 8) & 1 == 1
+    }
+
+    /// Resolution in bits according to the configuration register
+    fn resolution(&self) -> u8 {
+        9 + (self.scratchpad[4] >> 5 & 0x03)
+    }
+
+    fn update_crc(&mut self) {
+        self.scratchpad[8] = crc8(&self.scratchpad[..8]);
+    }
+
+    /// The master started a time slot
+    fn slot(&mut self, bus: &mut Bus) {
+        match self.mode {
+            Mode::Idle if self.send.is_empty() => (),
+            Mode::Search { bit, step } if step < 2 => {
+                let value = self.rom_bit(bit) ^ (step == 1);
+                self.mode = Mode::Search { bit, step: step + 1 };
+                self.send_bit(bus, value);
+            }
+            Mode::Convert => {
+                let done = !self.converting;
+                self.send_bit(bus, done);
+            }
+            _ => match self.send.pop_front() {
+                Some(bit) => self.send_bit(bus, bit),
+                None => bus.schedule(SAMPLE, SAMPLE_BIT),
+            },
+        }
+    }
+
+    /// Answer a read slot, a zero is sent by holding the line low
+    fn send_bit(&mut self, bus: &mut Bus, bit: bool) {
+        if !bit {
+            self.drive(bus, true);
+            bus.schedule(SAMPLE, RELEASE);
+        }
+    }
+
+    /// A bit written by the master was sampled
+    fn received(&mut self, bus: &mut Bus, bit: bool) {
+        if let Mode::Search { bit: index, .. } = self.mode {
+            self.mode = if bit != self.rom_bit(index) {
+                Mode::Idle
+            } else if index == 63 {
+                Mode::Function
+            } else {
+                Mode::Search { bit: index + 1, step: 0 }
+            };
+            return;
+        }
+
+        self.byte = self.byte >> 1 | (bit as u8) << 7;
+        self.bits += 1;
+        if self.bits == 8 {
+            let byte = self.byte;
+            self.bits = 0;
+            self.byte = 0;
+            self.command(bus, byte);
+        }
+    }
+
+    /// A complete byte was received
+    fn command(&mut self, bus: &mut Bus, byte: u8) {
+        self.mode = match (self.mode.clone(), byte) {
+            (Mode::Rom, 0x33) => {
+                let rom = self.rom;
+                self.queue(&rom);
+                Mode::Function
+            }
+            (Mode::Rom, 0xcc) => Mode::Function,
+            (Mode::Rom, 0x55) => Mode::MatchRom(Vec::new()),
+            (Mode::Rom, 0xf0) => Mode::Search { bit: 0, step: 0 },
+            (Mode::Rom, 0xec) if self.alarm() => Mode::Search { bit: 0, step: 0 },
+            (Mode::MatchRom(mut rom), _) => {
+                rom.push(byte);
+                match rom.len() {
+                    8 if rom[..] == self.rom[..] => Mode::Function,
+                    8 => Mode::Idle,
+                    _ => Mode::MatchRom(rom),
+                }
+            }
+            (Mode::Function, 0x44) => {
+                let duration = time::Duration::from_micros(93_750 << (self.resolution() - 9));
+                self.converting = true;
+                bus.schedule(duration, CONVERSION_DONE);
+                Mode::Convert
+            }
+            (Mode::Function, 0xbe) => {
+                let scratchpad = self.scratchpad;
+                self.queue(&scratchpad);
+                Mode::Idle
+            }
+            (Mode::Function, 0x4e) => Mode::WriteScratchpad(Vec::new()),
+            (Mode::Function, 0x48) => {
+                self.eeprom.copy_from_slice(&self.scratchpad[2..5]);
+                Mode::Idle
+            }
+            (Mode::Function, 0xb8) => {
+                self.scratchpad[2..5].copy_from_slice(&self.eeprom);
+                self.update_crc();
+                Mode::Idle
+            }
+            (Mode::WriteScratchpad(mut data), _) => {
+                data.push(byte);
+                if data.len() < 3 {
+                    Mode::WriteScratchpad(data)
+                } else {
+                    self.scratchpad[2] = data[0];
+                    self.scratchpad[3] = data[1];
+                    self.scratchpad[4] = data[2] & 0x60 | 0x1f;
+                    self.update_crc();
+                    Mode::Idle
+                }
+            }
+            _ => Mode::Idle,
+        };
+    }
+
+    /// Whether the last converted temperature is outside the alarm limits
+    fn alarm(&self) -> bool {
+        let temperature = (i16::from(self.scratchpad[0]) | i16::from(self.scratchpad[1]) << 8) >> 4;
+        let (high, low) = (self.scratchpad[2] as i8, self.scratchpad[3] as i8);
+        temperature >= i16::from(high) || temperature <= i16::from(low)
+    }
+
+    fn convert(&mut self) {
+        let raw = (self.temperature.clamp(-55.0, 125.0) * 16.0).round() as i16;
+        // lower resolutions leave the least significant bits undefined, they read as zero
+        let raw = raw & !((1 << (12 - self.resolution())) - 1);
+        self.scratchpad[0] = raw as u8;
+        self.scratchpad[1] = (raw >> 8) as u8;
+        self.update_crc();
+        self.converting = false;
+    }
+}
+
+/// A DS18B20 1-Wire temperature sensor with external power supply
+///
+/// The sensor answers resets with a presence pulse and understands the ROM commands read (0x33),
+/// match (0x55), skip (0xcc), search (0xf0) and alarm search (0xec) as well as the function
+/// commands convert (0x44), read (0xbe) and write scratchpad (0x4e), copy scratchpad (0x48)
+/// and recall (0xb8). A conversion takes 93.75 ms to 750 ms depending on the configured
+/// resolution; until then the scratchpad keeps the previous temperature, 85 °C after power-up.
+/// Clones refer to the same device.
+#[derive(Debug, Clone)]
+pub struct Ds18b20 {
+    state: sync::Arc<sync::Mutex<State>>,
+}
+
+impl Ds18b20 {
+    /// Create a sensor on `dq` with the 48-bit serial number `serial`
+    pub fn new(dq: Wire, serial: u64) -> Ds18b20 {
+        let mut rom = [0; 8];
+        rom[0] = FAMILY;
+        for (i, byte) in rom[1..7].iter_mut().enumerate() {
+            *byte = (serial >> (8 * i)) as u8;
+        }
+        rom[7] = crc8(&rom[..7]);
+
+        let mut state = State {
+            dq,
+            rom,
+            scratchpad: [0x50, 0x05, 0x4b, 0x46, 0x7f, 0xff, 0x0c, 0x10, 0],
+            eeprom: [0x4b, 0x46, 0x7f],
+            temperature: 85.0,
+            mode: Mode::Idle,
+            send: collections::VecDeque::new(),
+            bits: 0,
+            byte: 0,
+            converting: false,
+            fall: None,
+            quiet: None,
+            driving: false,
+            low: false,
+        };
+        state.update_crc();
+        Ds18b20 {
+            state: sync::Arc::new(sync::Mutex::new(state)),
+        }
+    }
+
+    #[inline]
+    fn state(&self) -> sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// The 64-bit ROM code: family code, serial number and CRC
+    #[inline]
+    pub fn rom(&self) -> Vec<u8> {
+        self.state().rom.to_vec()
+    }
+
+    /// Set the temperature in °C measured by the next conversion
+    #[inline]
+    pub fn set_temperature(&self, celsius: f32) {
+        self.state().temperature = celsius;
+    }
+
+    /// The scratchpad including its CRC
+    #[inline]
+    pub fn scratchpad(&self) -> Vec<u8> {
+        self.state().scratchpad.to_vec()
+    }
+}
+
+impl Device for Ds18b20 {
+    fn wires(&self) -> Vec<Wire> {
+        vec![self.state().dq]
+    }
+
+    fn changed(&mut self, bus: &mut Bus, wire: Wire) {
+        let mut state = self.state();
+        let low = bus.level(wire) == Some(GpioValue::Low);
+        if low == state.low {
+            return;
+        }
+        state.low = low;
+
+        if low {
+            // pulses started by the sensor itself are not slots
+            state.fall = if state.driving { None } else { Some(bus.now()) };
+            let quiet = state.quiet.is_some_and(|until| bus.now() < until);
+            if state.fall.is_some() && !quiet {
+                state.slot(bus);
+            }
+        } else if let Some(fall) = state.fall.take() {
+            if bus.now().duration_since(fall) >= RESET {
+                state.send.clear();
+                state.bits = 0;
+                state.byte = 0;
+                state.mode = Mode::Rom;
+                state.quiet = Some(bus.now() + RESET);
+                bus.schedule(PRESENCE_WAIT, PRESENCE_START);
+            }
+        }
+    }
+
+    fn timer(&mut self, bus: &mut Bus, token: u32) {
+        let mut state = self.state();
+        match token {
+            PRESENCE_START => {
+                state.drive(bus, true);
+                bus.schedule(PRESENCE, RELEASE);
+            }
+            RELEASE => state.drive(bus, false),
+            SAMPLE_BIT => {
+                let bit = bus.is_high(state.dq);
+                state.received(bus, bit);
+            }
+            CONVERSION_DONE => state.convert(),
+            _ => (),
+        }
+    }
+}
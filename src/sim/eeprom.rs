@@ -0,0 +1,377 @@
+//! 24Cxx I2C EEPROM
+//!
+//! ## Example
+//!
+//! A bit-banged I2C master writing two bytes and reading them back:
+//!
+//! ```rust
+//! use gpio::{GpioIn, GpioOut, Pull};
+//! use gpio::clock::Clock;
+//! use gpio::sim::{Circuit, Eeprom24Cxx, SimGpioOut};
+//! use std::time::Duration;
+//!
+//! struct I2c {
+//!     scl: SimGpioOut,
+//!     sda: SimGpioOut,
+//! }
+//!
+//! impl I2c {
+//!     fn start(&mut self) {
+//!         self.sda.set_high().unwrap();
+//!         self.scl.set_high().unwrap();
+//!         self.sda.set_low().unwrap();
+//!         self.scl.set_low().unwrap();
+//!     }
+//!
+//!     fn stop(&mut self) {
+//!         self.sda.set_low().unwrap();
+//!         self.scl.set_high().unwrap();
+//!         self.sda.set_high().unwrap();
+//!     }
+//!
+//!     fn bit(&mut self, bit: bool) -> bool {
+//!         self.sda.set_value(bit).unwrap();
+//!         self.scl.set_high().unwrap();
+//!         let read = self.sda.read_value().unwrap().into();
+//!         self.scl.set_low().unwrap();
+//!         read
+//!     }
+//!
+//!     /// Write a byte, returns whether it was acknowledged
+//!     fn write(&mut self, byte: u8) -> bool {
+//!         for i in (0..8).rev() {
+//!             self.bit(byte >> i & 1 == 1);
+//!         }
+//!         !self.bit(true)
+//!     }
+//!
+//!     fn read(&mut self, ack: bool) -> u8 {
+//!         let byte = (0..8).fold(0, |byte, _| byte << 1 | self.bit(true) as u8);
+//!         self.bit(!ack);
+//!         byte
+//!     }
+//! }
+//!
+//! let circuit = Circuit::new();
+//! let scl = circuit.wire("scl", Pull::Up);
+//! let sda = circuit.wire("sda", Pull::Up);
+//! let eeprom = Eeprom24Cxx::new(scl, sda, 256, 8);
+//! circuit.attach(eeprom.clone());
+//!
+//! let mut i2c = I2c { scl: circuit.open_drain(scl), sda: circuit.open_drain(sda) };
+//!
+//! i2c.start();
+//! assert!(i2c.write(0x50 << 1));
+//! assert!(i2c.write(0x10));
+//! assert!(i2c.write(0xca) && i2c.write(0xfe));
+//! i2c.stop();
+//!
+//! // the write cycle is running, the address is not acknowledged
+//! i2c.start();
+//! assert!(!i2c.write(0x50 << 1));
+//! i2c.stop();
+//! circuit.clock().sleep(Duration::from_millis(5));
+//!
+//! i2c.start();
+//! assert!(i2c.write(0x50 << 1) && i2c.write(0x10));
+//! i2c.start();
+//! assert!(i2c.write(0x50 << 1 | 1));
+//! assert_eq!((i2c.read(true), i2c.read(false)), (0xca, 0xfe));
+//! i2c.stop();
+//!
+//! assert_eq!(eeprom.read(0x10, 2), vec![0xca, 0xfe]);
+//! ```
+
+use std::{sync, time};
+use super::super::GpioValue;
+use super::{Bus, Device, Wire};
+
+/// Time a write cycle takes, the device does not acknowledge its address meanwhile
+const WRITE_CYCLE: time::Duration = time::Duration::from_millis(5);
+
+/// Timer ending the write cycle
+const WRITE_DONE: u32 = 0;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Phase {
+    /// Waiting for a start condition
+    Idle,
+    /// Receiving the device address
+    Address,
+    /// Acknowledging the device address
+    AddressAck { read: bool },
+    /// Receiving the memory address or data
+    Receive,
+    /// Acknowledging a received byte
+    ReceiveAck,
+    /// Sending a byte
+    Send,
+    /// Waiting for the master to acknowledge a sent byte
+    SendAck { acked: bool },
+}
+
+#[derive(Debug)]
+struct State {
+    scl: Wire,
+    sda: Wire,
+    scl_high: bool,
+    sda_high: bool,
+    address: u8,
+    page_size: usize,
+    memory: Vec<u8>,
+    phase: Phase,
+    bits: u8,
+    byte: u8,
+    /// Address bytes received in the current write
+    address_bytes: usize,
+    pointer: usize,
+    /// Written bytes that are committed on the stop condition
+    pending: Vec<(usize, u8)>,
+    busy: bool,
+}
+
+impl State {
+    /// Number of address bytes, smaller parts use bits of the device address instead
+    fn address_len(&self) -> usize {
+        if self.memory.len() > 2048 { 2 } else { 1 }
+    }
+
+    /// Bits of the device address selecting a 256 byte block on smaller parts
+    fn block_bits(&self) -> u32 {
+        if self.address_len() == 2 {
+            0
+        } else {
+            (self.memory.len().max(256) / 256).trailing_zeros()
+        }
+    }
+
+    fn drive(&self, bus: &mut Bus, bit: bool) {
+        bus.drive(self.sda, if bit { None } else { Some(GpioValue::Low) });
+    }
+
+    /// Handle a complete device address
+    fn address(&mut self, bus: &mut Bus) {
+        let shift = self.block_bits();
+        let (address, read) = (self.byte >> 1, self.byte & 1 == 1);
+        if self.busy || address >> shift != self.address >> shift {
+            self.phase = Phase::Idle;
+            return;
+        }
+        if shift > 0 {
+            let block = (address & ((1 << shift) - 1)) as usize;
+            self.pointer = block << 8 | (self.pointer & 0xff);
+        }
+        self.address_bytes = 0;
+        self.drive(bus, false);
+        self.phase = Phase::AddressAck { read };
+    }
+
+    /// Handle a complete byte of a write
+    fn receive(&mut self, bus: &mut Bus) {
+        let byte = self.byte as usize;
+        if self.address_bytes < self.address_len() {
+            self.pointer = match self.address_len() {
+                1 => (self.pointer & !0xff) | byte,
+                _ if self.address_bytes == 0 => byte << 8,
+                _ => self.pointer | byte,
+            } % self.memory.len();
+            self.address_bytes += 1;
+        } else {
+            // writes wrap around within the page
+            let page = self.pointer - self.pointer % self.page_size;
+            self.pending.push((self.pointer, self.byte));
+            self.pointer = page + (self.pointer + 1) % self.page_size;
+        }
+        self.drive(bus, false);
+        self.phase = Phase::ReceiveAck;
+    }
+
+    /// Start sending the next byte, beginning with its most significant bit
+    fn send(&mut self, bus: &mut Bus) {
+        self.byte = self.memory[self.pointer];
+        self.pointer = (self.pointer + 1) % self.memory.len();
+        self.bits = 0;
+        let bit = self.byte & 0x80 != 0;
+        self.drive(bus, bit);
+        self.phase = Phase::Send;
+    }
+
+    fn scl_rising(&mut self, bus: &mut Bus) {
+        let sda = bus.is_high(self.sda);
+        match self.phase {
+            Phase::Address | Phase::Receive => {
+                self.byte = self.byte << 1 | sda as u8;
+                self.bits += 1;
+            }
+            Phase::Send => self.bits += 1,
+            Phase::SendAck { .. } => self.phase = Phase::SendAck { acked: !sda },
+            _ => (),
+        }
+    }
+
+    fn scl_falling(&mut self, bus: &mut Bus) {
+        match self.phase {
+            Phase::Address if self.bits == 8 => self.address(bus),
+            Phase::Receive if self.bits == 8 => self.receive(bus),
+            Phase::AddressAck { read: true } => self.send(bus),
+            Phase::AddressAck { read: false } | Phase::ReceiveAck => {
+                self.drive(bus, true);
+                self.bits = 0;
+                self.byte = 0;
+                self.phase = Phase::Receive;
+            }
+            Phase::Send if self.bits < 8 => {
+                let bit = self.byte << self.bits & 0x80 != 0;
+                self.drive(bus, bit);
+            }
+            Phase::Send => {
+                self.drive(bus, true);
+                self.phase = Phase::SendAck { acked: false };
+            }
+            Phase::SendAck { acked: true } => self.send(bus),
+            Phase::SendAck { acked: false } => self.phase = Phase::Idle,
+            _ => (),
+        }
+    }
+
+    fn start(&mut self, bus: &mut Bus) {
+        // only a stop condition starts the write cycle
+        self.pending.clear();
+        self.drive(bus, true);
+        self.phase = Phase::Address;
+        self.bits = 0;
+        self.byte = 0;
+    }
+
+    fn stop(&mut self, bus: &mut Bus) {
+        self.commit(bus);
+        self.drive(bus, true);
+        self.phase = Phase::Idle;
+    }
+
+    /// Write the pending bytes, starting a write cycle
+    fn commit(&mut self, bus: &mut Bus) {
+        if self.pending.is_empty() {
+            return;
+        }
+        for (address, byte) in self.pending.drain(..) {
+            self.memory[address] = byte;
+        }
+        self.busy = true;
+        bus.schedule(WRITE_CYCLE, WRITE_DONE);
+    }
+}
+
+/// An I2C EEPROM of the 24Cxx family
+///
+/// Byte and page writes, current address, random and sequential reads are supported. Parts up
+/// to 2 KiB (24C01 to 24C16) take a single address byte and use the low bits of the device
+/// address to select a 256 byte block, larger ones take two address bytes. Written bytes are
+/// committed on the stop condition and discarded on a repeated start. Committing starts a write
+/// cycle during which the device does not acknowledge its address. Clones refer to the same
+/// device.
+#[derive(Debug, Clone)]
+pub struct Eeprom24Cxx {
+    state: sync::Arc<sync::Mutex<State>>,
+}
+
+impl Eeprom24Cxx {
+    /// Create an erased EEPROM of `size` bytes at address 0x50, writes wrap within `page_size`
+    /// bytes
+    ///
+    /// Both `size` and `page_size` must be powers of two.
+    pub fn new(scl: Wire, sda: Wire, size: usize, page_size: usize) -> Eeprom24Cxx {
+        assert!(size.is_power_of_two(), "EEPROM size must be a power of two");
+        assert!(
+            page_size.is_power_of_two() && page_size <= size,
+            "EEPROM page size must be a power of two not larger than the EEPROM"
+        );
+        Eeprom24Cxx {
+            state: sync::Arc::new(sync::Mutex::new(State {
+                scl,
+                sda,
+                scl_high: true,
+                sda_high: true,
+                address: 0x50,
+                page_size,
+                memory: vec![0xff; size],
+                phase: Phase::Idle,
+                bits: 0,
+                byte: 0,
+                address_bytes: 0,
+                pointer: 0,
+                pending: Vec::new(),
+                busy: false,
+            })),
+        }
+    }
+
+    #[inline]
+    fn state(&self) -> sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// Set the 7-bit device address, as selected by the A0 to A2 pins
+    pub fn address(self, address: u8) -> Self {
+        self.state().address = address & 0x7f;
+        self
+    }
+
+    /// Size in bytes
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.state().memory.len()
+    }
+
+    /// Read `len` bytes of the memory starting at `offset`
+    pub fn read(&self, offset: usize, len: usize) -> Vec<u8> {
+        self.state().memory[offset..offset + len].to_vec()
+    }
+
+    /// Overwrite the memory starting at `offset`, e.g. to prepare its contents for a test
+    pub fn write(&self, offset: usize, data: &[u8]) {
+        self.state().memory[offset..offset + data.len()].copy_from_slice(data)
+    }
+
+    /// Whether a write cycle is running
+    #[inline]
+    pub fn is_busy(&self) -> bool {
+        self.state().busy
+    }
+}
+
+impl Device for Eeprom24Cxx {
+    fn wires(&self) -> Vec<Wire> {
+        let state = self.state();
+        vec![state.scl, state.sda]
+    }
+
+    fn changed(&mut self, bus: &mut Bus, wire: Wire) {
+        let mut state = self.state();
+        let (scl, sda) = (bus.is_high(state.scl), bus.is_high(state.sda));
+
+        if wire == state.sda && sda != state.sda_high {
+            state.sda_high = sda;
+            // data changing while the clock is high are start and stop conditions
+            if state.scl_high && scl {
+                if sda {
+                    state.stop(bus);
+                } else {
+                    state.start(bus);
+                }
+            }
+        }
+        if wire == state.scl && scl != state.scl_high {
+            state.scl_high = scl;
+            if scl {
+                state.scl_rising(bus);
+            } else {
+                state.scl_falling(bus);
+            }
+        }
+    }
+
+    fn timer(&mut self, _bus: &mut Bus, _token: u32) {
+        self.state().busy = false;
+    }
+}
@@ -0,0 +1,304 @@
+//! 25-series SPI NOR flash
+//!
+//! ## Example
+//!
+//! A bit-banged SPI master (mode 0) programming a page:
+//!
+//! ```rust
+//! use gpio::{GpioIn, GpioOut, Pull};
+//! use gpio::clock::Clock;
+//! use gpio::sim::{Circuit, SimGpioIn, SimGpioOut, SpiFlash};
+//! use std::time::Duration;
+//!
+//! struct Spi {
+//!     cs: SimGpioOut,
+//!     sck: SimGpioOut,
+//!     mosi: SimGpioOut,
+//!     miso: SimGpioIn,
+//! }
+//!
+//! impl Spi {
+//!     fn transfer(&mut self, data: &[u8]) -> Vec<u8> {
+//!         self.cs.set_low().unwrap();
+//!         let read = data
+//!             .iter()
+//!             .map(|&byte| {
+//!                 (0..8).rev().fold(0, |read, i| {
+//!                     self.mosi.set_value(byte >> i & 1).unwrap();
+//!                     self.sck.set_high().unwrap();
+//!                     let bit: u8 = self.miso.read_value().unwrap().into();
+//!                     self.sck.set_low().unwrap();
+//!                     read << 1 | bit
+//!                 })
+//!             })
+//!             .collect();
+//!         self.cs.set_high().unwrap();
+//!         read
+//!     }
+//! }
+//!
+//! let circuit = Circuit::new();
+//! let (cs, sck) = (circuit.wire("cs", Pull::Up), circuit.wire("sck", Pull::Down));
+//! let (mosi, miso) = (circuit.wire("mosi", Pull::Down), circuit.wire("miso", Pull::Up));
+//! let flash = SpiFlash::new(cs, sck, mosi, miso, 1 << 20).jedec_id([0xef, 0x40, 0x14]);
+//! circuit.attach(flash.clone());
+//!
+//! let mut spi = Spi {
+//!     cs: circuit.output(cs),
+//!     sck: circuit.output(sck),
+//!     mosi: circuit.output(mosi),
+//!     miso: circuit.input(miso),
+//! };
+//! spi.cs.set_high().unwrap();
+//! spi.sck.set_low().unwrap();
+//!
+//! assert_eq!(spi.transfer(&[0x9f, 0, 0, 0])[1..], [0xef, 0x40, 0x14]);
+//!
+//! // write enable, then program
+//! spi.transfer(&[0x06]);
+//! spi.transfer(&[0x02, 0x00, 0x01, 0x00, 0x12, 0x34]);
+//! assert_eq!(spi.transfer(&[0x05, 0])[1] & 1, 1);
+//! circuit.clock().sleep(Duration::from_millis(1));
+//! assert_eq!(spi.transfer(&[0x05, 0])[1], 0);
+//!
+//! assert_eq!(spi.transfer(&[0x03, 0x00, 0x01, 0x00, 0, 0, 0])[4..], [0x12, 0x34, 0xff]);
+//! assert_eq!(flash.read(0x100, 2), vec![0x12, 0x34]);
+//! ```
+
+use std::{sync, time};
+use super::super::GpioValue;
+use super::{Bus, Device, Wire};
+
+const PAGE_SIZE: usize = 256;
+const SECTOR_SIZE: usize = 4096;
+const BLOCK_SIZE: usize = 65536;
+
+/// Times of the operations setting the busy bit, typical values of common parts
+const PROGRAM_TIME: time::Duration = time::Duration::from_millis(1);
+const SECTOR_ERASE_TIME: time::Duration = time::Duration::from_millis(50);
+const BLOCK_ERASE_TIME: time::Duration = time::Duration::from_millis(200);
+const CHIP_ERASE_TIME: time::Duration = time::Duration::from_secs(5);
+
+/// Timer ending a program or erase operation
+const DONE: u32 = 0;
+
+/// Status register bits
+const STATUS_BUSY: u8 = 0x01;
+const STATUS_WEL: u8 = 0x02;
+
+#[derive(Debug)]
+struct State {
+    cs: Wire,
+    sck: Wire,
+    mosi: Wire,
+    miso: Wire,
+    jedec_id: [u8; 3],
+    memory: Vec<u8>,
+    selected: bool,
+    sck_high: bool,
+    /// Bits of the current input byte
+    bits: u8,
+    byte: u8,
+    /// Bytes received since chip select was asserted
+    received: Vec<u8>,
+    /// Byte being sent and the number of its bits driven so far
+    out: u8,
+    out_bits: u8,
+    status: u8,
+}
+
+impl State {
+    fn address(&self) -> usize {
+        let a = &self.received[1..4];
+        ((a[0] as usize) << 16 | (a[1] as usize) << 8 | a[2] as usize) % self.memory.len()
+    }
+
+    fn busy(&self) -> bool {
+        self.status & STATUS_BUSY != 0
+    }
+
+    /// The byte to send after `received` bytes were received
+    fn response(&self) -> u8 {
+        let n = self.received.len();
+        match self.received[0] {
+            0x05 => self.status,
+            _ if self.busy() => 0xff,
+            0x9f => self.jedec_id.get(n - 1).cloned().unwrap_or(0xff),
+            0x03 if n >= 4 => self.memory[(self.address() + n - 4) % self.memory.len()],
+            0x0b if n >= 5 => self.memory[(self.address() + n - 5) % self.memory.len()],
+            _ => 0xff,
+        }
+    }
+
+    /// Execute the command received once chip select is released
+    fn execute(&mut self, bus: &mut Bus) {
+        if self.received.is_empty() || self.busy() {
+            return;
+        }
+        let enabled = self.status & STATUS_WEL != 0;
+        let n = self.received.len();
+        let duration = match self.received[0] {
+            0x06 if n == 1 => {
+                self.status |= STATUS_WEL;
+                None
+            }
+            0x04 if n == 1 => {
+                self.status &= !STATUS_WEL;
+                None
+            }
+            0x02 if enabled && n > 4 => {
+                // programming can only clear bits and wraps around within the page
+                let address = self.address();
+                let page = address - address % PAGE_SIZE;
+                for (i, &byte) in self.received[4..].iter().enumerate() {
+                    let address = page + (address + i) % PAGE_SIZE;
+                    self.memory[address] &= byte;
+                }
+                Some(PROGRAM_TIME)
+            }
+            0x20 if enabled && n == 4 => Some(self.erase(SECTOR_SIZE, SECTOR_ERASE_TIME)),
+            0xd8 if enabled && n == 4 => Some(self.erase(BLOCK_SIZE, BLOCK_ERASE_TIME)),
+            0xc7 | 0x60 if enabled && n == 1 => {
+                self.memory.iter_mut().for_each(|b| *b = 0xff);
+                Some(CHIP_ERASE_TIME)
+            }
+            _ => None,
+        };
+        if let Some(duration) = duration {
+            self.status |= STATUS_BUSY;
+            bus.schedule(duration, DONE);
+        }
+    }
+
+    fn erase(&mut self, size: usize, duration: time::Duration) -> time::Duration {
+        let start = self.address() - self.address() % size;
+        let end = (start + size).min(self.memory.len());
+        self.memory[start..end].iter_mut().for_each(|b| *b = 0xff);
+        duration
+    }
+}
+
+/// A SPI NOR flash of the 25 series, e.g. W25Q or MX25L
+///
+/// The device works in SPI mode 0 and understands the common commands: read (0x03), fast read
+/// (0x0b), JEDEC ID (0x9f), read status (0x05), write enable (0x06) and disable (0x04), page
+/// program (0x02), 4 KiB sector (0x20), 64 KiB block (0xd8) and chip erase (0xc7 or 0x60).
+/// Programming and erasing require a preceding write enable, take time during which the busy bit
+/// of the status register is set and reset the write enable latch when done. MISO is only driven
+/// while the chip is selected. Clones refer to the same device.
+#[derive(Debug, Clone)]
+pub struct SpiFlash {
+    state: sync::Arc<sync::Mutex<State>>,
+}
+
+impl SpiFlash {
+    /// Create an erased flash of `size` bytes
+    pub fn new(cs: Wire, sck: Wire, mosi: Wire, miso: Wire, size: usize) -> SpiFlash {
+        assert!(size > 0, "flash size must not be zero");
+        SpiFlash {
+            state: sync::Arc::new(sync::Mutex::new(State {
+                cs,
+                sck,
+                mosi,
+                miso,
+                jedec_id: [0xef, 0x40, 0x00],
+                memory: vec![0xff; size],
+                selected: false,
+                sck_high: false,
+                bits: 0,
+                byte: 0,
+                received: Vec::new(),
+                out: 0xff,
+                out_bits: 8,
+                status: 0,
+            })),
+        }
+    }
+
+    #[inline]
+    fn state(&self) -> sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// Set the manufacturer, memory type and capacity returned by the JEDEC ID command
+    pub fn jedec_id(self, id: [u8; 3]) -> Self {
+        self.state().jedec_id = id;
+        self
+    }
+
+    /// Size in bytes
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.state().memory.len()
+    }
+
+    /// Read `len` bytes of the memory starting at `offset`
+    pub fn read(&self, offset: usize, len: usize) -> Vec<u8> {
+        self.state().memory[offset..offset + len].to_vec()
+    }
+
+    /// Overwrite the memory starting at `offset`, e.g. to prepare its contents for a test
+    pub fn write(&self, offset: usize, data: &[u8]) {
+        self.state().memory[offset..offset + data.len()].copy_from_slice(data)
+    }
+
+    /// The status register
+    #[inline]
+    pub fn status(&self) -> u8 {
+        self.state().status
+    }
+}
+
+impl Device for SpiFlash {
+    fn wires(&self) -> Vec<Wire> {
+        let state = self.state();
+        vec![state.cs, state.sck]
+    }
+
+    fn changed(&mut self, bus: &mut Bus, wire: Wire) {
+        let mut state = self.state();
+
+        if wire == state.cs {
+            let selected = bus.level(wire) == Some(GpioValue::Low);
+            if selected && !state.selected {
+                state.received.clear();
+                state.bits = 0;
+                state.byte = 0;
+                state.out_bits = 8;
+            } else if !selected && state.selected {
+                bus.drive(state.miso, None);
+                state.execute(bus);
+            }
+            state.selected = selected;
+        }
+
+        if wire == state.sck {
+            let high = bus.is_high(wire);
+            let (rising, falling) = (high && !state.sck_high, !high && state.sck_high);
+            state.sck_high = high;
+            if !state.selected {
+                return;
+            }
+            if rising {
+                state.byte = state.byte << 1 | bus.is_high(state.mosi) as u8;
+                state.bits += 1;
+                if state.bits == 8 {
+                    let byte = state.byte;
+                    state.received.push(byte);
+                    state.out = state.response();
+                    state.out_bits = 0;
+                    state.bits = 0;
+                    state.byte = 0;
+                }
+            } else if falling && state.out_bits < 8 {
+                let bit = state.out << state.out_bits & 0x80 != 0;
+                state.out_bits += 1;
+                bus.drive(state.miso, Some(bit.into()));
+            }
+        }
+    }
+
+    fn timer(&mut self, _bus: &mut Bus, _token: u32) {
+        self.state().status &= !(STATUS_BUSY | STATUS_WEL);
+    }
+}
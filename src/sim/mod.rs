@@ -0,0 +1,620 @@
+//! Simulated circuits
+//!
+//! A `Circuit` connects pins to simulated devices using named wires. Host pins created by the
+//! circuit implement `GpioIn` and `GpioOut`, so any driver can talk to the devices as if they
+//! were real hardware. Devices react to the transitions of their wires and may schedule timers,
+//! e.g. to answer a reset pulse after a delay or to stay busy while an erase is running.
+//!
+//! Time is discrete and virtual: the circuit owns a `VirtualClock` that drivers sleep on. Timers
+//! that expired while the clock was advanced are run in order before every access of a host
+//! pin, so a bit-banged protocol sees the same timing as on real hardware without taking any
+//! real time.
+//!
+//! Models of common parts are included:
+//!
+//! * `Eeprom24Cxx`, an I2C EEPROM of the 24Cxx family
+//! * `SpiFlash`, a SPI NOR flash understanding the common 25-series commands
+//! * `Ds18b20`, a 1-Wire temperature sensor
+//! * `ShiftRegister`, a 74HC595 serial-in, parallel-out shift register
+//!
+//! Further devices are added by implementing `Device`.
+//!
+//! Host inputs report edges through a dummy pin following their wire. A `DummyEdgeIter` using
+//! the circuit as its clock runs the devices' timers while it waits, so an edge caused by a
+//! device is seen at its exact virtual time:
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use gpio::{GpioEdge, GpioIn, GpioOut, Pull};
+//! use gpio::clock::Clock;
+//! use gpio::dummy::DummyEdgeIter;
+//! use gpio::sim::{Circuit, Ds18b20};
+//!
+//! let circuit = Circuit::new();
+//! let dq = circuit.wire("dq", Pull::Up);
+//! circuit.attach(Ds18b20::new(dq, 0x0000_0123_4567));
+//!
+//! // a reset pulse is answered with a presence pulse
+//! let mut master = circuit.open_drain(dq);
+//! master.set_low().unwrap();
+//! circuit.sleep(Duration::from_micros(480));
+//! master.set_high().unwrap();
+//!
+//! let mut presence = circuit.input(dq);
+//! presence.set_edge(GpioEdge::Falling).unwrap();
+//! let mut edges = DummyEdgeIter::with_clock(circuit.clone()).unwrap();
+//! edges.timeout_ms(1).add(presence.dummy()).unwrap();
+//!
+//! let released = circuit.now();
+//! assert!(edges.next().unwrap().is_ok());
+//! let delay = circuit.now() - released;
+//! assert!(delay >= Duration::from_micros(15) && delay <= Duration::from_micros(60));
+//! ```
+//!
+//! ## Example
+//!
+//! ```rust
+//! use gpio::{GpioOut, Pull};
+//! use gpio::sim::{Circuit, ShiftRegister};
+//!
+//! let circuit = Circuit::new();
+//! let ser = circuit.wire("ser", Pull::None);
+//! let srclk = circuit.wire("srclk", Pull::None);
+//! let rclk = circuit.wire("rclk", Pull::None);
+//!
+//! let leds = ShiftRegister::new(ser, srclk, rclk);
+//! circuit.attach(leds.clone());
+//!
+//! let (mut data, mut clock, mut latch) =
+//!     (circuit.output(ser), circuit.output(srclk), circuit.output(rclk));
+//! latch.set_low().unwrap();
+//! clock.set_low().unwrap();
+//! for bit in (0..8).rev() {
+//!     data.set_value(0b1010_0110u8 >> bit & 1).unwrap();
+//!     clock.set_high().unwrap();
+//!     clock.set_low().unwrap();
+//! }
+//! assert_eq!(leds.outputs(), 0);
+//! latch.set_high().unwrap();
+//! assert_eq!(leds.outputs(), 0b1010_0110);
+//! ```
+
+use std::{cmp, collections, fmt, sync, time};
+use super::{GpioEdge, GpioIn, GpioOut, GpioValue, Pull};
+use clock::{Clock, Notifier, VirtualClock};
+use dummy::{DummyGpioIn, NetError, Resolution};
+
+mod ds18b20;
+mod eeprom;
+mod flash;
+mod shift;
+
+pub use self::ds18b20::Ds18b20;
+pub use self::eeprom::Eeprom24Cxx;
+pub use self::flash::SpiFlash;
+pub use self::shift::ShiftRegister;
+
+/// Settling a change must not take more steps than this, otherwise the circuit oscillates
+const MAX_STEPS: usize = 100_000;
+
+quick_error! {
+    /// Errors of host pins of a circuit
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum SimError {
+        /// The wire is driven high and low at the same time
+        Conflict(wire: String) {
+            description("wire is driven high and low at the same time")
+            display("wire {} is driven high and low at the same time", wire)
+        }
+        /// The wire is neither driven nor pulled
+        Floating(wire: String) {
+            description("wire is neither driven nor pulled")
+            display("wire {} is neither driven nor pulled", wire)
+        }
+        /// Devices keep changing wires in response to each other, the circuit does not settle
+        Oscillating {
+            description("simulated circuit does not settle")
+            display("simulated circuit does not settle")
+        }
+    }
+}
+
+/// A wire of a circuit
+///
+/// Wires are created by `Circuit::wire` and passed to devices and host pins to connect them.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Wire(usize);
+
+/// Something driving a wire
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Driver {
+    Host(usize),
+    Device(usize),
+}
+
+#[derive(Debug)]
+struct WireState {
+    name: String,
+    resolution: Resolution,
+    pull: Pull,
+    drivers: Vec<(Driver, GpioValue)>,
+    level: Result<GpioValue, NetError>,
+    /// Follows the level of the wire, waking up edge iterators of host inputs
+    pin: DummyGpioIn,
+}
+
+impl WireState {
+    #[inline]
+    fn resolve(&self) -> Result<GpioValue, NetError> {
+        self.resolution.resolve(self.pull, self.drivers.iter().map(|&(_, v)| v))
+    }
+
+    fn error(&self, err: NetError) -> SimError {
+        match err {
+            NetError::Conflict => SimError::Conflict(self.name.clone()),
+            NetError::Floating => SimError::Floating(self.name.clone()),
+        }
+    }
+}
+
+/// A timer of a device, ordered by expiry and then by the order it was scheduled in
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
+struct Timer {
+    at: time::Instant,
+    seq: u64,
+    device: usize,
+    token: u32,
+}
+
+/// Everything but the devices, so devices can be called while it is borrowed
+#[derive(Debug)]
+struct Board {
+    wires: Vec<WireState>,
+    timers: collections::BinaryHeap<cmp::Reverse<Timer>>,
+    seq: u64,
+    /// Wires whose level changed and whose devices have not been notified yet
+    changed: collections::VecDeque<Wire>,
+    now: time::Instant,
+    conflicts: usize,
+}
+
+impl Board {
+    /// Set the value `driver` drives `wire` with, `None` to stop driving it
+    fn drive(&mut self, wire: Wire, driver: Driver, value: Option<GpioValue>) {
+        let state = &mut self.wires[wire.0];
+        state.drivers.retain(|&(d, _)| d != driver);
+        if let Some(value) = value {
+            state.drivers.push((driver, value));
+        }
+        let level = state.resolve();
+        if level != state.level {
+            if level == Err(NetError::Conflict) {
+                self.conflicts += 1;
+            }
+            state.level = level;
+            if let Ok(value) = level {
+                state.pin.set(value);
+            }
+            self.changed.push_back(wire);
+        }
+    }
+}
+
+struct Sim {
+    board: Board,
+    devices: Vec<Box<dyn Device>>,
+    hosts: usize,
+}
+
+impl Sim {
+    /// Run all timers due until `now` and propagate all changes
+    ///
+    /// Changes still pending when the circuit oscillates are propagated by the next call.
+    fn settle(&mut self, now: time::Instant) -> Result<(), SimError> {
+        let mut steps = 0;
+        loop {
+            steps += 1;
+            if steps >= MAX_STEPS {
+                return Err(SimError::Oscillating);
+            }
+
+            if let Some(wire) = self.board.changed.pop_front() {
+                for (index, device) in self.devices.iter_mut().enumerate() {
+                    if device.wires().contains(&wire) {
+                        device.changed(&mut Bus { board: &mut self.board, device: index }, wire);
+                    }
+                }
+                continue;
+            }
+
+            if self.board.timers.peek().is_none_or(|timer| timer.0.at > now) {
+                break;
+            }
+            let cmp::Reverse(timer) = self.board.timers.pop().expect("peeked timer");
+            self.board.now = cmp::max(self.board.now, timer.at);
+            self.devices[timer.device]
+                .timer(&mut Bus { board: &mut self.board, device: timer.device }, timer.token);
+        }
+        self.board.now = cmp::max(self.board.now, now);
+        Ok(())
+    }
+}
+
+/// The view of a circuit a device has while it reacts to a change or timer
+pub struct Bus<'a> {
+    board: &'a mut Board,
+    device: usize,
+}
+
+impl<'a> fmt::Debug for Bus<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Bus")
+            .field("now", &self.board.now)
+            .field("device", &self.device)
+            .finish()
+    }
+}
+
+impl<'a> Bus<'a> {
+    /// Current simulation time
+    #[inline]
+    pub fn now(&self) -> time::Instant {
+        self.board.now
+    }
+
+    /// Level of `wire`, `None` if it is floating or driven high and low at the same time
+    #[inline]
+    pub fn level(&self, wire: Wire) -> Option<GpioValue> {
+        self.board.wires[wire.0].level.ok()
+    }
+
+    /// Whether `wire` is high
+    #[inline]
+    pub fn is_high(&self, wire: Wire) -> bool {
+        self.level(wire) == Some(GpioValue::High)
+    }
+
+    /// Drive `wire` with `value`, `None` releases it
+    ///
+    /// Devices are notified of the resulting change after the current call returns, including
+    /// the device driving the wire.
+    #[inline]
+    pub fn drive(&mut self, wire: Wire, value: Option<GpioValue>) {
+        self.board.drive(wire, Driver::Device(self.device), value)
+    }
+
+    /// Call `Device::timer` with `token` after `delay`
+    ///
+    /// Timers with the same expiry run in the order they were scheduled in; a delay of zero runs
+    /// the timer after all devices have seen the current change.
+    pub fn schedule(&mut self, delay: time::Duration, token: u32) {
+        self.board.seq += 1;
+        let timer = Timer {
+            at: self.board.now + delay,
+            seq: self.board.seq,
+            device: self.device,
+            token,
+        };
+        self.board.timers.push(cmp::Reverse(timer));
+    }
+}
+
+/// A simulated device
+pub trait Device: Send {
+    /// The wires the device is connected to, it is only notified of changes of these
+    fn wires(&self) -> Vec<Wire>;
+
+    /// The level of `wire` changed
+    fn changed(&mut self, bus: &mut Bus, wire: Wire);
+
+    /// A timer scheduled using `Bus::schedule` expired
+    fn timer(&mut self, bus: &mut Bus, token: u32) {
+        let _ = (bus, token);
+    }
+}
+
+/// A circuit of wires, devices and host pins
+///
+/// Clones refer to the same circuit and all handles can be sent to other threads.
+#[derive(Clone)]
+pub struct Circuit {
+    sim: sync::Arc<sync::Mutex<Sim>>,
+    clock: VirtualClock,
+}
+
+impl fmt::Debug for Circuit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sim = self.sim();
+        f.debug_struct("Circuit")
+            .field("wires", &sim.board.wires)
+            .field("devices", &sim.devices.len())
+            .field("elapsed", &self.clock.elapsed())
+            .finish()
+    }
+}
+
+impl Circuit {
+    /// Create an empty circuit with a new virtual clock
+    #[inline]
+    pub fn new() -> Circuit {
+        Self::with_clock(VirtualClock::new())
+    }
+
+    /// Create an empty circuit running on `clock`
+    pub fn with_clock(clock: VirtualClock) -> Circuit {
+        Circuit {
+            sim: sync::Arc::new(sync::Mutex::new(Sim {
+                board: Board {
+                    wires: Vec::new(),
+                    timers: collections::BinaryHeap::new(),
+                    seq: 0,
+                    changed: collections::VecDeque::new(),
+                    now: clock.now(),
+                    conflicts: 0,
+                },
+                devices: Vec::new(),
+                hosts: 0,
+            })),
+            clock,
+        }
+    }
+
+    #[inline]
+    fn sim(&self) -> sync::MutexGuard<'_, Sim> {
+        self.sim.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// Lock the simulation and bring it up to the current time
+    fn settled(&self) -> Result<sync::MutexGuard<'_, Sim>, SimError> {
+        let mut sim = self.sim();
+        sim.settle(self.clock.now())?;
+        Ok(sim)
+    }
+
+    /// The clock of the circuit, drivers sleep on it to let time pass
+    #[inline]
+    pub fn clock(&self) -> VirtualClock {
+        self.clock.clone()
+    }
+
+    /// Let `duration` pass and run all timers expiring in the meantime
+    pub fn advance(&self, duration: time::Duration) -> Result<(), SimError> {
+        self.clock.advance(duration);
+        self.sim().settle(self.clock.now())
+    }
+
+    /// Add a wire whose drivers are resolved as wired-AND, i.e. any low driver pulls it low
+    ///
+    /// This matches open-drain buses like I2C and 1-Wire, for push-pull signals a conflict
+    /// between drivers can be detected using `wire_with`.
+    #[inline]
+    pub fn wire(&self, name: &str, pull: Pull) -> Wire {
+        self.wire_with(name, pull, Resolution::WiredAnd)
+    }
+
+    /// Add a wire resolving its drivers using `resolution`
+    pub fn wire_with(&self, name: &str, pull: Pull, resolution: Resolution) -> Wire {
+        let mut sim = self.sim();
+        let mut state = WireState {
+            name: name.to_owned(),
+            resolution,
+            pull,
+            drivers: Vec::new(),
+            level: Err(NetError::Floating),
+            pin: DummyGpioIn::with_value(GpioValue::Low),
+        };
+        state.level = state.resolve();
+        if let Ok(value) = state.level {
+            state.pin.set(value);
+        }
+        sim.board.wires.push(state);
+        Wire(sim.board.wires.len() - 1)
+    }
+
+    /// Add a device to the circuit
+    pub fn attach<D: Device + 'static>(&self, device: D) {
+        self.sim().devices.push(Box::new(device));
+    }
+
+    /// Current level of `wire`
+    pub fn level(&self, wire: Wire) -> Result<GpioValue, SimError> {
+        let sim = self.settled()?;
+        let state = &sim.board.wires[wire.0];
+        state.level.map_err(|err| state.error(err))
+    }
+
+    /// Number of times a wire was driven high and low at the same time
+    pub fn conflicts(&self) -> Result<usize, SimError> {
+        Ok(self.settled()?.board.conflicts)
+    }
+
+    fn host(&self, wire: Wire, open_drain: bool) -> SimGpioOut {
+        let mut sim = self.sim();
+        sim.hosts += 1;
+        SimGpioOut {
+            circuit: self.clone(),
+            wire,
+            driver: Driver::Host(sim.hosts),
+            open_drain,
+        }
+    }
+
+    /// A push-pull output driving `wire`
+    #[inline]
+    pub fn output(&self, wire: Wire) -> SimGpioOut {
+        self.host(wire, false)
+    }
+
+    /// An open-drain output, it drives `wire` low and releases it when set high
+    ///
+    /// Reading the output returns the level of the wire, as needed for open-drain protocols.
+    #[inline]
+    pub fn open_drain(&self, wire: Wire) -> SimGpioOut {
+        self.host(wire, true)
+    }
+
+    /// An input reading `wire`
+    pub fn input(&self, wire: Wire) -> SimGpioIn {
+        SimGpioIn {
+            circuit: self.clone(),
+            wire,
+            pin: self.sim().board.wires[wire.0].pin.clone(),
+        }
+    }
+
+    /// The time of the next timer, if any
+    fn next_timer(&self) -> Option<time::Instant> {
+        self.sim().board.timers.peek().map(|timer| timer.0.at)
+    }
+}
+
+/// The circuit's time, waiting on it runs the devices' timers
+///
+/// Sleeping advances the clock like `advance`. Waiting advances it from timer to timer until
+/// `notifier` is notified, e.g. by an edge of a host input, or the timeout has passed. Pass the
+/// circuit to `DummyEdgeIter::with_clock` to wait for edges caused by devices.
+impl Clock for Circuit {
+    #[inline]
+    fn now(&self) -> time::Instant {
+        self.clock.now()
+    }
+
+    #[inline]
+    fn sleep(&self, duration: time::Duration) {
+        // an oscillating circuit is reported by the next access of a host pin
+        self.advance(duration).ok();
+    }
+
+    fn wait(&self, notifier: &Notifier, generation: u64, timeout: Option<time::Duration>) {
+        let deadline = timeout.map(|timeout| self.now() + timeout);
+        while notifier.generation() == generation {
+            let now = self.now();
+            let next = self
+                .next_timer()
+                .filter(|&at| deadline.is_none_or(|deadline| at <= deadline));
+            let advanced = match next {
+                Some(at) => self.advance(at.saturating_duration_since(now)).is_ok(),
+                None => false,
+            };
+            if !advanced {
+                // no timer is due in time or the circuit oscillates, let the rest pass
+                let rest = deadline.map(|deadline| deadline.saturating_duration_since(self.now()));
+                return self.clock.wait(notifier, generation, rest);
+            }
+        }
+    }
+}
+
+impl Default for Circuit {
+    #[inline]
+    fn default() -> Circuit {
+        Circuit::new()
+    }
+}
+
+/// Host input of a circuit
+///
+/// Edges are waited for using a `DummyEdgeIter` on the pin returned by `dummy`, running on the
+/// circuit as its clock. Only changes between high and low are edges, a wire that floats or has
+/// conflicting drivers keeps the level it had before.
+#[derive(Debug, Clone)]
+pub struct SimGpioIn {
+    circuit: Circuit,
+    wire: Wire,
+    pin: DummyGpioIn,
+}
+
+impl SimGpioIn {
+    /// A dummy pin following the wire, with the edge setting of this input
+    #[inline]
+    pub fn dummy(&self) -> &DummyGpioIn {
+        &self.pin
+    }
+}
+
+impl GpioIn for SimGpioIn {
+    type Error = SimError;
+
+    #[inline]
+    fn read_value(&self) -> Result<GpioValue, Self::Error> {
+        self.circuit.level(self.wire)
+    }
+
+    /// Set the edges reported for the pin returned by `dummy`
+    #[inline]
+    fn set_edge(&mut self, edge: GpioEdge) -> Result<(), Self::Error> {
+        // dummy pins only fail with injected faults
+        self.pin.set_edge(edge).ok();
+        Ok(())
+    }
+}
+
+/// Host output of a circuit
+///
+/// The output stops driving its wire when dropped.
+#[derive(Debug)]
+pub struct SimGpioOut {
+    circuit: Circuit,
+    wire: Wire,
+    driver: Driver,
+    open_drain: bool,
+}
+
+impl SimGpioOut {
+    fn write(&mut self, value: Option<GpioValue>) -> Result<(), SimError> {
+        let mut sim = self.circuit.settled()?;
+        sim.board.drive(self.wire, self.driver, value);
+        let now = sim.board.now;
+        sim.settle(now)?;
+
+        let state = &sim.board.wires[self.wire.0];
+        match state.level {
+            Err(NetError::Conflict) => Err(state.error(NetError::Conflict)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Stop driving the wire
+    #[inline]
+    pub fn release(&mut self) -> Result<(), SimError> {
+        self.write(None)
+    }
+}
+
+impl GpioOut for SimGpioOut {
+    type Error = SimError;
+
+    #[inline]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.write(Some(GpioValue::Low))
+    }
+
+    #[inline]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        if self.open_drain {
+            self.write(None)
+        } else {
+            self.write(Some(GpioValue::High))
+        }
+    }
+}
+
+impl GpioIn for SimGpioOut {
+    type Error = SimError;
+
+    #[inline]
+    fn read_value(&self) -> Result<GpioValue, Self::Error> {
+        self.circuit.level(self.wire)
+    }
+
+    #[inline]
+    fn set_edge(&mut self, _edge: GpioEdge) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl Drop for SimGpioOut {
+    fn drop(&mut self) {
+        let _ = self.release();
+    }
+}
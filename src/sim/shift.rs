@@ -0,0 +1,106 @@
+//! 74HC595 shift register
+
+use std::{sync, time};
+use super::super::GpioValue;
+use super::{Bus, Device, Wire};
+
+/// Timer updating the serial output after the clock edge, so chained registers sample the old bit
+const SERIAL_OUT: u32 = 0;
+
+#[derive(Debug)]
+struct State {
+    ser: Wire,
+    srclk: Wire,
+    rclk: Wire,
+    serial_out: Option<Wire>,
+    srclk_high: bool,
+    rclk_high: bool,
+    shift: u8,
+    outputs: u8,
+}
+
+/// A 74HC595 serial-in, parallel-out shift register
+///
+/// The bit on `ser` is shifted in on rising edges of `srclk` and the shifted byte is latched to
+/// the outputs on rising edges of `rclk`. Registers are chained by connecting the serial output
+/// of one to `ser` of the next. Clones refer to the same register.
+#[derive(Debug, Clone)]
+pub struct ShiftRegister {
+    state: sync::Arc<sync::Mutex<State>>,
+}
+
+impl ShiftRegister {
+    /// Create a register shifting in `ser` on `srclk` and latching on `rclk`
+    pub fn new(ser: Wire, srclk: Wire, rclk: Wire) -> ShiftRegister {
+        ShiftRegister {
+            state: sync::Arc::new(sync::Mutex::new(State {
+                ser,
+                srclk,
+                rclk,
+                serial_out: None,
+                srclk_high: false,
+                rclk_high: false,
+                shift: 0,
+                outputs: 0,
+            })),
+        }
+    }
+
+    #[inline]
+    fn state(&self) -> sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// Drive the last stage (QH') onto `wire`, e.g. to chain another register
+    pub fn serial_out(self, wire: Wire) -> Self {
+        self.state().serial_out = Some(wire);
+        self
+    }
+
+    /// The latched outputs, QA is the least significant bit
+    #[inline]
+    pub fn outputs(&self) -> u8 {
+        self.state().outputs
+    }
+
+    /// The contents of the shift stages that have not been latched yet
+    #[inline]
+    pub fn shifted(&self) -> u8 {
+        self.state().shift
+    }
+}
+
+impl Device for ShiftRegister {
+    fn wires(&self) -> Vec<Wire> {
+        let state = self.state();
+        vec![state.ser, state.srclk, state.rclk]
+    }
+
+    fn changed(&mut self, bus: &mut Bus, wire: Wire) {
+        let mut state = self.state();
+        if wire == state.srclk {
+            let high = bus.is_high(wire);
+            if high && !state.srclk_high {
+                state.shift = state.shift << 1 | bus.is_high(state.ser) as u8;
+                if state.serial_out.is_some() {
+                    bus.schedule(time::Duration::from_secs(0), SERIAL_OUT);
+                }
+            }
+            state.srclk_high = high;
+        }
+        if wire == state.rclk {
+            let high = bus.is_high(wire);
+            if high && !state.rclk_high {
+                state.outputs = state.shift;
+            }
+            state.rclk_high = high;
+        }
+    }
+
+    fn timer(&mut self, bus: &mut Bus, _token: u32) {
+        let state = self.state();
+        if let Some(wire) = state.serial_out {
+            bus.drive(wire, Some(GpioValue::from(state.shift >> 7)));
+        }
+    }
+}
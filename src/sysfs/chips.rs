@@ -0,0 +1,155 @@
+//! GPIO controllers of the sysfs interface
+//!
+//! The kernel numbers the GPIOs of all GPIO controllers, or chips, consecutively. Every chip has
+//! a directory `gpiochipN` in the sysfs tree, with the kernel number of its first GPIO as `base`,
+//! its number of GPIOs as `ngpio` and the name of its driver or device as `label`. Bases depend
+//! on the order the chips are registered in and change between kernels, so a pin is better
+//! identified by the label of its chip and its offset within the chip.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use std::fs;
+//! use gpio::sysfs::KernelTree;
+//! use gpio::sysfs::chips;
+//!
+//! // a fixture of a Raspberry Pi 4 with a kernel numbering GPIOs from 512
+//! let root = std::env::temp_dir().join(format!("gpio-chips-{}", std::process::id()));
+//! for &(name, base, ngpio, label) in &[
+//!     ("gpiochip512", "512", "58", "pinctrl-bcm2711"),
+//!     ("gpiochip570", "570", "8", "raspberrypi-exp-gpio"),
+//! ] {
+//!     fs::create_dir_all(root.join(name)).unwrap();
+//!     fs::write(root.join(name).join("base"), format!("{}\n", base)).unwrap();
+//!     fs::write(root.join(name).join("ngpio"), format!("{}\n", ngpio)).unwrap();
+//!     fs::write(root.join(name).join("label"), format!("{}\n", label)).unwrap();
+//! }
+//!
+//! let tree = KernelTree::at(&root);
+//! let chips = chips::list_at(&tree).unwrap();
+//! assert_eq!(chips.len(), 2);
+//! assert_eq!(chips[1].label(), "raspberrypi-exp-gpio");
+//!
+//! let bcm = chips::find_at(&tree, "pinctrl-bcm2711").unwrap();
+//! assert_eq!(bcm.gpio_num(17).unwrap(), 529);
+//! assert_eq!(bcm.offset(529), Some(17));
+//! assert!(bcm.gpio_num(58).is_err());
+//! # fs::remove_dir_all(root).unwrap();
+//! ```
+
+use std::str;
+use super::{GpioError, GpioResult, KernelTree, SysFsTree};
+
+/// A GPIO controller
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct GpioChip {
+    name: String,
+    base: u16,
+    ngpio: u16,
+    label: String,
+}
+
+impl GpioChip {
+    /// Read the attributes of the chip `name`, e.g. `gpiochip0`, in `tree`
+    pub fn open_at(tree: &dyn SysFsTree, name: &str) -> GpioResult<GpioChip> {
+        let attribute = |attribute: &str| -> GpioResult<String> {
+            let data = tree.read(&format!("{}/{}", name, attribute))?;
+            match str::from_utf8(&data) {
+                Ok(text) => Ok(text.trim_end_matches('\n').to_owned()),
+                Err(_) => Err(GpioError::InvalidChip(name.to_owned())),
+            }
+        };
+        let number = |attribute_name: &str| -> GpioResult<u16> {
+            attribute(attribute_name)?
+                .parse()
+                .map_err(|_| GpioError::InvalidChip(name.to_owned()))
+        };
+
+        Ok(GpioChip {
+            name: name.to_owned(),
+            base: number("base")?,
+            ngpio: number("ngpio")?,
+            label: attribute("label")?,
+        })
+    }
+
+    /// The name of the chip's directory, e.g. `gpiochip0`
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The kernel number of the first GPIO
+    #[inline]
+    pub fn base(&self) -> u16 {
+        self.base
+    }
+
+    /// The number of GPIOs
+    #[inline]
+    pub fn ngpio(&self) -> u16 {
+        self.ngpio
+    }
+
+    /// The label, usually the name of the driver or the device
+    #[inline]
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// The kernel number of line `offset`
+    ///
+    /// Fails with `InvalidChip` if the number is beyond the range of kernel numbers, which only a
+    /// chip with a bogus `base` or `ngpio` can cause.
+    pub fn gpio_num(&self, offset: u16) -> GpioResult<u16> {
+        if offset >= self.ngpio {
+            return Err(GpioError::InvalidOffset(self.label.clone(), offset));
+        }
+        self.base
+            .checked_add(offset)
+            .ok_or_else(|| GpioError::InvalidChip(self.name.clone()))
+    }
+
+    /// The offset of kernel number `gpio_num`, unless it belongs to another chip
+    pub fn offset(&self, gpio_num: u16) -> Option<u16> {
+        gpio_num
+            .checked_sub(self.base)
+            .filter(|&offset| offset < self.ngpio)
+    }
+}
+
+/// All GPIO chips, sorted by base
+#[inline]
+pub fn list() -> GpioResult<Vec<GpioChip>> {
+    list_at(&KernelTree::new())
+}
+
+/// All GPIO chips in `tree`, sorted by base
+pub fn list_at(tree: &dyn SysFsTree) -> GpioResult<Vec<GpioChip>> {
+    let mut chips = tree
+        .list()?
+        .iter()
+        .filter(|name| name.starts_with("gpiochip"))
+        .map(|name| GpioChip::open_at(tree, name))
+        .collect::<GpioResult<Vec<_>>>()?;
+    chips.sort_by_key(GpioChip::base);
+    Ok(chips)
+}
+
+/// The GPIO chip labeled `label`, which has to be the only one of that label
+#[inline]
+pub fn find(label: &str) -> GpioResult<GpioChip> {
+    find_at(&KernelTree::new(), label)
+}
+
+/// The GPIO chip labeled `label` in `tree`, which has to be the only one of that label
+pub fn find_at(tree: &dyn SysFsTree, label: &str) -> GpioResult<GpioChip> {
+    let mut chips = list_at(tree)?
+        .into_iter()
+        .filter(|chip| chip.label == label);
+    match (chips.next(), chips.next()) {
+        (Some(chip), None) => Ok(chip),
+        (Some(_), Some(_)) => Err(GpioError::AmbiguousChip(label.to_owned())),
+        (None, _) => Err(GpioError::UnknownChip(label.to_owned())),
+    }
+}
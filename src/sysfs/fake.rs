@@ -0,0 +1,591 @@
+//! Simulated sysfs GPIO tree
+//!
+//! `FakeSysFs` behaves like the kernel's GPIO sysfs interface, so the `sysfs` backend can be
+//! tested without hardware, including its error paths: pins have to be exported before their
+//! attributes exist, invalid writes fail with `EINVAL`, exporting a pin twice or a pin claimed by
+//! a driver fails with `EBUSY`, writing the value of an input fails with `EPERM` and open value
+//! files of unexported pins fail with `ENODEV`.
+//!
+//! The kernel signals edges with `EPOLLPRI` on the `value` files. Regular files cannot be polled,
+//! so a pipe stands in for the value file's poll state: it becomes readable on every edge and is
+//! drained whenever the value is read. Like the kernel's, a freshly opened value file reports an
+//! event until it is read for the first time.
+//!
+//! The state of the tree is mirrored to a temporary directory, which is removed when the tree
+//! and all pins opened in it are dropped.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use std::thread;
+//! use std::time::Duration;
+//! use gpio::{GpioEdge, GpioIn, GpioOut, GpioValue};
+//! use gpio::sysfs::{GpioError, SysFsGpioEdgeIter, SysFsGpioInput, SysFsGpioOutput, SysFsTree};
+//! use gpio::sysfs::fake::FakeSysFs;
+//!
+//! let fake = FakeSysFs::new(32).unwrap();
+//!
+//! let mut led = SysFsGpioOutput::open_at(fake.clone(), 27).unwrap();
+//! led.set_high().unwrap();
+//! assert_eq!(fake.level(27), GpioValue::High);
+//! assert_eq!(fake.attribute(27, "direction").unwrap(), "out");
+//!
+//! // pins claimed by a driver cannot be exported
+//! fake.claim(4);
+//! match SysFsGpioInput::open_at(fake.clone(), 4) {
+//!     Err(GpioError::Io(ref err)) if err.raw_os_error() == Some(16) => (), // EBUSY
+//!     other => panic!("unexpected result: {:?}", other),
+//! }
+//!
+//! let mut button = SysFsGpioInput::open_at(fake.clone(), 17).unwrap();
+//! button.set_edge(GpioEdge::Rising).unwrap();
+//! // clear the initial event of the freshly opened value file
+//! assert_eq!(button.read_value().unwrap(), GpioValue::Low);
+//!
+//! let presser = {
+//!     let fake = fake.clone();
+//!     thread::spawn(move || {
+//!         thread::sleep(Duration::from_millis(10));
+//!         fake.drive(17, GpioValue::High);
+//!     })
+//! };
+//!
+//! let mut edges = SysFsGpioEdgeIter::new().unwrap();
+//! edges.timeout_ms(1000).add(&button).unwrap();
+//! assert_eq!(edges.next().unwrap().unwrap().gpio_num(), 17);
+//! assert_eq!(button.read_value().unwrap(), GpioValue::High);
+//! presser.join().unwrap();
+//!
+//! // closing a pin unexports it
+//! drop(button);
+//! assert!(!fake.is_exported(17));
+//!
+//! // pins exported by someone else are attached to as they are configured
+//! fake.write("export", b"22").unwrap();
+//! fake.write("gpio22/active_low", b"1").unwrap();
+//! let switch = SysFsGpioInput::attach_at(fake.clone(), 22).unwrap();
+//! assert!(switch.info().unwrap().active_low);
+//! assert_eq!(switch.read_value().unwrap(), GpioValue::High);
+//! drop(switch);
+//! assert!(fake.is_exported(22));
+//! ```
+
+use nix::{fcntl, libc, unistd};
+use nix::sys::epoll::EpollFlags;
+use std::{collections, fs, io, path, process, str, sync};
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{self, AtomicUsize};
+use super::{SysFsTree, SysFsValue};
+use super::super::{GpioEdge, GpioValue};
+
+static TREE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+const ATTRIBUTES: [&str; 4] = ["active_low", "direction", "edge", "value"];
+
+/// The single GPIO chip of a tree, which has all lines
+const CHIP: &str = "gpiochip0";
+
+/// The label of the chip
+pub const CHIP_LABEL: &str = "gpio-fake";
+
+#[inline]
+fn errno(code: i32) -> io::Error {
+    io::Error::from_raw_os_error(code)
+}
+
+/// Parse an integer the way the kernel's `kstrtol` does
+fn parse_int(data: &str) -> io::Result<i64> {
+    let data = data.trim_end_matches('\n');
+    if let Some(hex) = data.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else {
+        data.parse()
+    }
+    .map_err(|_| errno(libc::EINVAL))
+}
+
+/// The stand-in for the poll state of a value file
+#[derive(Debug)]
+struct Pipe {
+    read: RawFd,
+    write: RawFd,
+}
+
+impl Pipe {
+    fn new() -> io::Result<Pipe> {
+        let (read, write) = unistd::pipe2(fcntl::OFlag::O_NONBLOCK | fcntl::OFlag::O_CLOEXEC)
+            .map_err(|_| io::Error::last_os_error())?;
+        Ok(Pipe { read, write })
+    }
+
+    #[inline]
+    fn notify(&self) {
+        // a full pipe already signals an event
+        unistd::write(self.write, &[0]).ok();
+    }
+
+    fn drain(&self) {
+        let mut buf = [0; 64];
+        while let Ok(n) = unistd::read(self.read, &mut buf) {
+            if n == 0 {
+                break;
+            }
+        }
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        unistd::close(self.read).ok();
+        unistd::close(self.write).ok();
+    }
+}
+
+/// An exported pin
+#[derive(Debug)]
+struct Pin {
+    /// Identifies the export, value files of earlier exports are stale
+    id: usize,
+    output: bool,
+    active_low: bool,
+    edge: GpioEdge,
+    watchers: Vec<sync::Weak<Pipe>>,
+}
+
+#[derive(Debug)]
+struct State {
+    root: path::PathBuf,
+    ngpio: u16,
+    exports: usize,
+    /// Physical levels of all lines, exported or not
+    levels: collections::HashMap<u16, GpioValue>,
+    /// Every level set on a line, oldest first
+    history: collections::HashMap<u16, Vec<GpioValue>>,
+    claimed: collections::HashSet<u16>,
+    pins: collections::BTreeMap<u16, Pin>,
+}
+
+impl State {
+    #[inline]
+    fn level(&self, gpio_num: u16) -> GpioValue {
+        self.levels
+            .get(&gpio_num)
+            .cloned()
+            .unwrap_or(GpioValue::Low)
+    }
+
+    /// The value of an exported pin, as read from its value file
+    fn value(&self, gpio_num: u16) -> GpioValue {
+        let level = self.level(gpio_num);
+        match self.pins.get(&gpio_num) {
+            Some(pin) if pin.active_low => invert(level),
+            _ => level,
+        }
+    }
+
+    fn chip_attribute(&self, name: &str) -> Option<String> {
+        match name {
+            "base" => Some("0".to_owned()),
+            "ngpio" => Some(self.ngpio.to_string()),
+            "label" => Some(CHIP_LABEL.to_owned()),
+            _ => None,
+        }
+    }
+
+    fn attribute(&self, gpio_num: u16, name: &str) -> Option<String> {
+        let pin = self.pins.get(&gpio_num)?;
+        Some(match name {
+            "active_low" => if pin.active_low { "1" } else { "0" }.to_owned(),
+            "direction" => if pin.output { "out" } else { "in" }.to_owned(),
+            "edge" => match pin.edge {
+                GpioEdge::None => "none",
+                GpioEdge::Rising => "rising",
+                GpioEdge::Falling => "falling",
+                GpioEdge::Both => "both",
+            }.to_owned(),
+            "value" => u8::from(self.value(gpio_num)).to_string(),
+            _ => return None,
+        })
+    }
+
+    /// Write the attributes of a pin to the mirror directory, best effort
+    fn mirror(&self, gpio_num: u16) {
+        let dir = self.root.join(format!("gpio{}", gpio_num));
+        if !self.pins.contains_key(&gpio_num) {
+            fs::remove_dir_all(dir).ok();
+            return;
+        }
+        fs::create_dir_all(&dir).ok();
+        for name in &ATTRIBUTES {
+            if let Some(content) = self.attribute(gpio_num, name) {
+                fs::write(dir.join(name), content + "\n").ok();
+            }
+        }
+    }
+
+    /// Change the physical level of a line, notifying value files on edges
+    fn set_level(&mut self, gpio_num: u16, level: GpioValue) {
+        let old = self.value(gpio_num);
+        self.levels.insert(gpio_num, level);
+        self.history.entry(gpio_num).or_default().push(level);
+        let new = self.value(gpio_num);
+
+        if let Some(pin) = self.pins.get_mut(&gpio_num) {
+            if old != new && !pin.output && pin.edge.matches(new) {
+                pin.watchers.retain(|watcher| match watcher.upgrade() {
+                    Some(pipe) => {
+                        pipe.notify();
+                        true
+                    }
+                    None => false,
+                });
+            }
+        }
+        self.mirror(gpio_num);
+    }
+
+    fn pin_num(&self, data: &str) -> io::Result<u16> {
+        match parse_int(data)? {
+            n if n >= 0 && n < i64::from(self.ngpio) => Ok(n as u16),
+            _ => Err(errno(libc::EINVAL)),
+        }
+    }
+
+    fn export(&mut self, data: &str) -> io::Result<()> {
+        let gpio_num = self.pin_num(data)?;
+        if self.claimed.contains(&gpio_num) || self.pins.contains_key(&gpio_num) {
+            return Err(errno(libc::EBUSY));
+        }
+        self.exports += 1;
+        self.pins.insert(
+            gpio_num,
+            Pin {
+                id: self.exports,
+                output: false,
+                active_low: false,
+                edge: GpioEdge::None,
+                watchers: Vec::new(),
+            },
+        );
+        self.mirror(gpio_num);
+        Ok(())
+    }
+
+    fn unexport(&mut self, data: &str) -> io::Result<()> {
+        let gpio_num = self.pin_num(data)?;
+        self.pins
+            .remove(&gpio_num)
+            .ok_or_else(|| errno(libc::EINVAL))?;
+        self.mirror(gpio_num);
+        Ok(())
+    }
+
+    fn write_value(&mut self, gpio_num: u16, data: &str) -> io::Result<()> {
+        if !self.pins[&gpio_num].output {
+            return Err(errno(libc::EPERM));
+        }
+        let value = GpioValue::from(parse_int(data)? != 0);
+        let level = if self.pins[&gpio_num].active_low {
+            invert(value)
+        } else {
+            value
+        };
+        self.set_level(gpio_num, level);
+        Ok(())
+    }
+
+    fn write_attribute(&mut self, gpio_num: u16, name: &str, data: &str) -> io::Result<()> {
+        let pin = self
+            .pins
+            .get_mut(&gpio_num)
+            .ok_or_else(|| errno(libc::ENOENT))?;
+
+        match name {
+            "direction" => {
+                // raw levels, like the kernel
+                let level = match data.trim_end_matches('\n') {
+                    "in" => None,
+                    "out" | "low" => Some(GpioValue::Low),
+                    "high" => Some(GpioValue::High),
+                    _ => return Err(errno(libc::EINVAL)),
+                };
+                match level {
+                    // lines used as interrupts cannot be outputs
+                    Some(_) if pin.edge != GpioEdge::None => return Err(errno(libc::EIO)),
+                    Some(level) => {
+                        pin.output = true;
+                        self.set_level(gpio_num, level);
+                    }
+                    None => pin.output = false,
+                }
+            }
+            "edge" => {
+                let edge = match data.trim_end_matches('\n') {
+                    "none" => GpioEdge::None,
+                    "rising" => GpioEdge::Rising,
+                    "falling" => GpioEdge::Falling,
+                    "both" => GpioEdge::Both,
+                    _ => return Err(errno(libc::EINVAL)),
+                };
+                if pin.output && edge != GpioEdge::None {
+                    return Err(errno(libc::EIO));
+                }
+                pin.edge = edge;
+            }
+            "active_low" => pin.active_low = parse_int(data)? != 0,
+            "value" => return self.write_value(gpio_num, data),
+            _ => return Err(errno(libc::ENOENT)),
+        }
+        self.mirror(gpio_num);
+        Ok(())
+    }
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.root).ok();
+    }
+}
+
+#[inline]
+fn invert(value: GpioValue) -> GpioValue {
+    match value {
+        GpioValue::Low => GpioValue::High,
+        GpioValue::High => GpioValue::Low,
+    }
+}
+
+/// The attribute of the chip in `gpiochip0/attribute`
+fn chip_path(path: &str) -> Option<&str> {
+    path.strip_prefix(CHIP)?.strip_prefix('/')
+}
+
+/// Split `gpioN/attribute` into the pin number and the attribute
+fn split_path(path: &str) -> Option<(u16, Option<&str>)> {
+    let path = path.trim_matches('/');
+    let (dir, name) = match path.find('/') {
+        Some(split) => (&path[..split], Some(&path[split + 1..])),
+        None => (path, None),
+    };
+    Some((dir.strip_prefix("gpio")?.parse().ok()?, name))
+}
+
+/// A simulated GPIO sysfs tree with `ngpio` lines
+///
+/// Clones refer to the same tree. The lines are not connected to anything, their levels are
+/// controlled through `drive`. Lines keep their level while not exported. All lines belong to a
+/// single chip `gpiochip0`, labeled `CHIP_LABEL`.
+#[derive(Debug, Clone)]
+pub struct FakeSysFs {
+    state: sync::Arc<sync::Mutex<State>>,
+}
+
+impl FakeSysFs {
+    /// Create a tree with the lines `0` to `ngpio - 1`, all low and unexported
+    pub fn new(ngpio: u16) -> io::Result<FakeSysFs> {
+        let root = ::std::env::temp_dir().join(format!(
+            "gpio-fake-sysfs-{}-{}",
+            process::id(),
+            TREE_COUNT.fetch_add(1, atomic::Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&root)?;
+        fs::write(root.join("export"), "")?;
+        fs::write(root.join("unexport"), "")?;
+        fs::create_dir_all(root.join(CHIP))?;
+        fs::write(root.join(CHIP).join("base"), "0\n")?;
+        fs::write(root.join(CHIP).join("ngpio"), format!("{}\n", ngpio))?;
+        fs::write(root.join(CHIP).join("label"), format!("{}\n", CHIP_LABEL))?;
+
+        Ok(FakeSysFs {
+            state: sync::Arc::new(sync::Mutex::new(State {
+                root,
+                ngpio,
+                exports: 0,
+                levels: collections::HashMap::new(),
+                history: collections::HashMap::new(),
+                claimed: collections::HashSet::new(),
+                pins: collections::BTreeMap::new(),
+            })),
+        })
+    }
+
+    #[inline]
+    fn state(&self) -> sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    /// The directory the tree is mirrored to
+    pub fn path(&self) -> path::PathBuf {
+        self.state().root.clone()
+    }
+
+    /// Mark a line as used by a kernel driver, exporting it fails with `EBUSY`
+    pub fn claim(&self, gpio_num: u16) {
+        self.state().claimed.insert(gpio_num);
+    }
+
+    /// Drive a line from the outside
+    ///
+    /// The level is only visible while the line is not an output, edges are signalled to open
+    /// value files according to the pin's edge setting.
+    pub fn drive(&self, gpio_num: u16, level: GpioValue) {
+        let mut state = self.state();
+        let is_output = state.pins.get(&gpio_num).is_some_and(|p| p.output);
+        if !is_output {
+            state.set_level(gpio_num, level);
+        }
+    }
+
+    /// The physical level of a line
+    pub fn level(&self, gpio_num: u16) -> GpioValue {
+        self.state().level(gpio_num)
+    }
+
+    /// Every level a line was set to by an output or `drive`, oldest first
+    ///
+    /// Also lists levels that did not change the line, e.g. an output driven low by writing
+    /// `out` to its direction before its value is set.
+    pub fn history(&self, gpio_num: u16) -> Vec<GpioValue> {
+        self.state()
+            .history
+            .get(&gpio_num)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Whether a line is exported
+    pub fn is_exported(&self, gpio_num: u16) -> bool {
+        self.state().pins.contains_key(&gpio_num)
+    }
+
+    /// The content of an attribute of an exported pin, without the trailing newline
+    pub fn attribute(&self, gpio_num: u16, name: &str) -> Option<String> {
+        self.state().attribute(gpio_num, name)
+    }
+}
+
+impl SysFsTree for FakeSysFs {
+    fn exists(&self, path: &str) -> bool {
+        let state = self.state();
+        match path.trim_matches('/') {
+            "" | "export" | "unexport" | CHIP => true,
+            path if path.starts_with(CHIP) => chip_path(path)
+                .and_then(|name| state.chip_attribute(name))
+                .is_some(),
+            path => match split_path(path) {
+                Some((gpio_num, None)) => state.pins.contains_key(&gpio_num),
+                Some((gpio_num, Some(name))) => {
+                    state.pins.contains_key(&gpio_num) && ATTRIBUTES.contains(&name)
+                }
+                None => false,
+            },
+        }
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> io::Result<()> {
+        let data = str::from_utf8(data).map_err(|_| errno(libc::EINVAL))?;
+        let mut state = self.state();
+        match path.trim_matches('/') {
+            "export" => state.export(data),
+            "unexport" => state.unexport(data),
+            path => match split_path(path) {
+                Some((gpio_num, Some(name))) => state.write_attribute(gpio_num, name, data),
+                Some((_, None)) => Err(errno(libc::EISDIR)),
+                None => Err(errno(libc::ENOENT)),
+            },
+        }
+    }
+
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        let state = self.state();
+        match path.trim_matches('/') {
+            // write-only
+            "export" | "unexport" => Err(errno(libc::EACCES)),
+            CHIP => Err(errno(libc::EISDIR)),
+            path if path.starts_with(CHIP) => chip_path(path)
+                .and_then(|name| state.chip_attribute(name))
+                .map(|content| (content + "\n").into_bytes())
+                .ok_or_else(|| errno(libc::ENOENT)),
+            path => match split_path(path) {
+                Some((gpio_num, Some(name))) => state
+                    .attribute(gpio_num, name)
+                    .map(|content| (content + "\n").into_bytes())
+                    .ok_or_else(|| errno(libc::ENOENT)),
+                Some((_, None)) => Err(errno(libc::EISDIR)),
+                None => Err(errno(libc::ENOENT)),
+            },
+        }
+    }
+
+    fn open_value(&self, gpio_num: u16, _writable: bool) -> io::Result<Box<dyn SysFsValue>> {
+        let mut state = self.state();
+        let pin = state
+            .pins
+            .get_mut(&gpio_num)
+            .ok_or_else(|| errno(libc::ENOENT))?;
+
+        let pipe = sync::Arc::new(Pipe::new()?);
+        pipe.notify();
+        pin.watchers.push(sync::Arc::downgrade(&pipe));
+
+        Ok(Box::new(FakeValue {
+            tree: self.clone(),
+            gpio_num,
+            id: pin.id,
+            pipe,
+        }))
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        let state = self.state();
+        let fixed = ["export", "unexport", CHIP].iter().map(|&name| name.to_owned());
+        let pins = state.pins.keys().map(|gpio_num| format!("gpio{}", gpio_num));
+        Ok(fixed.chain(pins).collect())
+    }
+}
+
+/// An open value file of a `FakeSysFs`
+#[derive(Debug)]
+struct FakeValue {
+    tree: FakeSysFs,
+    gpio_num: u16,
+    id: usize,
+    pipe: sync::Arc<Pipe>,
+}
+
+impl FakeValue {
+    /// Lock the tree, failing if the pin has been unexported since the file was opened
+    fn state(&self) -> io::Result<sync::MutexGuard<'_, State>> {
+        let state = self.tree.state();
+        match state.pins.get(&self.gpio_num) {
+            Some(pin) if pin.id == self.id => Ok(state),
+            _ => Err(errno(libc::ENODEV)),
+        }
+    }
+}
+
+impl SysFsValue for FakeValue {
+    fn read_value(&mut self) -> io::Result<u8> {
+        let state = self.state()?;
+        self.pipe.drain();
+        Ok(match state.value(self.gpio_num) {
+            GpioValue::Low => b'0',
+            GpioValue::High => b'1',
+        })
+    }
+
+    fn write_value(&mut self, data: &[u8]) -> io::Result<()> {
+        let data = str::from_utf8(data).map_err(|_| errno(libc::EINVAL))?;
+        self.state()?.write_value(self.gpio_num, data)
+    }
+
+    #[inline]
+    fn poll_fd(&self) -> RawFd {
+        self.pipe.read
+    }
+
+    #[inline]
+    fn poll_events(&self) -> EpollFlags {
+        EpollFlags::EPOLLIN | EpollFlags::EPOLLET
+    }
+}
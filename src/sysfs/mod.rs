@@ -0,0 +1,724 @@
+//! Linux `/sys`-fs based GPIO control
+//!
+//! Uses the [Linux GPIO Sysfs](https://www.kernel.org/doc/Documentation/gpio/sysfs.txt) filesystem
+//! operations to control GPIO ports. It tries to reduce the otherwise hefty syscall overhead
+//! by keeping the sysfs files open, instead of reopening them on each read.
+//!
+//! Every `open` call to a GPIO pin will automatically export the necessary pin and unexport it
+//! on close.
+//!
+//! Pins exported and configured by someone else, e.g. a boot script, are used as they are by the
+//! `attach` constructors: they only open the `value` attribute, fail if the pin has the wrong
+//! direction and leave the pin exported on close. `inspect` reads a pin's configuration.
+//!
+//! Pins are opened in the kernel's tree at `/sys/class/gpio` by default. The `open_at`
+//! constructors accept any `SysFsTree` instead, e.g. a `KernelTree` with a different root or,
+//! with the `fake-sysfs` feature enabled, a simulated tree from the `fake` module.
+//!
+//! Kernel numbers depend on the order GPIO controllers are registered in. The `open_line`
+//! constructors identify a pin by the label of its controller and its offset instead, see the
+//! `chips` module.
+
+use nix;
+use nix::sys::epoll::{self, EpollEvent, EpollFlags, EpollOp};
+use std::{cell, fmt, fs, io, path};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use super::{GpioEdge, GpioIn, GpioOut, GpioValue};
+
+pub mod chips;
+#[cfg(feature = "fake-sysfs")]
+pub mod fake;
+
+/// The direction of a pin, as in its `direction` attribute
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GpioDirection {
+    Input,
+    Output,
+}
+
+impl fmt::Display for GpioDirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            GpioDirection::Input => "input",
+            GpioDirection::Output => "output",
+        })
+    }
+}
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum GpioError {
+        Io(err: io::Error) {
+            from()
+            description("io error")
+            display("I/O error: {}", err)
+            cause(err)
+        }
+        Epoll(err: nix::Error) {
+            from()
+            description("epoll error")
+            display("Epoll error: {}", err)
+            cause(err)
+        }
+        EpollEventCount(count: usize) {
+            description("epoll_wait returned unexpected event count value")
+            display("epoll_wait returned unexpected event count value: {}", count)
+        }
+        EpollDataValue(val: u64) {
+            description("epoll_wait returned unexpected data value")
+            display("epoll_wait returned unexpected data value: {}", val)
+        }
+        /// No chip has the label
+        UnknownChip(label: String) {
+            description("no GPIO chip with the label")
+            display("no GPIO chip labeled {:?}", label)
+        }
+        /// Several chips have the label, so it does not identify a chip
+        AmbiguousChip(label: String) {
+            description("several GPIO chips with the label")
+            display("several GPIO chips labeled {:?}", label)
+        }
+        /// The chip has no line at the offset
+        InvalidOffset(label: String, offset: u16) {
+            description("offset out of the range of the GPIO chip")
+            display("GPIO chip {:?} has no line {}", label, offset)
+        }
+        /// An attribute of a chip could not be parsed
+        InvalidChip(name: String) {
+            description("GPIO chip with invalid attributes")
+            display("GPIO chip {} has invalid attributes", name)
+        }
+        /// The pin is not exported, so there is nothing to attach to or inspect
+        NotExported(gpio_num: u16) {
+            description("pin not exported")
+            display("GPIO {} is not exported", gpio_num)
+        }
+        /// An attached pin is configured with the other direction
+        WrongDirection(gpio_num: u16, direction: GpioDirection) {
+            description("pin configured with the other direction")
+            display("GPIO {} is configured as {}", gpio_num, direction)
+        }
+        /// An attribute of an exported pin has unexpected content
+        InvalidAttribute(path: String, content: String) {
+            description("invalid attribute content")
+            display("attribute {} has unexpected content {:?}", path, content)
+        }
+        InvalidData(val: u8) {
+            description("read a value that was neither '0' nor '1' from Linux sysfs GPIO interface")
+            display("read value {:?} from Linux sysfs GPIO interface, which is neither '0' nor '1'",
+                    val)
+        }
+    }
+}
+
+pub type GpioResult<T> = Result<T, GpioError>;
+
+/// Access to a tree of GPIO sysfs attributes
+///
+/// Paths are relative to the root of the tree, e.g. `export` or `gpio17/direction`. The kernel's
+/// tree is accessed through `KernelTree`, the `fake` module provides a simulated one for tests.
+pub trait SysFsTree: fmt::Debug + Send + Sync {
+    /// Whether `path` exists
+    fn exists(&self, path: &str) -> bool;
+
+    /// Write `data` to the attribute at `path`
+    fn write(&self, path: &str, data: &[u8]) -> io::Result<()>;
+
+    /// Read the attribute at `path`
+    fn read(&self, path: &str) -> io::Result<Vec<u8>>;
+
+    /// Open the `value` attribute of an exported pin, for writing if `writable`
+    fn open_value(&self, gpio_num: u16, writable: bool) -> io::Result<Box<dyn SysFsValue>>;
+
+    /// The names of the entries at the root of the tree, e.g. `export` and `gpiochip0`
+    ///
+    /// Fails with `ErrorKind::Unsupported` unless implemented.
+    fn list(&self) -> io::Result<Vec<String>> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "tree cannot be listed"))
+    }
+}
+
+/// An open `value` attribute
+pub trait SysFsValue: fmt::Debug + Send {
+    /// Read the first byte of the attribute
+    fn read_value(&mut self) -> io::Result<u8>;
+
+    /// Write `data` to the attribute
+    fn write_value(&mut self, data: &[u8]) -> io::Result<()>;
+
+    /// File descriptor to register with epoll to wait for edges
+    fn poll_fd(&self) -> RawFd;
+
+    /// Events to wait for on `poll_fd`
+    #[inline]
+    fn poll_events(&self) -> EpollFlags {
+        EpollFlags::EPOLLPRI | EpollFlags::EPOLLET
+    }
+}
+
+/// The kernel's GPIO sysfs interface
+#[derive(Clone, Debug)]
+pub struct KernelTree {
+    root: path::PathBuf,
+}
+
+impl KernelTree {
+    /// The tree at `/sys/class/gpio`
+    #[inline]
+    pub fn new() -> KernelTree {
+        Self::at("/sys/class/gpio")
+    }
+
+    /// The tree at `root`
+    #[inline]
+    pub fn at<P: Into<path::PathBuf>>(root: P) -> KernelTree {
+        KernelTree { root: root.into() }
+    }
+
+    /// The root of the tree
+    #[inline]
+    pub fn root(&self) -> &path::Path {
+        &self.root
+    }
+}
+
+impl Default for KernelTree {
+    #[inline]
+    fn default() -> KernelTree {
+        KernelTree::new()
+    }
+}
+
+impl SysFsTree for KernelTree {
+    #[inline]
+    fn exists(&self, path: &str) -> bool {
+        fs::metadata(self.root.join(path)).is_ok()
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> io::Result<()> {
+        fs::OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(self.root.join(path))?
+            .write_all(data)
+    }
+
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.root.join(path))
+    }
+
+    fn open_value(&self, gpio_num: u16, writable: bool) -> io::Result<Box<dyn SysFsValue>> {
+        let file = fs::OpenOptions::new()
+            .read(!writable)
+            .write(writable)
+            .open(self.root.join(format!("gpio{}/value", gpio_num)))?;
+        Ok(Box::new(KernelValue(file)))
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        fs::read_dir(&self.root)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct KernelValue(fs::File);
+
+impl SysFsValue for KernelValue {
+    fn read_value(&mut self) -> io::Result<u8> {
+        let mut buf: [u8; 1] = [0; 1];
+
+        // we rewind the file descriptor first, otherwise read will fail
+        self.0.seek(SeekFrom::Start(0))?;
+
+        // we read one byte, the trailing byte is a newline
+        self.0.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    #[inline]
+    fn write_value(&mut self, data: &[u8]) -> io::Result<()> {
+        self.0.write_all(data)
+    }
+
+    #[inline]
+    fn poll_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+#[inline]
+fn export_gpio_if_unexported(tree: &dyn SysFsTree, gpio_num: u16) -> GpioResult<()> {
+    // export port first if not exported
+    if !tree.exists(&format!("gpio{}", gpio_num)) {
+        tree.write("export", format!("{}", gpio_num).as_bytes())?;
+    }
+
+    // ensure we're using '0' as low
+    tree.write(&format!("gpio{}/active_low", gpio_num), b"0")?;
+    Ok(())
+}
+
+#[inline]
+/// Set the direction of a pin, starting an output at level `initial` rather than low
+fn set_gpio_direction(
+    tree: &dyn SysFsTree,
+    gpio_num: u16,
+    direction: GpioDirection,
+    initial: Option<GpioValue>,
+) -> GpioResult<()> {
+    tree.write(
+        &format!("gpio{}/direction", gpio_num),
+        match (direction, initial) {
+            (GpioDirection::Input, _) => "in",
+            (GpioDirection::Output, None) => "out",
+            (GpioDirection::Output, Some(GpioValue::Low)) => "low",
+            (GpioDirection::Output, Some(GpioValue::High)) => "high",
+        }
+        .as_bytes(),
+    )?;
+    Ok(())
+}
+
+#[inline]
+fn open_gpio(
+    tree: &dyn SysFsTree,
+    gpio_num: u16,
+    direction: GpioDirection,
+) -> GpioResult<Box<dyn SysFsValue>> {
+    Ok(tree.open_value(gpio_num, direction == GpioDirection::Output)?)
+}
+
+/// The configuration of an exported pin, as read from its attributes
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PinInfo {
+    /// The kernel number of the pin
+    pub gpio_num: u16,
+    /// The direction of the pin
+    pub direction: GpioDirection,
+    /// The edges signalled to waiting readers, `None` for outputs
+    pub edge: GpioEdge,
+    /// Whether the kernel inverts the pin's values
+    pub active_low: bool,
+    /// The current value, with `active_low` applied
+    pub value: GpioValue,
+}
+
+/// Read the configuration of exported pin `gpio_num`
+#[inline]
+pub fn inspect(gpio_num: u16) -> GpioResult<PinInfo> {
+    inspect_at(&KernelTree::new(), gpio_num)
+}
+
+/// The value of attribute `name` of pin `gpio_num`, one of the `values` by content
+fn read_attribute<T: Copy>(
+    tree: &dyn SysFsTree,
+    gpio_num: u16,
+    name: &str,
+    values: &[(&str, T)],
+) -> GpioResult<T> {
+    let path = format!("gpio{}/{}", gpio_num, name);
+    let data = tree.read(&path)?;
+    let content = String::from_utf8_lossy(&data).trim_end_matches('\n').to_owned();
+    match values.iter().find(|&&(text, _)| text == content) {
+        Some(&(_, value)) => Ok(value),
+        None => Err(GpioError::InvalidAttribute(path, content)),
+    }
+}
+
+/// Read the configuration of exported pin `gpio_num` in `tree`, without changing it
+pub fn inspect_at(tree: &dyn SysFsTree, gpio_num: u16) -> GpioResult<PinInfo> {
+    if !tree.exists(&format!("gpio{}", gpio_num)) {
+        return Err(GpioError::NotExported(gpio_num));
+    }
+    let direction = read_attribute(
+        tree,
+        gpio_num,
+        "direction",
+        &[("in", GpioDirection::Input), ("out", GpioDirection::Output)],
+    )?;
+    // pins that cannot signal edges have no edge attribute
+    let edge = if tree.exists(&format!("gpio{}/edge", gpio_num)) {
+        read_attribute(
+            tree,
+            gpio_num,
+            "edge",
+            &[
+                ("none", GpioEdge::None),
+                ("rising", GpioEdge::Rising),
+                ("falling", GpioEdge::Falling),
+                ("both", GpioEdge::Both),
+            ],
+        )?
+    } else {
+        GpioEdge::None
+    };
+    let active_low = read_attribute(tree, gpio_num, "active_low", &[("0", false), ("1", true)])?;
+    let value = read_attribute(
+        tree,
+        gpio_num,
+        "value",
+        &[("0", GpioValue::Low), ("1", GpioValue::High)],
+    )?;
+
+    Ok(PinInfo {
+        gpio_num,
+        direction,
+        edge,
+        active_low,
+        value,
+    })
+}
+
+#[derive(Debug)]
+struct SysFsGpio {
+    gpio_num: u16,
+    tree: Box<dyn SysFsTree>,
+    sysfp: cell::RefCell<Box<dyn SysFsValue>>,
+    /// Whether the pin was exported by someone else, who is left to unexport it
+    attached: bool,
+}
+
+impl SysFsGpio {
+    fn open(
+        tree: Box<dyn SysFsTree>,
+        gpio_num: u16,
+        direction: GpioDirection,
+        initial: Option<GpioValue>,
+    ) -> GpioResult<SysFsGpio> {
+        export_gpio_if_unexported(&*tree, gpio_num)?;
+
+        // ensure we're using '0' as low.
+        // FIXME: this should be configurable
+        tree.write(&format!("gpio{}/active_low", gpio_num), b"0")?;
+
+        set_gpio_direction(&*tree, gpio_num, direction, initial)?;
+
+        // finally, we can open the device
+        Ok(SysFsGpio {
+            gpio_num,
+            sysfp: cell::RefCell::new(open_gpio(&*tree, gpio_num, direction)?),
+            tree,
+            attached: false,
+        })
+    }
+
+    /// Use an exported pin as it is configured, only opening its value file
+    fn attach(
+        tree: Box<dyn SysFsTree>,
+        gpio_num: u16,
+        direction: GpioDirection,
+    ) -> GpioResult<SysFsGpio> {
+        let info = inspect_at(&*tree, gpio_num)?;
+        if info.direction != direction {
+            return Err(GpioError::WrongDirection(gpio_num, info.direction));
+        }
+        Ok(SysFsGpio {
+            gpio_num,
+            sysfp: cell::RefCell::new(open_gpio(&*tree, gpio_num, direction)?),
+            tree,
+            attached: true,
+        })
+    }
+
+    #[inline]
+    fn set_direction(&mut self, direction: GpioDirection) -> GpioResult<()> {
+        set_gpio_direction(&*self.tree, self.gpio_num, direction, None)?;
+        self.sysfp = cell::RefCell::new(open_gpio(&*self.tree, self.gpio_num, direction)?);
+
+        Ok(())
+    }
+}
+
+impl Drop for SysFsGpio {
+    #[inline]
+    fn drop(&mut self) {
+        // attached pins stay exported for whoever exported them
+        if self.attached {
+            return;
+        }
+
+        // unexport the pin, if we have not done so already
+        // best effort, failures are ignored
+        self.tree
+            .write("unexport", format!("{}\n", self.gpio_num).as_bytes())
+            .ok();
+    }
+}
+
+/// `/sys`-fs based GPIO output
+#[derive(Debug)]
+pub struct SysFsGpioOutput {
+    gpio: SysFsGpio,
+}
+
+impl SysFsGpioOutput {
+    /// Open a GPIO port for Output.
+    #[inline]
+    pub fn open(gpio_num: u16) -> GpioResult<SysFsGpioOutput> {
+        Self::open_at(KernelTree::new(), gpio_num)
+    }
+
+    /// Open a GPIO port for output in `tree`
+    #[inline]
+    pub fn open_at<T: SysFsTree + 'static>(tree: T, gpio_num: u16) -> GpioResult<SysFsGpioOutput> {
+        Ok(SysFsGpioOutput {
+            gpio: SysFsGpio::open(Box::new(tree), gpio_num, GpioDirection::Output, None)?,
+        })
+    }
+
+    /// Open a GPIO port for output, driving it with `value` from the start
+    ///
+    /// `open` drives the pin low until a value is set, this sets the direction and the value in
+    /// a single write.
+    #[inline]
+    pub fn open_with_value<V: Into<GpioValue>>(
+        gpio_num: u16,
+        value: V,
+    ) -> GpioResult<SysFsGpioOutput> {
+        Self::open_with_value_at(KernelTree::new(), gpio_num, value)
+    }
+
+    /// Open a GPIO port for output in `tree`, driving it with `value` from the start
+    #[inline]
+    pub fn open_with_value_at<T: SysFsTree + 'static, V: Into<GpioValue>>(
+        tree: T,
+        gpio_num: u16,
+        value: V,
+    ) -> GpioResult<SysFsGpioOutput> {
+        let value = Some(value.into());
+        Ok(SysFsGpioOutput {
+            gpio: SysFsGpio::open(Box::new(tree), gpio_num, GpioDirection::Output, value)?,
+        })
+    }
+
+    /// Open line `offset` of the GPIO chip labeled `label` for output
+    #[inline]
+    pub fn open_line(label: &str, offset: u16) -> GpioResult<SysFsGpioOutput> {
+        Self::open_line_at(KernelTree::new(), label, offset)
+    }
+
+    /// Open line `offset` of the GPIO chip labeled `label` for output in `tree`
+    pub fn open_line_at<T: SysFsTree + 'static>(
+        tree: T,
+        label: &str,
+        offset: u16,
+    ) -> GpioResult<SysFsGpioOutput> {
+        let gpio_num = chips::find_at(&tree, label)?.gpio_num(offset)?;
+        Self::open_at(tree, gpio_num)
+    }
+
+    /// Use exported output `gpio_num` without reconfiguring it or unexporting it on drop
+    ///
+    /// Values are written with the pin's `active_low` setting applied by the kernel.
+    #[inline]
+    pub fn attach(gpio_num: u16) -> GpioResult<SysFsGpioOutput> {
+        Self::attach_at(KernelTree::new(), gpio_num)
+    }
+
+    /// Use exported output `gpio_num` in `tree` without reconfiguring it
+    #[inline]
+    pub fn attach_at<T: SysFsTree + 'static>(
+        tree: T,
+        gpio_num: u16,
+    ) -> GpioResult<SysFsGpioOutput> {
+        Ok(SysFsGpioOutput {
+            gpio: SysFsGpio::attach(Box::new(tree), gpio_num, GpioDirection::Output)?,
+        })
+    }
+
+    /// The current configuration of the pin
+    #[inline]
+    pub fn info(&self) -> GpioResult<PinInfo> {
+        inspect_at(&*self.gpio.tree, self.gpio.gpio_num)
+    }
+
+    #[inline]
+    pub fn into_input(mut self) -> GpioResult<SysFsGpioInput> {
+        self.gpio.set_direction(GpioDirection::Input)?;
+        SysFsGpioInput::from_gpio(self.gpio)
+    }
+
+    #[inline]
+    pub fn gpio_num(&self) -> u16 {
+        self.gpio.gpio_num
+    }
+}
+
+impl GpioOut for SysFsGpioOutput {
+    type Error = GpioError;
+
+    #[inline]
+    fn set_low(&mut self) -> GpioResult<()> {
+        self.gpio.sysfp.get_mut().write_value(b"0")?;
+        Ok(())
+    }
+
+    #[inline]
+    fn set_high(&mut self) -> GpioResult<()> {
+        self.gpio.sysfp.get_mut().write_value(b"1")?;
+        Ok(())
+    }
+}
+
+/// `/sys`-fs based GPIO output
+#[derive(Debug)]
+pub struct SysFsGpioInput {
+    gpio: SysFsGpio,
+}
+
+impl SysFsGpioInput {
+    /// Open a GPIO port for Output.
+    #[inline]
+    pub fn open(gpio_num: u16) -> GpioResult<SysFsGpioInput> {
+        Self::open_at(KernelTree::new(), gpio_num)
+    }
+
+    /// Open a GPIO port for input in `tree`
+    #[inline]
+    pub fn open_at<T: SysFsTree + 'static>(tree: T, gpio_num: u16) -> GpioResult<SysFsGpioInput> {
+        Self::from_gpio(SysFsGpio::open(Box::new(tree), gpio_num, GpioDirection::Input, None)?)
+    }
+
+    /// Open line `offset` of the GPIO chip labeled `label` for input
+    #[inline]
+    pub fn open_line(label: &str, offset: u16) -> GpioResult<SysFsGpioInput> {
+        Self::open_line_at(KernelTree::new(), label, offset)
+    }
+
+    /// Open line `offset` of the GPIO chip labeled `label` for input in `tree`
+    pub fn open_line_at<T: SysFsTree + 'static>(
+        tree: T,
+        label: &str,
+        offset: u16,
+    ) -> GpioResult<SysFsGpioInput> {
+        let gpio_num = chips::find_at(&tree, label)?.gpio_num(offset)?;
+        Self::open_at(tree, gpio_num)
+    }
+
+    /// Use exported input `gpio_num` without reconfiguring it or unexporting it on drop
+    ///
+    /// Values are read with the pin's `active_low` setting applied by the kernel, edges are
+    /// signalled as configured until `set_edge` is called.
+    #[inline]
+    pub fn attach(gpio_num: u16) -> GpioResult<SysFsGpioInput> {
+        Self::attach_at(KernelTree::new(), gpio_num)
+    }
+
+    /// Use exported input `gpio_num` in `tree` without reconfiguring it
+    #[inline]
+    pub fn attach_at<T: SysFsTree + 'static>(tree: T, gpio_num: u16) -> GpioResult<SysFsGpioInput> {
+        Self::from_gpio(SysFsGpio::attach(Box::new(tree), gpio_num, GpioDirection::Input)?)
+    }
+
+    /// The current configuration of the pin
+    #[inline]
+    pub fn info(&self) -> GpioResult<PinInfo> {
+        inspect_at(&*self.gpio.tree, self.gpio.gpio_num)
+    }
+
+    #[inline]
+    fn from_gpio(gpio: SysFsGpio) -> GpioResult<SysFsGpioInput> {
+        Ok(SysFsGpioInput { gpio })
+    }
+
+    #[inline]
+    pub fn into_output(mut self) -> GpioResult<SysFsGpioOutput> {
+        self.gpio.set_direction(GpioDirection::Output)?;
+        Ok(SysFsGpioOutput { gpio: self.gpio })
+    }
+
+    #[inline]
+    pub fn gpio_num(&self) -> u16 {
+        self.gpio.gpio_num
+    }
+}
+
+impl GpioIn for SysFsGpioInput {
+    type Error = GpioError;
+
+    #[inline]
+    fn read_value(&self) -> Result<GpioValue, Self::Error> {
+        match self.gpio.sysfp.borrow_mut().read_value()? {
+            b'0' => Ok(GpioValue::Low),
+            b'1' => Ok(GpioValue::High),
+            val => Err(GpioError::InvalidData(val)),
+        }
+    }
+
+    fn set_edge(&mut self, edge: GpioEdge) -> Result<(), Self::Error> {
+        self.gpio.tree.write(
+            &format!("gpio{}/edge", self.gpio.gpio_num),
+            match edge {
+                GpioEdge::None => b"none",
+                GpioEdge::Rising => b"rising",
+                GpioEdge::Falling => b"falling",
+                GpioEdge::Both => b"both",
+            },
+        )?;
+        Ok(())
+    }
+}
+
+pub struct SysFsGpioEdgeIter<'a> {
+    /// The timeout, if any.
+    timeout: Option<u64>,
+    /// The GPIO devices whose edges will be included in this iterator.
+    devs: Vec<&'a SysFsGpioInput>,
+    /// The file descriptor of the epoll instance.
+    epoll_fd: RawFd,
+}
+
+impl<'a> SysFsGpioEdgeIter<'a> {
+    pub fn new() -> GpioResult<SysFsGpioEdgeIter<'a>> {
+        let epoll_fd = epoll::epoll_create()?;
+        Ok(SysFsGpioEdgeIter {
+            timeout: None,
+            devs: Vec::new(),
+            epoll_fd,
+        })
+    }
+
+    pub fn timeout_ms(&mut self, timeout_ms: u64) -> &mut Self {
+        self.timeout = Some(timeout_ms);
+        self
+    }
+
+    pub fn add(&mut self, dev: &'a SysFsGpioInput) -> GpioResult<&mut Self> {
+        // We use the device's index in the `devs` vector as the data registered with epoll.
+        let index = self.devs.len() as u64;
+        let (flags, dev_fd) = {
+            let value = dev.gpio.sysfp.borrow();
+            (value.poll_events(), value.poll_fd())
+        };
+        let mut event = EpollEvent::new(flags, index);
+        epoll::epoll_ctl(self.epoll_fd, EpollOp::EpollCtlAdd, dev_fd, &mut event)?;
+        self.devs.push(dev);
+        Ok(self)
+    }
+
+    fn get_next(&mut self) -> GpioResult<&'a SysFsGpioInput> {
+        let timeout = self.timeout.map_or(isize::MAX, |t| t as isize);
+        // A dummy event, to be overwritten by `epoll`.
+        let mut events = [EpollEvent::empty()];
+        let event_count = epoll::epoll_wait(self.epoll_fd, &mut events, timeout)?;
+        if event_count != 1 {
+            return Err(GpioError::EpollEventCount(event_count));
+        }
+        // Epoll wrote the event data into the array. We used the device's index as the data:
+        self.devs
+            .get(events[0].data() as usize)
+            .copied()
+            .ok_or_else(|| GpioError::EpollDataValue(events[0].data()))
+    }
+}
+
+impl<'a> Iterator for SysFsGpioEdgeIter<'a> {
+    type Item = GpioResult<&'a SysFsGpioInput>;
+
+    fn next(&mut self) -> Option<GpioResult<&'a SysFsGpioInput>> {
+        Some(self.get_next())
+    }
+}